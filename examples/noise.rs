@@ -2,16 +2,27 @@ extern crate image;
 extern crate rand;
 extern crate rrt;
 
-use image::{GenericImage, ImageBuffer};
+use image::ImageBuffer;
 
 use rrt::*;
-use rrt::noise;
+use rrt::noise::*;
 use std::fs::File;
 
 fn main() {
-    let img = ImageBuffer::from_fn(500, 500, |x, y| {
-        let n = noise::perlin_noise(&vec3(x as f32 / 32.0, y as f32 / 32.0, 0.0));
-        image::Rgb::from(Rgb::white() * ((n + 1.0) / 2.0))
+    let fbm = Fractal::fbm(Simplex::new(1), 6);
+    let ridged = Fractal::ridged(Perlin::new(2), 6);
+    let cells = Worley::new(3, WorleyFeature::F2MinusF1);
+    let turbulence = Fractal::turbulence(Perlin::new(4), 6);
+    //四个象限分别是单纯形 fBm、Perlin 山脊、Worley 细胞和 Perlin 湍流。
+    let img = ImageBuffer::from_fn(512, 512, |x, y| {
+        let p = vec2(x as f32, y as f32) / 64.0;
+        let n = match (x < 256, y < 256) {
+            (true, true) => (fbm.noise2(p) + 1.0) / 2.0,
+            (false, true) => ridged.noise2(p),
+            (true, false) => cells.noise2(p),
+            (false, false) => turbulence.noise2(p),
+        };
+        image::Rgb::from(Rgb::white() * n.clamp(0.0, 1.0))
     });
 
     let mut out = File::create("noise.png").unwrap();
@@ -0,0 +1,85 @@
+extern crate image;
+extern crate rrt;
+
+use rrt::film::{Film, MitchellFilter};
+use rrt::integrator::PathTracer;
+use rrt::light::PointLight;
+use rrt::material::{Dielectric, Emissive, Mirror};
+use rrt::render::Renderer;
+use rrt::tonemap::{ToneMap, ToneMapper};
+use rrt::*;
+use std::f32;
+use std::fs::File;
+use std::io::BufWriter;
+
+const WIDTH: u32 = 300;
+const HEIGHT: u32 = 200;
+const SAMPLE_COUNT: u32 = 64;
+
+fn main() {
+    let camera = CameraBuilder {
+        lens: ThinLens {
+            radius: 0.0,
+            center: vec3(0.0, 1.0, 4.0),
+            focal_length: 1.0,
+        },
+        at: vec3(0.0, 1.0, 4.0),
+        target: vec3(0.0, 0.5, 0.0),
+        up: Vector3::unit_y(),
+        aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+        fov: f32::consts::PI / 8.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    }.build();
+
+    let mut mirror = pure_color_shape(
+        Rgb::new(0.9, 0.9, 0.9),
+        Sphere::new(vec3(-1.1, 0.5, 0.0), 0.5),
+    );
+    mirror.material = Box::new(Mirror);
+    let mut glass = pure_color_shape(Rgb::white(), Sphere::new(vec3(1.1, 0.5, 0.0), 0.5));
+    glass.material = Box::new(Dielectric { ior: 1.5 });
+    let mut lamp = pure_color_shape(Rgb::white(), Sphere::new(vec3(0.0, 3.0, 0.0), 0.5));
+    lamp.material = Box::new(Emissive { intensity: 8.0 });
+
+    let shapes = vec![
+        pure_color_shape(
+            Rgb::new(0.8, 0.8, 0.6),
+            Sphere::new(vec3(0.0, -1000.0, 0.0), 1000.0),
+        ),
+        pure_color_shape(Rgb::new(0.7, 0.2, 0.2), Sphere::new(vec3(0.0, 0.5, 0.0), 0.5)),
+        mirror,
+        glass,
+        lamp,
+    ];
+    let lights: Vec<Box<dyn light::Light>> = vec![Box::new(PointLight {
+        pos: vec3(3.0, 4.0, 3.0),
+        intensity: Rgb::new(10.0, 10.0, 10.0),
+    })];
+    let scene = Scene::new(camera, shapes, lights);
+
+    let mut film = Film::new(
+        WIDTH,
+        HEIGHT,
+        Box::new(MitchellFilter {
+            radius: vec2(2.0, 2.0),
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }),
+    );
+    let renderer = Renderer {
+        spp: SAMPLE_COUNT,
+        ..Renderer::default()
+    };
+    renderer.render(&scene, &PathTracer::default(), &mut film);
+    let pixels = film.resolve();
+    let tone_mapper = ToneMapper {
+        exposure: 0.5,
+        operator: ToneMap::Aces,
+    };
+    let mut out = File::create("path_tracing.png").unwrap();
+    let img = tone_mapper.to_image(WIDTH, HEIGHT, &pixels);
+    image::ImageRgb8(img).save(&mut out, image::PNG).unwrap();
+    let mut out = BufWriter::new(File::create("path_tracing.hdr").unwrap());
+    hdr::write_hdr(&mut out, WIDTH, HEIGHT, &pixels).unwrap();
+}
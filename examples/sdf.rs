@@ -0,0 +1,95 @@
+extern crate image;
+extern crate rrt;
+
+use rrt::film::{Film, MitchellFilter};
+use rrt::integrator::PathTracer;
+use rrt::light::PointLight;
+use rrt::render::Renderer;
+use rrt::shapes::sdf::{SdfBox, SdfSphere, SdfTorus};
+use rrt::tonemap::{ToneMap, ToneMapper};
+use rrt::*;
+use std::f32;
+use std::fs::File;
+
+const WIDTH: u32 = 300;
+const HEIGHT: u32 = 200;
+const SAMPLE_COUNT: u32 = 64;
+
+fn main() {
+    let camera = CameraBuilder {
+        lens: ThinLens {
+            radius: 0.0,
+            center: vec3(0.0, 1.5, 4.0),
+            focal_length: 1.0,
+        },
+        at: vec3(0.0, 1.5, 4.0),
+        target: vec3(0.0, 0.5, 0.0),
+        up: Vector3::unit_y(),
+        aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+        fov: f32::consts::PI / 8.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    }.build();
+
+    //从左到右：挖掉一个球的圆角方块、与球平滑融合的圆环、加了噪声位移的球。
+    let carved = SdfBox {
+        center: vec3(-0.8, 0.4, 0.0),
+        half_size: vec3(0.4, 0.4, 0.4),
+        rounding: 0.05,
+    }.difference(SdfSphere {
+        center: vec3(-0.8, 0.4, 0.0),
+        radius: 0.5,
+    });
+    let blob = SdfTorus {
+        center: vec3(0.0, 0.15, 0.0),
+        major_radius: 0.4,
+        minor_radius: 0.15,
+    }.smooth_union(
+        SdfSphere {
+            center: vec3(0.0, 0.5, 0.0),
+            radius: 0.3,
+        },
+        0.3,
+    );
+    let rock = SdfSphere {
+        center: vec3(0.9, 0.4, 0.0),
+        radius: 0.4,
+    }.displace(0.05, 6.0);
+
+    let shapes = vec![
+        pure_color_shape(
+            Rgb::new(0.8, 0.8, 0.6),
+            Plane::new(Vector3::zero(), Vector3::unit_y()),
+        ),
+        pure_color_shape(Rgb::new(0.7, 0.2, 0.2), SdfShape::new(carved)),
+        pure_color_shape(Rgb::new(0.2, 0.5, 0.8), SdfShape::new(blob)),
+        pure_color_shape(Rgb::new(0.5, 0.5, 0.5), SdfShape::new(rock)),
+    ];
+    let lights: Vec<Box<dyn light::Light>> = vec![Box::new(PointLight {
+        pos: vec3(3.0, 4.0, 3.0),
+        intensity: Rgb::new(15.0, 15.0, 15.0),
+    })];
+    let scene = Scene::new(camera, shapes, lights);
+
+    let mut film = Film::new(
+        WIDTH,
+        HEIGHT,
+        Box::new(MitchellFilter {
+            radius: vec2(2.0, 2.0),
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }),
+    );
+    let renderer = Renderer {
+        spp: SAMPLE_COUNT,
+        ..Renderer::default()
+    };
+    renderer.render(&scene, &PathTracer::default(), &mut film);
+    let tone_mapper = ToneMapper {
+        exposure: 0.0,
+        operator: ToneMap::Aces,
+    };
+    let img = tone_mapper.to_image(WIDTH, HEIGHT, &film.resolve());
+    let mut out = File::create("sdf.png").unwrap();
+    image::ImageRgb8(img).save(&mut out, image::PNG).unwrap();
+}
@@ -1,53 +1,479 @@
-use math::{Vector3, Zero};
-use shapes::Ray;
-use std::iter::*;
+use math::{vec3, Affine, AnimatedTransform, ElementWise, InnerSpace, Vector3};
+use shapes::{HitRecord, Ray};
 use std::f32;
 
 #[derive(Clone, Copy, Debug)]
-struct BBox {
+pub struct BBox {
     pub min: Vector3,
     pub max: Vector3,
 }
 
 impl BBox {
-    pub fn ray_intersect(&self, ray: &Ray, tmin: f32, tmax: f32) -> bool {
-        let mut min_max: [Vector3; 2] = [Vector3::zero(), Vector3::zero()];
-        let intersect = |i, x| (x - ray.origin[i]) / ray.direction[i];
-        let intersect_min = |i| intersect(i, self.min[i]);
-        let intersect_max = |i| intersect(i, self.max[i]);
+    pub fn new(min: Vector3, max: Vector3) -> BBox {
+        BBox { min, max }
+    }
+
+    /// 不包含任何点的包围盒，作为 `union` 的单位元。
+    pub fn empty() -> BBox {
+        BBox {
+            min: vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    /// 包含整个空间，用于无限大的形状。
+    pub fn infinite() -> BBox {
+        BBox {
+            min: vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            max: vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    /// 是否有界，空包围盒也算有界。
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|i| {
+            self.min[i] > self.max[i] || (self.min[i].is_finite() && self.max[i].is_finite())
+        })
+    }
+
+    pub fn from_point(point: Vector3) -> BBox {
+        BBox {
+            min: point,
+            max: point,
+        }
+    }
+
+    pub fn union(&self, other: &BBox) -> BBox {
+        BBox {
+            min: vec3(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: vec3(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
 
-        for i in 0..3 {
-            min_max[ray.neg[i] as usize][i] = intersect_min(i);
-            min_max[1 - ray.neg[i] as usize][i] = intersect_max(i);
-            let left = min_max[0][i];
-            let right = min_max[1][i];
-            //这里用 !(x > y) 是为了糊 NaN，下面 fold 中劳什子类似。
-            assert!(!(left > right));
+    /// 两个包围盒的公共部分，不相交时 `min` 会大于 `max`，相当于空包围盒。
+    pub fn intersection(&self, other: &BBox) -> BBox {
+        BBox {
+            min: vec3(
+                self.min.x.max(other.min.x),
+                self.min.y.max(other.min.y),
+                self.min.z.max(other.min.z),
+            ),
+            max: vec3(
+                self.max.x.min(other.max.x),
+                self.max.y.min(other.max.y),
+                self.max.z.min(other.max.z),
+            ),
         }
+    }
+
+    pub fn union_point(&self, point: &Vector3) -> BBox {
+        self.union(&BBox::from_point(*point))
+    }
 
-        let left = BBox::vec3_max(min_max[0]);
-        let right = BBox::vec3_min(min_max[1]);    
-        left < right && left >= tmin && right < tmax
+    /// 变换后八个角点的包围盒，无界的包围盒变换后按整个空间处理。
+    pub fn transform(&self, t: &Affine) -> BBox {
+        if self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z {
+            return BBox::empty();
+        }
+        if !self.is_finite() {
+            return BBox::infinite();
+        }
+        (0..8).fold(BBox::empty(), |bbox, i| {
+            let corner = vec3(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            bbox.union_point(&t.point(&corner))
+        })
     }
 
+    /// 随 `motion` 运动时在所有时刻扫过的范围。每段关键帧之间取若干时刻的包围盒，
+    /// 再按旋转在相邻时刻之间弦与弧的最大偏差向外扩张。
+    pub fn sweep(&self, motion: &AnimatedTransform) -> BBox {
+        const STEPS: usize = 16;
+        let keys = motion.keys();
+        let mut bbox = self.transform(&motion.affine_at(keys[0].0));
+        if bbox.min.x > bbox.max.x || !bbox.is_finite() {
+            return bbox;
+        }
+        let radius = (0..8)
+            .map(|i| {
+                vec3(
+                    if i & 1 == 0 { self.min.x } else { self.max.x },
+                    if i & 2 == 0 { self.min.y } else { self.max.y },
+                    if i & 4 == 0 { self.min.z } else { self.max.z },
+                ).magnitude()
+            })
+            .fold(0.0, f32::max);
+        for pair in keys.windows(2) {
+            let (t0, a) = pair[0];
+            let (t1, b) = pair[1];
+            let mut segment = BBox::empty();
+            for i in 1..=STEPS {
+                let time = t0 + (t1 - t0) * i as f32 / STEPS as f32;
+                segment = segment.union(&self.transform(&motion.affine_at(time)));
+            }
+            let angle = 2.0 * a.rot.dot(b.rot).abs().min(1.0).acos();
+            let bulge = radius * a.scale.max(b.scale) * (1.0 - (angle / STEPS as f32 / 2.0).cos());
+            let pad = vec3(bulge, bulge, bulge);
+            bbox = bbox.union(&BBox::new(segment.min - pad, segment.max + pad));
+        }
+        bbox
+    }
+
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn diagonal(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.diagonal();
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            0.0
+        } else {
+            2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+        }
+    }
+
+    /// 最长的轴，0、1、2 分别对应 x、y、z。
+    pub fn max_extent(&self) -> usize {
+        let d = self.diagonal();
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// 射线在 `[tmin, tmax]` 内是否穿过包围盒（包括起点在盒内的情况）。
+    pub fn ray_intersect(&self, ray: &Ray, tmin: f32, tmax: f32) -> bool {
+        self.ray_range(ray, tmin, tmax).is_some()
+    }
+
+    /// 射线在包围盒内的那一段与 `[tmin, tmax]` 的交集。
+    pub fn ray_range(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<(f32, f32)> {
+        //不用 ray.neg 判断先后：方向分量为 -0.0 时 neg 是 false，但 dir_inv 是负无穷。
+        let t0 = (self.min - ray.origin).mul_element_wise(ray.dir_inv);
+        let t1 = (self.max - ray.origin).mul_element_wise(ray.dir_inv);
+        let near = vec3(t0.x.min(t1.x), t0.y.min(t1.y), t0.z.min(t1.z));
+        let far = vec3(t0.x.max(t1.x), t0.y.max(t1.y), t0.z.max(t1.z));
+
+        let left = BBox::vec3_max(near).max(tmin);
+        let right = BBox::vec3_min(far).min(tmax);
+        if left <= right {
+            Some((left, right))
+        } else {
+            None
+        }
+    }
+
+    //射线平行于某个面并且起点恰好在面上时会得到 NaN，这里直接忽略 NaN 分量。
     fn vec3_min(vec3: Vector3) -> f32 {
         <Vector3 as Into<[f32; 3]>>::into(vec3)
-            .into_iter()
-            .fold(f32::INFINITY, |x, y| if !(x > *y) { x } else { *y })
+            .iter()
+            .fold(f32::INFINITY, |x, &y| if y < x { y } else { x })
     }
 
     fn vec3_max(vec3: Vector3) -> f32 {
         <Vector3 as Into<[f32; 3]>>::into(vec3)
-            .into_iter()
-            .fold(f32::NEG_INFINITY, |x, y| if !(x < *y) { x } else { *y })
+            .iter()
+            .fold(f32::NEG_INFINITY, |x, &y| if y > x { y } else { x })
+    }
+}
+
+/// 能被 `Bvh` 组织的图元。
+pub trait Primitive {
+    fn bounding_box(&self) -> BBox;
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord>;
+}
+
+const MAX_PRIMS_IN_LEAF: usize = 4;
+const BUCKET_COUNT: usize = 12;
+
+#[derive(Debug)]
+enum BvhNode {
+    Leaf {
+        bbox: BBox,
+        first: usize,
+        count: usize,
+    },
+    //左孩子总是紧跟在父节点后面，所以只需要记录右孩子。
+    Interior {
+        bbox: BBox,
+        right: usize,
+        axis: usize,
+    },
+}
+
+impl BvhNode {
+    fn bbox(&self) -> &BBox {
+        match *self {
+            BvhNode::Leaf { ref bbox, .. } | BvhNode::Interior { ref bbox, .. } => bbox,
+        }
+    }
+}
+
+struct BuildItem {
+    index: usize,
+    bbox: BBox,
+    centroid: Vector3,
+}
+
+/// 用 SAH 构建的层次包围盒，节点按深度优先顺序存放在一个数组里。
+/// 包围盒无界的图元（比如无限大的平面）不参与划分，放在 `primitives` 末尾逐个求交。
+pub struct Bvh<T> {
+    primitives: Vec<T>,
+    nodes: Vec<BvhNode>,
+    unbounded: usize,
+}
+
+impl<T: Primitive> Bvh<T> {
+    pub fn new(primitives: Vec<T>) -> Bvh<T> {
+        let mut items: Vec<BuildItem> = primitives
+            .iter()
+            .enumerate()
+            .map(|(index, p)| {
+                let bbox = p.bounding_box();
+                BuildItem {
+                    index,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect();
+
+        items.sort_by_key(|item| !item.bbox.is_finite());
+        let bounded = items.iter().take_while(|item| item.bbox.is_finite()).count();
+        let mut nodes = Vec::new();
+        if bounded > 0 {
+            Bvh::<T>::build(&mut nodes, &mut items[..bounded], 0);
+        }
+
+        //按叶子中的顺序重排图元，叶子就只需要记录一个区间。
+        let mut slots: Vec<Option<T>> = primitives.into_iter().map(Some).collect();
+        let primitives = items
+            .iter()
+            .map(|item| slots[item.index].take().unwrap())
+            .collect();
+
+        Bvh {
+            primitives,
+            nodes,
+            unbounded: items.len() - bounded,
+        }
+    }
+
+    fn build(nodes: &mut Vec<BvhNode>, items: &mut [BuildItem], offset: usize) -> usize {
+        let bbox = items
+            .iter()
+            .fold(BBox::empty(), |acc, item| acc.union(&item.bbox));
+        let node_index = nodes.len();
+        let count = items.len();
+
+        if count <= MAX_PRIMS_IN_LEAF {
+            nodes.push(BvhNode::Leaf {
+                bbox,
+                first: offset,
+                count,
+            });
+            return node_index;
+        }
+
+        let centroid_bounds = items
+            .iter()
+            .fold(BBox::empty(), |acc, item| acc.union_point(&item.centroid));
+        let axis = centroid_bounds.max_extent();
+        let (cmin, cmax) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+
+        let mid = if cmax <= cmin {
+            //所有质心重合，无法再划分。
+            count / 2
+        } else {
+            match Bvh::<T>::sah_split(items, &bbox, axis, cmin, cmax) {
+                Some(mid) => mid,
+                None => {
+                    nodes.push(BvhNode::Leaf {
+                        bbox,
+                        first: offset,
+                        count,
+                    });
+                    return node_index;
+                }
+            }
+        };
+
+        nodes.push(BvhNode::Interior {
+            bbox,
+            right: 0,
+            axis,
+        });
+        let (left, right) = items.split_at_mut(mid);
+        Bvh::<T>::build(nodes, left, offset);
+        let right_index = Bvh::<T>::build(nodes, right, offset + mid);
+        if let BvhNode::Interior { ref mut right, .. } = nodes[node_index] {
+            *right = right_index;
+        }
+        node_index
+    }
+
+    /// 按 SAH 在 `axis` 上挑选划分位置并完成划分，返回右半部分的起始下标。
+    /// 如果不划分的代价更低（并且图元不多），返回 `None`。
+    fn sah_split(
+        items: &mut [BuildItem],
+        bbox: &BBox,
+        axis: usize,
+        cmin: f32,
+        cmax: f32,
+    ) -> Option<usize> {
+        let bucket_of = |c: f32| {
+            let b = ((c - cmin) / (cmax - cmin) * BUCKET_COUNT as f32) as usize;
+            b.min(BUCKET_COUNT - 1)
+        };
+
+        let mut counts = [0usize; BUCKET_COUNT];
+        let mut bounds = [BBox::empty(); BUCKET_COUNT];
+        for item in items.iter() {
+            let b = bucket_of(item.centroid[axis]);
+            counts[b] += 1;
+            bounds[b] = bounds[b].union(&item.bbox);
+        }
+
+        let total_area = bbox.surface_area();
+        let mut best = (f32::INFINITY, 0);
+        for split in 0..BUCKET_COUNT - 1 {
+            let (mut left_box, mut right_box) = (BBox::empty(), BBox::empty());
+            let (mut left_count, mut right_count) = (0, 0);
+            for b in 0..=split {
+                left_box = left_box.union(&bounds[b]);
+                left_count += counts[b];
+            }
+            for b in split + 1..BUCKET_COUNT {
+                right_box = right_box.union(&bounds[b]);
+                right_count += counts[b];
+            }
+            let cost = 0.125
+                + (left_count as f32 * left_box.surface_area()
+                    + right_count as f32 * right_box.surface_area())
+                    / total_area;
+            if cost < best.0 {
+                best = (cost, split);
+            }
+        }
+
+        if items.len() <= 2 * MAX_PRIMS_IN_LEAF && best.0 >= items.len() as f32 {
+            return None;
+        }
+
+        let split = best.1;
+        items.sort_by_key(|item| bucket_of(item.centroid[axis]) > split);
+        let mid = items
+            .iter()
+            .position(|item| bucket_of(item.centroid[axis]) > split)
+            .unwrap_or(items.len());
+        if mid == 0 || mid == items.len() {
+            Some(items.len() / 2)
+        } else {
+            Some(mid)
+        }
+    }
+
+    pub fn primitives(&self) -> &[T] {
+        &self.primitives
+    }
+
+    /// 返回 `[tmin, tmax]` 内最近的交点以及与之相交的图元。
+    pub fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<(&T, HitRecord)> {
+        let mut closest: Option<(&T, HitRecord)> = None;
+        let mut tmax = tmax;
+        for primitive in &self.primitives[self.primitives.len() - self.unbounded..] {
+            if let Some(hit) = primitive.hit(ray, tmin, tmax) {
+                tmax = hit.t;
+                closest = Some((primitive, hit));
+            }
+        }
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bbox().ray_intersect(ray, tmin, tmax) {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for primitive in &self.primitives[first..first + count] {
+                        if let Some(hit) = primitive.hit(ray, tmin, tmax) {
+                            tmax = hit.t;
+                            closest = Some((primitive, hit));
+                        }
+                    }
+                }
+                BvhNode::Interior { right, axis, .. } => {
+                    //先访问离射线起点近的孩子，方便尽早收紧 tmax。
+                    if ray.neg[axis] {
+                        stack.push(index + 1);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+impl<T: Primitive> Primitive for Bvh<T> {
+    fn bounding_box(&self) -> BBox {
+        if self.unbounded > 0 {
+            return BBox::infinite();
+        }
+        self.nodes
+            .first()
+            .map(|node| *node.bbox())
+            .unwrap_or_else(BBox::empty)
+    }
+
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        Bvh::hit(self, ray, tmin, tmax).map(|(_, hit)| hit)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::BBox;
-    use shapes::{Ray, RayBuilder};
+    use super::{BBox, Bvh, Primitive};
+    use shapes::{HitRecord, Ray, RayBuilder, Shape, Sphere};
     use math::*;
+
+    struct Ball(Sphere);
+
+    impl Primitive for Ball {
+        fn bounding_box(&self) -> BBox {
+            self.0.bounding_box()
+        }
+
+        fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+            self.0.hit(ray, tmin, tmax)
+        }
+    }
+
     #[test]
     fn intersect_2d() {
         let bbox = BBox {
@@ -62,4 +488,40 @@ mod tests {
 
         assert!(bbox.ray_intersect(&ray, 0.1, 10.0));
     }
+
+    #[test]
+    fn intersect_from_inside() {
+        let bbox = BBox::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+        let ray = RayBuilder {
+            origin: Vector3::zero(),
+            direction: vec3(0.0, 0.0, -1.0),
+        }.build();
+
+        assert!(bbox.ray_intersect(&ray, 0.001, 1000.0));
+        assert!(!bbox.ray_intersect(&ray, 2.0, 1000.0));
+    }
+
+    #[test]
+    fn closest_hit() {
+        //沿 -z 排成一列，故意打乱顺序。
+        let balls = (0..64)
+            .map(|i| ((i * 37) % 64) as f32)
+            .map(|i| Ball(Sphere::new(vec3(0.0, 0.0, -5.0 - i * 3.0), 1.0)))
+            .collect();
+        let bvh = Bvh::new(balls);
+
+        let ray = RayBuilder {
+            origin: Vector3::zero(),
+            direction: vec3(0.0, 0.0, -1.0),
+        }.build();
+        let (ball, hit) = bvh.hit(&ray, 0.001, 1000.0).unwrap();
+        assert_relative_eq!(hit.t, 4.0, epsilon = 1e-4);
+        assert_relative_eq!(ball.0.center.z, -5.0);
+
+        let miss = RayBuilder {
+            origin: Vector3::zero(),
+            direction: vec3(0.0, 1.0, 0.0),
+        }.build();
+        assert!(bvh.hit(&miss, 0.001, 1000.0).is_none());
+    }
 }
@@ -1,5 +1,5 @@
-use math::{InnerSpace, Vector2, Vector3};
-use shapes::{Ray, RayBuilder};
+use math::{lerp, InnerSpace, Vector2, Vector3};
+use shapes::{Ray, RayBuilder, RayDifferential};
 
 #[derive(Debug, Clone)]
 pub struct ThinLens {
@@ -9,26 +9,27 @@ pub struct ThinLens {
 }
 
 impl ThinLens {
-    fn refract(&self, ray: &Ray, hit_pos: Vector3, s: f32) -> Ray {
+    pub fn refract(&self, ray: &Ray, hit_pos: Vector3, s: f32) -> Ray {
         let i = s * self.focal_length / (s - self.focal_length);
-        let dir = &self.center - &ray.origin;
+        let dir = self.center - ray.origin;
         let distance = dir.magnitude() * s / i;
-        let dest = &dir.normalize() * distance + &self.center;
+        let dest = dir.normalize() * distance + self.center;
         RayBuilder {
-            origin: hit_pos.clone(),
-            direction: (&dest - &hit_pos).normalize(),
-        }.build()
+            origin: hit_pos,
+            direction: (dest - hit_pos).normalize(),
+        }.build_at(ray.time)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Camera {
     pub lens: ThinLens,
     u: Vector3,
     v: Vector3,
-    n: Vector3,
-    origin: Vector3,
     left_bottom: Vector3,
+    film_size: Vector2,
+    shutter_open: f32,
+    shutter_close: f32,
 }
 
 pub struct CameraBuilder {
@@ -38,43 +39,112 @@ pub struct CameraBuilder {
     pub up: Vector3,
     pub aspect_ratio: f32,
     pub fov: f32,
+    /// 快门打开的时间区间，射线的时刻在其中均匀分布。
+    pub shutter_open: f32,
+    pub shutter_close: f32,
 }
 
 impl CameraBuilder {
     pub fn build(&self) -> Camera {
         let up = self.up.normalize();
-        let n = (&self.target - &self.at).normalize();
+        let n = (self.target - self.at).normalize();
         let u = n.cross(up);
         let v = u.cross(n);
-        let origin = &self.at + &n * self.lens.focal_length;
+        let origin = self.at + n * self.lens.focal_length;
         let half_width = self.fov.tan();
         let half_height = half_width / self.aspect_ratio;
-        let left_bottom = &origin - &u * half_width - &v * half_height;
+        let left_bottom = origin - u * half_width - v * half_height;
         Camera {
             lens: self.lens.clone(),
             u,
             v,
-            n,
-            origin: self.at.clone(),
             left_bottom,
+            film_size: Vector2::new(half_width * 2.0, half_height * 2.0),
+            shutter_open: self.shutter_open,
+            shutter_close: self.shutter_close,
         }
     }
 }
 
 impl Camera {
+    /// `gen_ray` 所用的像平面坐标范围。
+    pub fn film_size(&self) -> Vector2 {
+        self.film_size
+    }
+
+    /// `u` 为 [0, 1) 上的样本，映射到快门区间内的时刻。
+    pub fn sample_time(&self, u: f32) -> f32 {
+        lerp(u, self.shutter_open, self.shutter_close)
+    }
+
+    /// 保持水平视角不变，换成新的宽高比。
+    pub fn with_aspect_ratio(&self, aspect_ratio: f32) -> Camera {
+        let half = self.film_size / 2.0;
+        let center = self.left_bottom + self.u * half.x + self.v * half.y;
+        let half_height = half.x / aspect_ratio;
+        Camera {
+            left_bottom: center - self.u * half.x - self.v * half_height,
+            film_size: Vector2::new(self.film_size.x, half_height * 2.0),
+            ..self.clone()
+        }
+    }
+
+    /// `raster` 为 `width` x `height` 图像上的连续像素坐标，原点在左上角。
+    /// 带有指向右边和下边相邻像素的射线微分。
+    pub fn raster_ray(
+        &self,
+        raster: &Vector2,
+        width: u32,
+        height: u32,
+        lens_pos: &Vector2,
+        time: f32,
+    ) -> Ray {
+        let pixel = Vector2::new(
+            raster.x / width as f32 * self.film_size.x,
+            (1.0 - raster.y / height as f32) * self.film_size.y,
+        );
+        //光栅坐标的 y 轴朝下，像平面坐标的 y 轴朝上。
+        let step = Vector2::new(
+            self.film_size.x / width as f32,
+            -self.film_size.y / height as f32,
+        );
+        self.gen_ray_differential(&pixel, &step, lens_pos, time)
+    }
+
     ///`x`, `y`: pixel coord.
-    pub fn gen_ray(&self, pixel: &Vector2, lens_pos: &Vector2) -> Ray {
+    pub fn gen_ray(&self, pixel: &Vector2, lens_pos: &Vector2, time: f32) -> Ray {
         //1. transform pixel coord to world.
-        let pos = &self.left_bottom + &self.u * pixel.x + &self.v * pixel.y;
+        let pos = self.left_bottom + self.u * pixel.x + self.v * pixel.y;
         let lens = &self.lens;
         let ux = lens_pos.x * 2.0 * lens.radius;
         let uy = lens_pos.y * 2.0 * lens.radius;
-        let lens_pos = &self.u * ux + &self.v * uy + &lens.center;
-        let new_dir = (&pos - &lens_pos).normalize();
+        let lens_pos = self.u * ux + self.v * uy + lens.center;
+        let new_dir = (pos - lens_pos).normalize();
         RayBuilder {
             origin: lens_pos,
             direction: new_dir,
-        }.build()
+        }.build_at(time)
+    }
+
+    /// 同 `gen_ray`，另外生成像平面坐标分别偏移 `step.x` 和 `step.y` 的两条射线作为微分，
+    /// 它们与主射线经过透镜上的同一点。
+    pub fn gen_ray_differential(
+        &self,
+        pixel: &Vector2,
+        step: &Vector2,
+        lens_pos: &Vector2,
+        time: f32,
+    ) -> Ray {
+        let mut ray = self.gen_ray(pixel, lens_pos, time);
+        let rx = self.gen_ray(&Vector2::new(pixel.x + step.x, pixel.y), lens_pos, time);
+        let ry = self.gen_ray(&Vector2::new(pixel.x, pixel.y + step.y), lens_pos, time);
+        ray.differentials = Some(RayDifferential {
+            rx_origin: rx.origin,
+            rx_direction: rx.direction,
+            ry_origin: ry.origin,
+            ry_direction: ry.direction,
+        });
+        ray
     }
 }
 
@@ -84,32 +154,61 @@ mod tests {
     use math::*;
     #[test]
     fn refract() {
-        let thinLens = ThinLens {
+        let thin_lens = ThinLens {
             radius: 20.0,
             center: Vector3::zero(),
             focal_length: 5.0,
         };
-        let mut ray = RayBuilder {
+        let ray = RayBuilder {
             origin: vec3(0.0, 0.0, 2.0),
             direction: vec3(-1.0, 0.0, 0.0),
         }.build();
-        let ray = thinLens.refract(&ray, Vector3::zero(), 10.0);
+        let ray = thin_lens.refract(&ray, Vector3::zero(), 10.0);
         assert_eq!(ray.origin, Vector3::zero());
         assert_eq!(ray.direction, vec3(0.0, 0.0, -1.0));
     }
 
+    #[test]
+    fn raster_differentials() {
+        let camera = CameraBuilder {
+            lens: ThinLens {
+                radius: 0.0,
+                center: Vector3::zero(),
+                focal_length: 1.0,
+            },
+            at: Vector3::zero(),
+            target: -Vector3::unit_z(),
+            up: Vector3::unit_y(),
+            aspect_ratio: 2.0,
+            fov: 0.5,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }.build();
+        let lens = Vector2::zero();
+        let ray = |x, y| camera.raster_ray(&vec2(x, y), 64, 32, &lens, 0.0);
+        let r = ray(10.0, 20.0).differentials.unwrap();
+        assert_relative_eq!(r.rx_direction, ray(11.0, 20.0).direction, epsilon = 1e-6);
+        assert_relative_eq!(r.ry_direction, ray(10.0, 21.0).direction, epsilon = 1e-6);
+        assert_relative_eq!(r.rx_origin, ray(11.0, 20.0).origin);
+
+        let mut half = ray(10.0, 20.0);
+        half.scale_differentials(0.5);
+        let r = half.differentials.unwrap();
+        assert_relative_eq!(r.ry_direction, ray(10.0, 20.5).direction, epsilon = 1e-3);
+    }
+
     #[test]
     fn refract_normal() {
-        let thinLens = ThinLens {
+        let thin_lens = ThinLens {
             radius: 20.0,
             center: Vector3::zero(),
             focal_length: 2.5,
         };
-        let mut ray = RayBuilder {
+        let ray = RayBuilder {
             origin: vec3(5.0, 5.0, 0.0),
             direction: vec3(-1.0, 0.0, 0.0),
         }.build();
-        let ray = thinLens.refract(&ray, vec3(0.0, 5.0, 0.0), 5.0);
+        let ray = thin_lens.refract(&ray, vec3(0.0, 5.0, 0.0), 5.0);
         relative_eq!(ray.origin, vec3(0.0, 5.0, 0.0));
         relative_eq!(ray.direction, vec3(-5.0, -10.0, 0.0).normalize());
     }
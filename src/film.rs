@@ -0,0 +1,360 @@
+extern crate image;
+
+use math::*;
+use rgb::Rgb;
+use std::sync::Arc;
+
+/// 像素重建滤波器，`eval` 的参数是样本相对于像素中心的偏移。
+pub trait Filter: Send + Sync {
+    fn radius(&self) -> Vector2;
+    fn eval(&self, offset: &Vector2) -> f32;
+}
+
+#[derive(Debug, Clone)]
+pub struct BoxFilter {
+    pub radius: Vector2,
+}
+
+impl Default for BoxFilter {
+    /// 每个样本只落在它所在的那个像素里。
+    fn default() -> Self {
+        BoxFilter {
+            radius: vec2(0.5, 0.5),
+        }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> Vector2 {
+        self.radius
+    }
+
+    fn eval(&self, _offset: &Vector2) -> f32 {
+        1.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TentFilter {
+    pub radius: Vector2,
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> Vector2 {
+        self.radius
+    }
+
+    fn eval(&self, offset: &Vector2) -> f32 {
+        (self.radius.x - offset.x.abs()).max(0.0) * (self.radius.y - offset.y.abs()).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GaussianFilter {
+    pub radius: Vector2,
+    pub alpha: f32,
+}
+
+impl GaussianFilter {
+    //减去半径处的值，让滤波器在边界上连续地降到零。
+    fn gaussian(&self, d: f32, radius: f32) -> f32 {
+        ((-self.alpha * d * d).exp() - (-self.alpha * radius * radius).exp()).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> Vector2 {
+        self.radius
+    }
+
+    fn eval(&self, offset: &Vector2) -> f32 {
+        self.gaussian(offset.x, self.radius.x) * self.gaussian(offset.y, self.radius.y)
+    }
+}
+
+/// Mitchell-Netravali 滤波器，论文推荐 `b = c = 1 / 3`。
+#[derive(Debug, Clone)]
+pub struct MitchellFilter {
+    pub radius: Vector2,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl MitchellFilter {
+    fn mitchell_1d(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        let x = (2.0 * x).abs();
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)) / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)) / 6.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> Vector2 {
+        self.radius
+    }
+
+    fn eval(&self, offset: &Vector2) -> f32 {
+        self.mitchell_1d(offset.x / self.radius.x) * self.mitchell_1d(offset.y / self.radius.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FilmPixel {
+    sum: Rgb,
+    weight: f32,
+}
+
+/// 图像上的矩形像素区域 `[x0, x1) x [y0, y1)`。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelBounds {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl PixelBounds {
+    fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    fn area(&self) -> usize {
+        (self.width() * (self.y1 - self.y0)) as usize
+    }
+}
+
+//把样本按滤波器权重分摊到 `bounds` 内它覆盖的像素上，`pixels` 按 `bounds` 的行存放。
+fn splat(
+    filter: &dyn Filter,
+    bounds: &PixelBounds,
+    pixels: &mut [FilmPixel],
+    pos: &Vector2,
+    radiance: Rgb,
+) {
+    let radius = filter.radius();
+    let x0 = ((pos.x - 0.5 - radius.x).ceil().max(bounds.x0 as f32)) as u32;
+    let y0 = ((pos.y - 0.5 - radius.y).ceil().max(bounds.y0 as f32)) as u32;
+    let x1 = ((pos.x - 0.5 + radius.x).floor() + 1.0).min(bounds.x1 as f32);
+    let y1 = ((pos.y - 0.5 + radius.y).floor() + 1.0).min(bounds.y1 as f32);
+    if x1 <= 0.0 || y1 <= 0.0 {
+        return;
+    }
+    for y in y0..y1 as u32 {
+        for x in x0..x1 as u32 {
+            let offset = vec2(x as f32 + 0.5 - pos.x, y as f32 + 0.5 - pos.y);
+            let weight = filter.eval(&offset);
+            if weight == 0.0 {
+                continue;
+            }
+            let index = (y - bounds.y0) * bounds.width() + (x - bounds.x0);
+            let pixel = &mut pixels[index as usize];
+            pixel.sum += radiance * weight;
+            pixel.weight += weight;
+        }
+    }
+}
+
+/// 累积带权重的样本，最后按权重归一化得到图像。
+/// 像素坐标是连续的，`(0, 0)` 是左上角像素的左上角，像素 `(x, y)` 的中心在 `(x + 0.5, y + 0.5)`。
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<FilmPixel>,
+    filter: Arc<dyn Filter>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Box<dyn Filter>) -> Film {
+        Film {
+            width,
+            height,
+            pixels: vec![FilmPixel::default(); (width * height) as usize],
+            filter: Arc::from(filter),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bounds(&self) -> PixelBounds {
+        PixelBounds {
+            x0: 0,
+            y0: 0,
+            x1: self.width,
+            y1: self.height,
+        }
+    }
+
+    /// 把 `pos` 处的样本按滤波器权重分摊到它覆盖的像素上。
+    pub fn add_sample(&mut self, pos: &Vector2, radiance: Rgb) {
+        let bounds = self.bounds();
+        splat(&*self.filter, &bounds, &mut self.pixels, pos, radiance);
+    }
+
+    /// 接收 `sample_bounds` 内样本的分块。滤波器会把样本分摊到相邻像素上，
+    /// 所以分块覆盖的像素比 `sample_bounds` 向外多出一个滤波半径。
+    pub fn tile(&self, sample_bounds: &PixelBounds) -> FilmTile {
+        let radius = self.filter.radius();
+        let bounds = PixelBounds {
+            x0: (sample_bounds.x0 as f32 - 0.5 - radius.x).floor().max(0.0) as u32,
+            y0: (sample_bounds.y0 as f32 - 0.5 - radius.y).floor().max(0.0) as u32,
+            x1: ((sample_bounds.x1 as f32 - 0.5 + radius.x).floor() as u32 + 1).min(self.width),
+            y1: ((sample_bounds.y1 as f32 - 0.5 + radius.y).floor() as u32 + 1).min(self.height),
+        };
+        FilmTile {
+            bounds,
+            pixels: vec![FilmPixel::default(); bounds.area()],
+            filter: self.filter.clone(),
+        }
+    }
+
+    pub fn merge_tile(&mut self, tile: FilmTile) {
+        let bounds = tile.bounds;
+        for (i, pixel) in tile.pixels.into_iter().enumerate() {
+            let x = bounds.x0 + i as u32 % bounds.width();
+            let y = bounds.y0 + i as u32 / bounds.width();
+            let target = &mut self.pixels[(y * self.width + x) as usize];
+            target.sum += pixel.sum;
+            target.weight += pixel.weight;
+        }
+    }
+
+    /// 归一化后的像素，按行存放，第 0 行在最上面。
+    pub fn resolve(&self) -> Vec<Rgb> {
+        self.pixels
+            .iter()
+            .map(|p| {
+                if p.weight == 0.0 {
+                    Rgb::black()
+                } else {
+                    p.sum / p.weight
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_image(&self) -> image::RgbImage {
+        let pixels = self.resolve();
+        let width = self.width;
+        image::ImageBuffer::from_fn(width, self.height, |x, y| {
+            pixels[(y * width + x) as usize].into()
+        })
+    }
+}
+
+/// `Film` 的一部分，可以在别的线程上独立累积样本，之后用 `Film::merge_tile` 合并回去。
+pub struct FilmTile {
+    bounds: PixelBounds,
+    pixels: Vec<FilmPixel>,
+    filter: Arc<dyn Filter>,
+}
+
+impl FilmTile {
+    pub fn add_sample(&mut self, pos: &Vector2, radiance: Rgb) {
+        splat(&*self.filter, &self.bounds, &mut self.pixels, pos, radiance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Vec<Box<dyn Filter>> {
+        vec![
+            Box::new(BoxFilter::default()),
+            Box::new(TentFilter {
+                radius: vec2(1.0, 1.0),
+            }),
+            Box::new(GaussianFilter {
+                radius: vec2(1.5, 1.5),
+                alpha: 2.0,
+            }),
+            Box::new(MitchellFilter {
+                radius: vec2(2.0, 2.0),
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+        ]
+    }
+
+    #[test]
+    fn constant_image_stays_constant() {
+        for filter in filters() {
+            let mut film = Film::new(4, 3, filter);
+            for y in 0..12 {
+                for x in 0..16 {
+                    let pos = vec2(x as f32 / 4.0 + 0.125, y as f32 / 4.0 + 0.125);
+                    film.add_sample(&pos, Rgb::new(0.25, 0.5, 1.0));
+                }
+            }
+            for p in film.resolve() {
+                assert_relative_eq!(p.g, 0.5, epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn box_filter_averages_pixel() {
+        let mut film = Film::new(2, 1, Box::new(BoxFilter::default()));
+        film.add_sample(&vec2(0.25, 0.5), Rgb::white());
+        film.add_sample(&vec2(0.75, 0.5), Rgb::black());
+        film.add_sample(&vec2(1.5, 0.5), Rgb::white());
+        let pixels = film.resolve();
+        assert_relative_eq!(pixels[0].r, 0.5);
+        assert_relative_eq!(pixels[1].r, 1.0);
+    }
+
+    #[test]
+    fn tiles_match_direct_splatting() {
+        let filter = || -> Box<dyn Filter> {
+            Box::new(TentFilter {
+                radius: vec2(1.5, 1.5),
+            })
+        };
+        let samples: Vec<Vector2> = (0..40)
+            .map(|i| vec2((i * 7 % 23) as f32 / 3.0, (i * 5 % 19) as f32 / 3.0))
+            .collect();
+
+        let mut direct = Film::new(8, 6, filter());
+        for (i, pos) in samples.iter().enumerate() {
+            direct.add_sample(pos, Rgb::new(i as f32, 1.0, 0.0));
+        }
+
+        let mut tiled = Film::new(8, 6, filter());
+        for &(x0, x1) in &[(0, 3), (3, 8)] {
+            let bounds = PixelBounds {
+                x0,
+                y0: 0,
+                x1,
+                y1: 6,
+            };
+            let mut tile = tiled.tile(&bounds);
+            for (i, pos) in samples.iter().enumerate() {
+                if pos.x >= x0 as f32 && pos.x < x1 as f32 {
+                    tile.add_sample(pos, Rgb::new(i as f32, 1.0, 0.0));
+                }
+            }
+            tiled.merge_tile(tile);
+        }
+
+        for (a, b) in direct.resolve().iter().zip(tiled.resolve()) {
+            assert_relative_eq!(a.r, b.r, epsilon = 1e-4);
+        }
+    }
+}
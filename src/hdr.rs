@@ -0,0 +1,403 @@
+//! 无损的浮点图像输出：PFM 与 Radiance `.hdr`（RGBE）。
+//! 像素都按行存放，第 0 行在最上面，与 `Film::resolve` 一致。
+
+use rgb::Rgb;
+use std::io::{self, BufRead, Write};
+
+/// 从文件读出的浮点图像。
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Rgb>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+//读文件时允许的最大像素数，挡住头部写坏导致的溢出和超大分配。
+const MAX_PIXELS: u64 = 1 << 28;
+
+fn pixel_count(width: u32, height: u32) -> io::Result<usize> {
+    match width.checked_mul(height) {
+        Some(n) if u64::from(n) <= MAX_PIXELS => Ok(n as usize),
+        _ => Err(invalid(&format!("image size {}x{} is too large", width, height))),
+    }
+}
+
+fn check_size(width: u32, height: u32, pixels: &[Rgb]) -> io::Result<()> {
+    if pixels.len() as u64 != u64::from(width) * u64::from(height) {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "expected {} pixels for a {}x{} image, got {}",
+                u64::from(width) * u64::from(height),
+                width,
+                height,
+                pixels.len()
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+//跳过空白和注释，读出一个以空白结尾的词。
+fn read_token<R: BufRead>(input: &mut R) -> io::Result<String> {
+    let mut token = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        if input.read(&mut byte)? == 0 {
+            break;
+        }
+        match byte[0] {
+            b'#' if token.is_empty() => {
+                let mut comment = Vec::new();
+                input.read_until(b'\n', &mut comment)?;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                if !token.is_empty() {
+                    break;
+                }
+            }
+            b => token.push(b),
+        }
+    }
+    if token.is_empty() {
+        Err(invalid("unexpected end of PFM header"))
+    } else {
+        String::from_utf8(token).map_err(|_| invalid("PFM header is not ASCII"))
+    }
+}
+
+/// 写出 PFM，按小端序存放，行从下往上。
+pub fn write_pfm<W: Write>(out: &mut W, width: u32, height: u32, pixels: &[Rgb]) -> io::Result<()> {
+    check_size(width, height, pixels)?;
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+    let mut row = Vec::with_capacity(width as usize * 12);
+    for y in (0..height).rev() {
+        row.clear();
+        for p in &pixels[(y * width) as usize..((y + 1) * width) as usize] {
+            for c in &[p.r, p.g, p.b] {
+                row.extend_from_slice(&c.to_bits().to_le_bytes());
+            }
+        }
+        out.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// 读取彩色（`PF`）或灰度（`Pf`）PFM，大小端都支持。
+pub fn read_pfm<R: BufRead>(input: &mut R) -> io::Result<HdrImage> {
+    let channels = match read_token(input)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid("not a PFM file")),
+    };
+    let parse_u32 = |s: String| s.parse::<u32>().map_err(|_| invalid("bad PFM size"));
+    let width = parse_u32(read_token(input)?)?;
+    let height = parse_u32(read_token(input)?)?;
+    let scale: f32 = read_token(input)?
+        .parse()
+        .map_err(|_| invalid("bad PFM scale"))?;
+    let little_endian = scale < 0.0;
+
+    let count = pixel_count(width, height)?;
+    let mut data = vec![0u8; count * channels * 4];
+    input.read_exact(&mut data)?;
+    let floats: Vec<f32> = data
+        .chunks(4)
+        .map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            let bits = if little_endian {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            };
+            f32::from_bits(bits)
+        })
+        .collect();
+
+    let mut pixels = Vec::with_capacity(count);
+    for y in (0..height as usize).rev() {
+        let row = &floats[y * width as usize * channels..(y + 1) * width as usize * channels];
+        for p in row.chunks(channels) {
+            pixels.push(if channels == 3 {
+                Rgb::new(p[0], p[1], p[2])
+            } else {
+                Rgb::new(p[0], p[0], p[0])
+            });
+        }
+    }
+    Ok(HdrImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn to_rgbe(p: &Rgb) -> [u8; 4] {
+    let v = p.r.max(p.g).max(p.b);
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    //v = m * 2^e，m 在 [0.5, 1) 内；指数最大只能存 127，更大的值和无穷都饱和到最大值。
+    let e = if v.is_finite() {
+        (v.log2().floor() as i32 + 1).min(127)
+    } else {
+        127
+    };
+    let scale = 256.0 / 2f32.powi(e);
+    let q = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+    [q(p.r), q(p.g), q(p.b), (e + 128) as u8]
+}
+
+fn from_rgbe(rgbe: &[u8]) -> Rgb {
+    if rgbe[3] == 0 {
+        return Rgb::black();
+    }
+    let f = 2f32.powi(i32::from(rgbe[3]) - (128 + 8));
+    let c = |b: u8| (f32::from(b) + 0.5) * f;
+    Rgb::new(c(rgbe[0]), c(rgbe[1]), c(rgbe[2]))
+}
+
+//新式游程编码：每个通道分开编码，计数大于 128 表示重复 `计数 - 128` 次。
+fn write_rle_channel<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let mut cur = 0;
+    while cur < data.len() {
+        //找到下一段足够长的重复。
+        let mut begin = cur;
+        let mut run = 0;
+        while begin < data.len() {
+            run = 1;
+            while begin + run < data.len() && run < 127 && data[begin + run] == data[begin] {
+                run += 1;
+            }
+            if run >= MIN_RUN {
+                break;
+            }
+            begin += run;
+        }
+        while cur < begin {
+            let count = (begin - cur).min(128);
+            out.write_all(&[count as u8])?;
+            out.write_all(&data[cur..cur + count])?;
+            cur += count;
+        }
+        if run >= MIN_RUN {
+            out.write_all(&[128 + run as u8, data[begin]])?;
+            cur += run;
+        }
+    }
+    Ok(())
+}
+
+/// 写出 Radiance HDR（RGBE），扫描线使用游程编码。
+pub fn write_hdr<W: Write>(out: &mut W, width: u32, height: u32, pixels: &[Rgb]) -> io::Result<()> {
+    check_size(width, height, pixels)?;
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    let rle = (8..0x8000).contains(&width);
+    let mut channels: Vec<Vec<u8>> = (0..4).map(|_| Vec::with_capacity(width as usize)).collect();
+    for y in 0..height {
+        let row = &pixels[(y * width) as usize..((y + 1) * width) as usize];
+        if !rle {
+            for p in row {
+                out.write_all(&to_rgbe(p))?;
+            }
+            continue;
+        }
+        for c in &mut channels {
+            c.clear();
+        }
+        for p in row {
+            for (c, b) in channels.iter_mut().zip(to_rgbe(p).iter()) {
+                c.push(*b);
+            }
+        }
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for c in &channels {
+            write_rle_channel(out, c)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(invalid("unexpected end of HDR header"));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+fn read_hdr_scanline<R: BufRead>(input: &mut R, width: usize, row: &mut [Rgb]) -> io::Result<()> {
+    let mut head = [0u8; 4];
+    input.read_exact(&mut head)?;
+    let new_rle = (8..0x8000).contains(&width)
+        && head[0] == 2
+        && head[1] == 2
+        && (usize::from(head[2]) << 8 | usize::from(head[3])) == width;
+    if !new_rle {
+        row[0] = from_rgbe(&head);
+        for p in row.iter_mut().skip(1) {
+            let mut rgbe = [0u8; 4];
+            input.read_exact(&mut rgbe)?;
+            *p = from_rgbe(&rgbe);
+        }
+        return Ok(());
+    }
+
+    let mut channels = vec![vec![0u8; width]; 4];
+    for c in &mut channels {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8];
+            input.read_exact(&mut count)?;
+            let count = count[0] as usize;
+            if count > 128 {
+                let run = count - 128;
+                if x + run > width {
+                    return Err(invalid("HDR run overflows scanline"));
+                }
+                let mut value = [0u8];
+                input.read_exact(&mut value)?;
+                for b in &mut c[x..x + run] {
+                    *b = value[0];
+                }
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid("bad HDR literal run"));
+                }
+                input.read_exact(&mut c[x..x + count])?;
+                x += count;
+            }
+        }
+    }
+    for (x, p) in row.iter_mut().enumerate() {
+        *p = from_rgbe(&[
+            channels[0][x],
+            channels[1][x],
+            channels[2][x],
+            channels[3][x],
+        ]);
+    }
+    Ok(())
+}
+
+/// 读取 `-Y h +X w` 方向的 Radiance HDR，支持未压缩和新式游程编码的扫描线。
+pub fn read_hdr<R: BufRead>(input: &mut R) -> io::Result<HdrImage> {
+    let magic = read_line(input)?;
+    if !magic.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    loop {
+        let line = read_line(input)?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("only 32-bit_rle_rgbe HDR files are supported"));
+        }
+    }
+
+    let size = read_line(input)?;
+    let parts: Vec<&str> = size.split_whitespace().collect();
+    if parts.len() != 4 || parts[0] != "-Y" || parts[2] != "+X" {
+        return Err(invalid("only -Y h +X w HDR orientation is supported"));
+    }
+    let parse = |s: &str| s.parse::<u32>().map_err(|_| invalid("bad HDR size"));
+    let height = parse(parts[1])?;
+    let width = parse(parts[3])?;
+
+    let mut pixels = vec![Rgb::black(); pixel_count(width, height)?];
+    for row in pixels.chunks_mut(width.max(1) as usize) {
+        read_hdr_scanline(input, width as usize, row)?;
+    }
+    Ok(HdrImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use std::io::Cursor;
+
+    fn gradient(width: u32, height: u32) -> Vec<Rgb> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f32, (i / width) as f32);
+                //有大片相同的像素，也有超过 1 的值。
+                if x < 10.0 {
+                    Rgb::new(0.5, 0.25, 0.125)
+                } else {
+                    Rgb::new(x * 0.37, y * 3.1, 100.0 / (x + y + 1.0))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pfm_roundtrip() {
+        let pixels = gradient(23, 7);
+        let mut buf = Vec::new();
+        write_pfm(&mut buf, 23, 7, &pixels).unwrap();
+        let image = read_pfm(&mut Cursor::new(buf)).unwrap();
+        assert_eq!((image.width, image.height), (23, 7));
+        for (a, b) in pixels.iter().zip(image.pixels.iter()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+
+    #[test]
+    fn hdr_roundtrip() {
+        for &width in &[5, 37] {
+            let pixels = gradient(width, 6);
+            let mut buf = Vec::new();
+            write_hdr(&mut buf, width, 6, &pixels).unwrap();
+            let image = read_hdr(&mut Cursor::new(buf)).unwrap();
+            assert_eq!((image.width, image.height), (width, 6));
+            for (a, b) in pixels.iter().zip(image.pixels.iter()) {
+                //RGBE 的尾数只有 8 位，按最大分量计误差在 1% 以内。
+                let tolerance = a.max_component() / 128.0;
+                assert_relative_eq!(a.r, b.r, epsilon = tolerance);
+                assert_relative_eq!(a.g, b.g, epsilon = tolerance);
+                assert_relative_eq!(a.b, b.b, epsilon = tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn size_mismatch_is_an_error() {
+        let mut buf = Vec::new();
+        assert!(write_pfm(&mut buf, 4, 4, &[Rgb::black(); 3]).is_err());
+    }
+
+    #[test]
+    fn rgbe_saturates() {
+        for &v in &[3e38, f32::INFINITY] {
+            let rgbe = to_rgbe(&Rgb::new(v, 1.0, 0.0));
+            assert_eq!((rgbe[0], rgbe[3]), (255, 255));
+            assert!(from_rgbe(&rgbe).r > 1e38);
+        }
+    }
+
+    #[test]
+    fn oversized_header_is_an_error() {
+        let kind = |r: io::Result<HdrImage>| r.unwrap_err().kind();
+        let pfm = b"PF\n4294967295 4294967295\n-1.0\n".to_vec();
+        assert_eq!(kind(read_pfm(&mut Cursor::new(pfm))), io::ErrorKind::InvalidData);
+        let hdr = b"#?RADIANCE\n\n-Y 65536 +X 65536\n".to_vec();
+        assert_eq!(kind(read_hdr(&mut Cursor::new(hdr))), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,15 @@
+use rgb::Rgb;
+use sample::Sampler;
+use scene::Scene;
+use shapes::Ray;
+
+pub mod path;
+pub mod whitted;
+
+pub use self::path::PathTracer;
+pub use self::whitted::Whitted;
+
+/// 计算沿射线方向到达的辐射亮度。
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler) -> Rgb;
+}
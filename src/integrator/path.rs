@@ -0,0 +1,173 @@
+use math::*;
+use rgb::Rgb;
+use sample::Sampler;
+use scene::Scene;
+use shapes::{Ray, RayBuilder};
+use super::Integrator;
+
+/// 单向路径追踪，在每个非镜面交点上对场景中的光源做直接光照采样。
+#[derive(Debug, Clone)]
+pub struct PathTracer {
+    pub max_depth: u32,
+    /// 从第几次弹射开始用俄罗斯轮盘赌终止路径。
+    pub rr_depth: u32,
+    pub background: Rgb,
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer {
+            max_depth: 16,
+            rr_depth: 3,
+            background: Rgb::black(),
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler) -> Rgb {
+        let mut radiance = Rgb::black();
+        let mut throughput = Rgb::white();
+        let mut ray = ray.clone();
+
+        for depth in 0..self.max_depth {
+            let (shape, hit) = match scene.intersect(&ray) {
+                Some(x) => x,
+                None => {
+                    radiance += throughput * self.background;
+                    break;
+                }
+            };
+            let wo = -ray.direction.normalize();
+            let albedo = shape.texture.get_at_hit(&hit);
+            let material = &shape.material;
+
+            //面光源只能被路径击中；点光源之类无法被击中，只能在这里直接采样，所以不会重复计算。
+            radiance += throughput * material.emitted(&wo, &hit, albedo);
+            for light in &scene.lights {
+                if let Some(sample) = light.illuminate(&hit.pos) {
+                    let f = material.eval(&wo, &sample.direction, &hit, albedo);
+                    if f.is_black() {
+                        continue;
+                    }
+                    let shadow = RayBuilder {
+                        origin: hit.pos,
+                        direction: sample.direction,
+                    }.build_at(ray.time);
+                    if !scene.occluded(&shadow, sample.distance) {
+                        let cos = hit.shading_normal.dot(sample.direction).abs();
+                        radiance += throughput * f * sample.radiance * cos;
+                    }
+                }
+            }
+
+            let scatter = match material.scatter(&wo, &hit, albedo, &sampler.get_2d()) {
+                Some(s) => s,
+                None => break,
+            };
+            throughput = throughput * scatter.weight;
+            if throughput.is_black() {
+                break;
+            }
+
+            if depth >= self.rr_depth {
+                let survive = throughput.max_component().min(0.95);
+                if sampler.get_1d() >= survive {
+                    break;
+                }
+                throughput = throughput / survive;
+            }
+
+            ray = RayBuilder {
+                origin: hit.pos,
+                direction: scatter.direction,
+            }.build_at(ray.time);
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::{CameraBuilder, ThinLens};
+    use light::{DirectionalLight, Light};
+    use material::Emissive;
+    use shapes::{pure_color_shape, Rect, Sphere, TexedShape};
+    use std::f32;
+
+    fn scene(shapes: Vec<TexedShape>) -> Scene {
+        let camera = CameraBuilder {
+            lens: ThinLens {
+                radius: 0.0,
+                center: Vector3::zero(),
+                focal_length: 1.0,
+            },
+            at: Vector3::zero(),
+            target: -Vector3::unit_z(),
+            up: Vector3::unit_y(),
+            aspect_ratio: 1.0,
+            fov: f32::consts::PI / 4.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }.build();
+        let lights: Vec<Box<dyn Light>> = vec![Box::new(DirectionalLight {
+            direction: -Vector3::unit_y(),
+            radiance: Rgb::white(),
+        })];
+        Scene::new(camera, shapes, lights)
+    }
+
+    //只走一段路径，结果就是交点处的发光加上直接光照。
+    fn direct_only() -> PathTracer {
+        PathTracer {
+            max_depth: 1,
+            ..PathTracer::default()
+        }
+    }
+
+    #[test]
+    fn sees_emission() {
+        let mut lamp = pure_color_shape(
+            Rgb::new(1.0, 0.5, 0.25),
+            Rect::new(
+                vec3(-1.0, -1.0, -5.0),
+                vec3(2.0, 0.0, 0.0),
+                vec3(0.0, 2.0, 0.0),
+            ),
+        );
+        lamp.material = Box::new(Emissive { intensity: 4.0 });
+        let ray = RayBuilder {
+            origin: Vector3::zero(),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let c = direct_only().radiance(&scene(vec![lamp]), &ray, &mut Sampler::new(0));
+        assert_relative_eq!(c.r, 4.0);
+        assert_relative_eq!(c.g, 2.0);
+        assert_relative_eq!(c.b, 1.0);
+    }
+
+    #[test]
+    fn lit_and_shadowed() {
+        let ground = pure_color_shape(
+            Rgb::white(),
+            Sphere::new(vec3(0.0, -1000.0, 0.0), 1000.0),
+        );
+        let blocker = pure_color_shape(Rgb::white(), Sphere::new(vec3(0.0, 2.0, 0.0), 0.5));
+        let scene = scene(vec![ground, blocker]);
+        let tracer = direct_only();
+        let mut sampler = Sampler::new(0);
+
+        let down = |x: f32| {
+            RayBuilder {
+                origin: vec3(x, 1.0, 0.0),
+                direction: -Vector3::unit_y(),
+            }.build()
+        };
+        //正对平行光的白色漫反射面，亮度是 1 / pi；挡住光的地方没有直接光照。
+        let lit = tracer.radiance(&scene, &down(3.0), &mut sampler);
+        assert_relative_eq!(lit.r, 1.0 / f32::consts::PI, epsilon = 1e-3);
+        let shadowed = tracer.radiance(&scene, &down(0.0), &mut sampler);
+        assert_relative_eq!(shadowed.r, 0.0);
+    }
+}
@@ -0,0 +1,125 @@
+use math::*;
+use rgb::Rgb;
+use sample::Sampler;
+use scene::Scene;
+use shapes::{Ray, RayBuilder};
+use super::Integrator;
+
+/// Whitted 风格的光线追踪：光源直接照明加阴影测试，镜面反射和折射递归到固定深度。
+/// 结果是确定性的，不使用 `sampler`。
+#[derive(Debug, Clone)]
+pub struct Whitted {
+    pub max_depth: u32,
+    pub background: Rgb,
+}
+
+impl Default for Whitted {
+    fn default() -> Self {
+        Whitted {
+            max_depth: 5,
+            background: Rgb::black(),
+        }
+    }
+}
+
+impl Whitted {
+    fn trace(&self, scene: &Scene, ray: &Ray, depth: u32) -> Rgb {
+        let (shape, hit) = match scene.intersect(ray) {
+            Some(x) => x,
+            None => return self.background,
+        };
+        let wo = -ray.direction.normalize();
+        let albedo = shape.texture.get_at_hit(&hit);
+        let material = &shape.material;
+
+        let mut radiance = material.emitted(&wo, &hit, albedo);
+        for light in &scene.lights {
+            if let Some(sample) = light.illuminate(&hit.pos) {
+                let f = material.eval(&wo, &sample.direction, &hit, albedo);
+                if f.is_black() {
+                    continue;
+                }
+                let shadow = RayBuilder {
+                    origin: hit.pos,
+                    direction: sample.direction,
+                }.build_at(ray.time);
+                if !scene.occluded(&shadow, sample.distance) {
+                    let cos = hit.shading_normal.dot(sample.direction).abs();
+                    radiance += f * sample.radiance * cos;
+                }
+            }
+        }
+
+        if depth + 1 < self.max_depth {
+            for lobe in material.specular_lobes(&wo, &hit, albedo) {
+                let next = RayBuilder {
+                    origin: hit.pos,
+                    direction: lobe.direction,
+                }.build_at(ray.time);
+                radiance += lobe.weight * self.trace(scene, &next, depth + 1);
+            }
+        }
+        radiance
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(&self, scene: &Scene, ray: &Ray, _sampler: &mut Sampler) -> Rgb {
+        self.trace(scene, ray, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::{CameraBuilder, ThinLens};
+    use light::{DirectionalLight, Light};
+    use shapes::{pure_color_shape, Sphere};
+    use std::f32;
+
+    fn scene(shapes: Vec<::shapes::TexedShape>) -> Scene {
+        let camera = CameraBuilder {
+            lens: ThinLens {
+                radius: 0.0,
+                center: Vector3::zero(),
+                focal_length: 1.0,
+            },
+            at: Vector3::zero(),
+            target: -Vector3::unit_z(),
+            up: Vector3::unit_y(),
+            aspect_ratio: 1.0,
+            fov: f32::consts::PI / 4.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }.build();
+        let lights: Vec<Box<dyn Light>> = vec![Box::new(DirectionalLight {
+            direction: -Vector3::unit_y(),
+            radiance: Rgb::white(),
+        })];
+        Scene::new(camera, shapes, lights)
+    }
+
+    #[test]
+    fn lit_and_shadowed() {
+        let ground = pure_color_shape(
+            Rgb::white(),
+            Sphere::new(vec3(0.0, -1000.0, 0.0), 1000.0),
+        );
+        let blocker = pure_color_shape(Rgb::white(), Sphere::new(vec3(0.0, 2.0, 0.0), 0.5));
+        let scene = scene(vec![ground, blocker]);
+        let whitted = Whitted::default();
+        let mut sampler = Sampler::new(0);
+
+        let down = |x: f32| {
+            RayBuilder {
+                origin: vec3(x, 1.0, 0.0),
+                direction: -Vector3::unit_y(),
+            }.build()
+        };
+        //正对平行光的白色漫反射面，亮度是 1 / pi。
+        let lit = whitted.radiance(&scene, &down(3.0), &mut sampler);
+        assert_relative_eq!(lit.r, 1.0 / f32::consts::PI, epsilon = 1e-3);
+        let shadowed = whitted.radiance(&scene, &down(0.0), &mut sampler);
+        assert_relative_eq!(shadowed.r, 0.0);
+    }
+}
@@ -3,7 +3,6 @@ extern crate rand;
 #[macro_use]
 mod macros;
 
-#[macro_use]
 extern crate approx;
 
 extern crate cgmath;
@@ -15,10 +14,20 @@ pub mod camera;
 pub mod rgb;
 pub mod shapes;
 pub mod texture;
+pub mod tonemap;
 pub mod vertices;
 pub mod bvh;
+pub mod film;
+pub mod hdr;
+pub mod light;
+pub mod integrator;
+pub mod material;
+pub mod mesh;
+pub mod scene;
+pub mod render;
 
 pub use math::*;
 pub use camera::*;
 pub use rgb::Rgb;
 pub use shapes::*;
+pub use scene::Scene;
@@ -0,0 +1,128 @@
+use math::*;
+use rgb::Rgb;
+use std::f32;
+
+/// 光源对某一点的照明。
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// 从被照亮的点指向光源的单位向量。
+    pub direction: Vector3,
+    /// 到光源的距离，平行光为无穷远。
+    pub distance: f32,
+    pub radiance: Rgb,
+}
+
+pub trait Light: Send + Sync {
+    fn illuminate(&self, pos: &Vector3) -> Option<LightSample>;
+}
+
+/// 向各个方向均匀发光的点光源，强度按距离平方衰减。
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    pub pos: Vector3,
+    pub intensity: Rgb,
+}
+
+impl Light for PointLight {
+    fn illuminate(&self, pos: &Vector3) -> Option<LightSample> {
+        let delta = self.pos - pos;
+        let distance2 = delta.magnitude2();
+        if distance2 == 0.0 {
+            return None;
+        }
+        let distance = distance2.sqrt();
+        Some(LightSample {
+            direction: delta / distance,
+            distance,
+            radiance: self.intensity / distance2,
+        })
+    }
+}
+
+/// 无穷远处的平行光。
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+    /// 光传播的方向。
+    pub direction: Vector3,
+    pub radiance: Rgb,
+}
+
+impl Light for DirectionalLight {
+    fn illuminate(&self, _pos: &Vector3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction.normalize(),
+            distance: f32::INFINITY,
+            radiance: self.radiance,
+        })
+    }
+}
+
+/// 聚光灯，`falloff_start` 以内全亮，到 `total_width` 平滑衰减到零，两者都是与轴线的夹角。
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    pub pos: Vector3,
+    pub direction: Vector3,
+    pub intensity: Rgb,
+    pub falloff_start: f32,
+    pub total_width: f32,
+}
+
+impl SpotLight {
+    fn falloff(&self, to_pos: &Vector3) -> f32 {
+        let cos_theta = self.direction.normalize().dot(*to_pos);
+        let cos_total = self.total_width.cos();
+        let cos_start = self.falloff_start.cos();
+        if cos_theta < cos_total {
+            0.0
+        } else if cos_theta >= cos_start {
+            1.0
+        } else {
+            let delta = (cos_theta - cos_total) / (cos_start - cos_total);
+            delta * delta * (3.0 - 2.0 * delta)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn illuminate(&self, pos: &Vector3) -> Option<LightSample> {
+        let point = PointLight {
+            pos: self.pos,
+            intensity: self.intensity,
+        };
+        let sample = point.illuminate(pos)?;
+        let falloff = self.falloff(&-sample.direction);
+        if falloff == 0.0 {
+            None
+        } else {
+            Some(LightSample {
+                radiance: sample.radiance * falloff,
+                ..sample
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn spot_light_cone() {
+        let spot = SpotLight {
+            pos: vec3(0.0, 1.0, 0.0),
+            direction: -Vector3::unit_y(),
+            intensity: Rgb::white(),
+            falloff_start: PI / 8.0,
+            total_width: PI / 4.0,
+        };
+        let below = spot.illuminate(&Vector3::zero()).unwrap();
+        assert_relative_eq!(below.radiance.r, 1.0);
+        assert_relative_eq!(below.direction, Vector3::unit_y());
+
+        //夹角 45° 附近已经在衰减区内，更远处完全照不到。
+        let edge = spot.illuminate(&vec3(0.9, 0.0, 0.0)).unwrap();
+        assert!(edge.radiance.r > 0.0 && edge.radiance.r < 1.0 / 1.81);
+        assert!(spot.illuminate(&vec3(2.0, 0.0, 0.0)).is_none());
+    }
+}
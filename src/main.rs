@@ -0,0 +1,308 @@
+extern crate image;
+extern crate rrt;
+
+use rrt::hdr;
+use rrt::sample::PixelSampler;
+use rrt::scene::IntegratorKind;
+use rrt::tonemap::{ToneMap, ToneMapper};
+use rrt::Scene;
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::time::Instant;
+
+const USAGE: &str = "usage: rrt <scene> [options]
+
+options:
+  -o, --output <file>     output image, .png, .hdr or .pfm (default: out.png)
+  --width <n>             image width, overrides the scene file
+  --height <n>            image height, overrides the scene file
+  --spp <n>               samples per pixel
+  --sampler <name>        random, jitter or nrooks
+  --integrator <name>     path or whitted
+  --threads <n>           worker threads, 0 uses every CPU
+  --seed <n>              random seed
+  --exposure <stops>      exposure adjustment for .png output
+  --tonemap <name>        clamp, reinhard, reinhard-extended:<white> or aces for .png output
+  -q, --quiet             no progress output
+  --help                  show this message";
+
+/// 输出文件的格式，由扩展名决定。
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum OutputFormat {
+    #[default]
+    Png,
+    Hdr,
+    Pfm,
+}
+
+impl OutputFormat {
+    fn from_path(path: &str) -> Option<OutputFormat> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("png") => Some(OutputFormat::Png),
+            Some("hdr") => Some(OutputFormat::Hdr),
+            Some("pfm") => Some(OutputFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+/// 命令行参数，没给出的项使用场景文件里的设置。
+#[derive(Debug, Default)]
+struct Options {
+    scene: String,
+    output: String,
+    format: OutputFormat,
+    width: Option<u32>,
+    height: Option<u32>,
+    spp: Option<u32>,
+    sampler: Option<PixelSampler>,
+    integrator: Option<IntegratorKind>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    tone_mapper: ToneMapper,
+    quiet: bool,
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for {}", value, name))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        output: "out.png".to_string(),
+        ..Options::default()
+    };
+    let mut scene = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.as_str();
+        if !arg.starts_with('-') {
+            if scene.is_some() {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+            scene = Some(arg.to_string());
+            continue;
+        }
+        match arg {
+            "-q" | "--quiet" => {
+                options.quiet = true;
+                continue;
+            }
+            "--help" => return Err(String::new()),
+            _ => {}
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", arg))?;
+        match arg {
+            "-o" | "--output" => {
+                //在渲染之前就检查扩展名，免得渲染完才发现写不出来。
+                options.format = OutputFormat::from_path(value).ok_or_else(|| {
+                    format!(
+                        "{}: unsupported output format, expected .png, .hdr or .pfm",
+                        value
+                    )
+                })?;
+                options.output = value.clone();
+            }
+            "--width" => options.width = Some(parse_value(arg, value)?),
+            "--height" => options.height = Some(parse_value(arg, value)?),
+            "--spp" => options.spp = Some(parse_value(arg, value)?),
+            "--threads" => options.threads = Some(parse_value(arg, value)?),
+            "--seed" => options.seed = Some(parse_value(arg, value)?),
+            "--exposure" => options.tone_mapper.exposure = parse_value(arg, value)?,
+            "--sampler" => {
+                options.sampler = Some(match value.as_str() {
+                    "random" => PixelSampler::Random,
+                    "jitter" => PixelSampler::Jitter,
+                    "nrooks" => PixelSampler::NRooks,
+                    _ => return Err(format!("unknown sampler `{}`", value)),
+                })
+            }
+            "--integrator" => {
+                options.integrator = Some(match value.as_str() {
+                    "path" => IntegratorKind::Path,
+                    "whitted" => IntegratorKind::Whitted,
+                    _ => return Err(format!("unknown integrator `{}`", value)),
+                })
+            }
+            "--tonemap" => {
+                options.tone_mapper.operator = match value.as_str() {
+                    "clamp" => ToneMap::Clamp,
+                    "reinhard" => ToneMap::Reinhard,
+                    "aces" => ToneMap::Aces,
+                    _ if value.starts_with("reinhard-extended:") => ToneMap::ReinhardExtended {
+                        white: parse_value(arg, &value["reinhard-extended:".len()..])?,
+                    },
+                    _ => return Err(format!("unknown tone mapping operator `{}`", value)),
+                }
+            }
+            _ => return Err(format!("unknown option `{}`", arg)),
+        }
+    }
+    if options.width == Some(0) || options.height == Some(0) || options.spp == Some(0) {
+        return Err("image size and spp must be positive".to_string());
+    }
+    options.scene = scene.ok_or_else(|| "missing scene file".to_string())?;
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let start = Instant::now();
+    let mut scene = Scene::load(&options.scene).map_err(|e| e.to_string())?;
+    let load_time = start.elapsed();
+
+    {
+        let settings = &mut scene.settings;
+        if options.width.is_some() || options.height.is_some() {
+            settings.width = options.width.unwrap_or(settings.width);
+            settings.height = options.height.unwrap_or(settings.height);
+        }
+        if let Some(spp) = options.spp {
+            settings.renderer.spp = spp;
+        }
+        if let Some(sampler) = options.sampler {
+            settings.renderer.sampler = sampler;
+        }
+        if let Some(integrator) = options.integrator {
+            settings.integrator = integrator;
+        }
+        if let Some(threads) = options.threads {
+            settings.renderer.threads = threads;
+        }
+        if let Some(seed) = options.seed {
+            settings.renderer.seed = seed;
+        }
+    }
+    if options.width.is_some() || options.height.is_some() {
+        let aspect_ratio = scene.settings.width as f32 / scene.settings.height as f32;
+        scene.camera = scene.camera.with_aspect_ratio(aspect_ratio);
+    }
+
+    let settings = scene.settings.clone();
+    let (width, height) = (settings.width, settings.height);
+    if !options.quiet {
+        eprintln!(
+            "scene: {} shapes, {} lights, loaded in {:.2}s",
+            scene.shapes().len(),
+            scene.lights.len(),
+            load_time.as_secs_f64()
+        );
+    }
+
+    let mut film = settings.film();
+    let integrator = settings.integrator();
+    let start = Instant::now();
+    let quiet = options.quiet;
+    settings
+        .renderer
+        .render_with_progress(&scene, &*integrator, &mut film, |done, total| {
+            if !quiet {
+                eprint!(
+                    "\rrendering {:3}% ({}/{} tiles)",
+                    done * 100 / total,
+                    done,
+                    total
+                );
+            }
+        });
+    let render_time = start.elapsed().as_secs_f64();
+    if !quiet {
+        let samples = f64::from(width) * f64::from(height) * f64::from(settings.renderer.spp);
+        eprintln!();
+        eprintln!(
+            "render: {}x{} at {} spp in {:.2}s, {:.2} Msamples/s",
+            width,
+            height,
+            settings.renderer.spp,
+            render_time,
+            samples / render_time / 1e6
+        );
+    }
+
+    let pixels = film.resolve();
+    let path = Path::new(&options.output);
+    let create = || {
+        File::create(path)
+            .map(BufWriter::new)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    let written = match options.format {
+        OutputFormat::Hdr => hdr::write_hdr(&mut create()?, width, height, &pixels),
+        OutputFormat::Pfm => hdr::write_pfm(&mut create()?, width, height, &pixels),
+        OutputFormat::Png => {
+            let img = options.tone_mapper.to_image(width, height, &pixels);
+            image::ImageRgb8(img)
+                .save(&mut create()?, image::PNG)
+                .map_err(|e| std::io::Error::other(e.to_string()))
+        }
+    };
+    written.map_err(|e| format!("{}: {}", path.display(), e))?;
+    if !quiet {
+        eprintln!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            if msg.is_empty() {
+                println!("{}", USAGE);
+                return;
+            }
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(msg) = run(&options) {
+        eprintln!("error: {}", msg);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse_args(&args(
+            "scene.txt --spp 8 --sampler jitter -o out.hdr --threads 2 --integrator whitted -q",
+        ))
+        .unwrap();
+        assert_eq!(options.scene, "scene.txt");
+        assert_eq!(options.output, "out.hdr");
+        assert_eq!(options.format, OutputFormat::Hdr);
+        assert_eq!(options.spp, Some(8));
+        assert_eq!(options.sampler, Some(PixelSampler::Jitter));
+        assert_eq!(options.integrator, Some(IntegratorKind::Whitted));
+        assert_eq!(options.threads, Some(2));
+        assert!(options.quiet);
+
+        assert!(parse_args(&args("--spp 8")).is_err());
+        assert!(parse_args(&args("a --spp many")).is_err());
+        assert!(parse_args(&args("a --sampler sobol")).is_err());
+        assert!(parse_args(&args("a -o x.jpg")).is_err());
+        assert_eq!(parse_args(&args("a")).unwrap().format, OutputFormat::Png);
+
+        let options = parse_args(&args("a --tonemap reinhard-extended:4")).unwrap();
+        assert_eq!(
+            options.tone_mapper.operator,
+            ToneMap::ReinhardExtended { white: 4.0 }
+        );
+        assert!(parse_args(&args("a --tonemap reinhard-extended:x")).is_err());
+    }
+}
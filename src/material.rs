@@ -0,0 +1,279 @@
+use math::*;
+use rgb::Rgb;
+use sample::cosine_hemisphere;
+use shapes::HitRecord;
+use std::f32;
+
+/// 一次散射的结果。
+#[derive(Debug, Clone, Copy)]
+pub struct Scatter {
+    /// 散射后的方向，背离表面。
+    pub direction: Vector3,
+    /// `f * |cos| / pdf`，直接乘到路径的吞吐量上。
+    pub weight: Rgb,
+    pub pdf: f32,
+    /// 镜面反射、折射这类 delta 分布，不能通过光源采样计算。
+    pub specular: bool,
+}
+
+/// 描述光在表面上如何散射。`wo` 和 `wi` 都背离表面，`albedo` 来自物体的纹理。
+pub trait Material: Send + Sync {
+    /// 用 `[0, 1)^2` 上的样本采样出射方向，被吸收时返回 `None`。
+    fn scatter(
+        &self,
+        wo: &Vector3,
+        hit: &HitRecord,
+        albedo: Rgb,
+        sample: &Vector2,
+    ) -> Option<Scatter>;
+
+    /// BSDF `f(wo, wi)`，delta 分布总是返回黑色。
+    fn eval(&self, _wo: &Vector3, _wi: &Vector3, _hit: &HitRecord, _albedo: Rgb) -> Rgb {
+        Rgb::black()
+    }
+
+    fn emitted(&self, _wo: &Vector3, _hit: &HitRecord, _albedo: Rgb) -> Rgb {
+        Rgb::black()
+    }
+
+    /// 全部镜面分量，`weight` 已经乘上了选择它的概率。确定性的积分器用它代替 `scatter`。
+    fn specular_lobes(&self, _wo: &Vector3, _hit: &HitRecord, _albedo: Rgb) -> Vec<Scatter> {
+        Vec::new()
+    }
+}
+
+/// 朝向 `wo` 一侧的法线。
+fn face_forward(n: &Vector3, wo: &Vector3) -> Vector3 {
+    if n.dot(*wo) < 0.0 {
+        -*n
+    } else {
+        *n
+    }
+}
+
+/// 电介质的菲涅尔反射率，`cos_i` 为入射角余弦。
+pub fn fresnel_dielectric(cos_i: f32, eta_i: f32, eta_t: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin_t = eta_i / eta_t * (1.0 - cos_i * cos_i).max(0.0).sqrt();
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).max(0.0).sqrt();
+    let parallel = (eta_t * cos_i - eta_i * cos_t) / (eta_t * cos_i + eta_i * cos_t);
+    let perpendicular = (eta_i * cos_i - eta_t * cos_t) / (eta_i * cos_i + eta_t * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+/// 理想漫反射。
+#[derive(Debug, Clone, Copy)]
+pub struct Lambertian;
+
+impl Material for Lambertian {
+    fn scatter(
+        &self,
+        wo: &Vector3,
+        hit: &HitRecord,
+        albedo: Rgb,
+        sample: &Vector2,
+    ) -> Option<Scatter> {
+        let n = face_forward(&hit.shading_normal, wo);
+        let (t, b) = coordinate_system(&n);
+        let local = cosine_hemisphere(sample);
+        if local.z <= 0.0 {
+            return None;
+        }
+        Some(Scatter {
+            direction: (t * local.x + b * local.y + n * local.z).normalize(),
+            weight: albedo,
+            pdf: local.z / f32::consts::PI,
+            specular: false,
+        })
+    }
+
+    fn eval(&self, wo: &Vector3, wi: &Vector3, hit: &HitRecord, albedo: Rgb) -> Rgb {
+        if hit.shading_normal.dot(*wo) * hit.shading_normal.dot(*wi) > 0.0 {
+            albedo / f32::consts::PI
+        } else {
+            Rgb::black()
+        }
+    }
+}
+
+/// 理想镜面反射。
+#[derive(Debug, Clone, Copy)]
+pub struct Mirror;
+
+impl Material for Mirror {
+    fn scatter(
+        &self,
+        wo: &Vector3,
+        hit: &HitRecord,
+        albedo: Rgb,
+        _sample: &Vector2,
+    ) -> Option<Scatter> {
+        self.specular_lobes(wo, hit, albedo).pop()
+    }
+
+    fn specular_lobes(&self, wo: &Vector3, hit: &HitRecord, albedo: Rgb) -> Vec<Scatter> {
+        let n = face_forward(&hit.shading_normal, wo);
+        vec![Scatter {
+            direction: reflect(&-*wo, &n),
+            weight: albedo,
+            pdf: 1.0,
+            specular: true,
+        }]
+    }
+}
+
+/// 玻璃之类的透明电介质，按菲涅尔项在反射和折射之间随机选择。
+#[derive(Debug, Clone, Copy)]
+pub struct Dielectric {
+    pub ior: f32,
+}
+
+impl Dielectric {
+    /// 反射方向、折射方向（全反射时没有）以及菲涅尔反射率。
+    fn split(&self, wo: &Vector3, hit: &HitRecord) -> (Vector3, Option<Vector3>, f32) {
+        //法线朝外，wo 在法线一侧说明射线正在进入物体。
+        let entering = hit.shading_normal.dot(*wo) > 0.0;
+        let (eta_i, eta_t) = if entering {
+            (1.0, self.ior)
+        } else {
+            (self.ior, 1.0)
+        };
+        let n = face_forward(&hit.shading_normal, wo);
+        let fresnel = fresnel_dielectric(n.dot(*wo), eta_i, eta_t);
+        let reflected = reflect(&-*wo, &n);
+        match refract(&-*wo, &n, eta_i / eta_t) {
+            Some(dir) => (reflected, Some(dir.normalize()), fresnel),
+            None => (reflected, None, 1.0),
+        }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(
+        &self,
+        wo: &Vector3,
+        hit: &HitRecord,
+        albedo: Rgb,
+        sample: &Vector2,
+    ) -> Option<Scatter> {
+        let (reflected, refracted, fresnel) = self.split(wo, hit);
+        let direction = match refracted {
+            Some(dir) if sample.x >= fresnel => dir,
+            _ => reflected,
+        };
+        Some(Scatter {
+            direction,
+            weight: albedo,
+            pdf: 1.0,
+            specular: true,
+        })
+    }
+
+    fn specular_lobes(&self, wo: &Vector3, hit: &HitRecord, albedo: Rgb) -> Vec<Scatter> {
+        let (reflected, refracted, fresnel) = self.split(wo, hit);
+        let mut lobes = vec![Scatter {
+            direction: reflected,
+            weight: albedo * fresnel,
+            pdf: fresnel,
+            specular: true,
+        }];
+        if let Some(direction) = refracted {
+            lobes.push(Scatter {
+                direction,
+                weight: albedo * (1.0 - fresnel),
+                pdf: 1.0 - fresnel,
+                specular: true,
+            });
+        }
+        lobes
+    }
+}
+
+/// 自发光表面，不散射光线。
+#[derive(Debug, Clone, Copy)]
+pub struct Emissive {
+    pub intensity: f32,
+}
+
+impl Material for Emissive {
+    fn scatter(
+        &self,
+        _wo: &Vector3,
+        _hit: &HitRecord,
+        _albedo: Rgb,
+        _sample: &Vector2,
+    ) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _wo: &Vector3, _hit: &HitRecord, albedo: Rgb) -> Rgb {
+        albedo * self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use texture::Footprint;
+
+    fn hit() -> HitRecord {
+        HitRecord {
+            t: 1.0,
+            pos: Vector3::zero(),
+            normal: Vector3::unit_z(),
+            shading_normal: Vector3::unit_z(),
+            uv: Vector2::zero(),
+            barycentric: Vector3::zero(),
+            tangent: Vector3::unit_x(),
+            bitangent: Vector3::unit_y(),
+            dpdu: Vector3::unit_x(),
+            dpdv: Vector3::unit_y(),
+            footprint: Footprint::zero(),
+        }
+    }
+
+    #[test]
+    fn fresnel_normal_incidence() {
+        let r0 = (1.5f32 - 1.0) / (1.5 + 1.0);
+        assert_relative_eq!(fresnel_dielectric(1.0, 1.0, 1.5), r0 * r0, epsilon = 1e-6);
+        //从玻璃内部以大角度射出会发生全反射。
+        assert_relative_eq!(fresnel_dielectric(0.1, 1.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn mirror_reflects() {
+        let wo = vec3(1.0, 0.0, 1.0).normalize();
+        let s = Mirror
+            .scatter(&wo, &hit(), Rgb::white(), &vec2(0.5, 0.5))
+            .unwrap();
+        assert_relative_eq!(s.direction, vec3(-1.0, 0.0, 1.0).normalize());
+    }
+
+    #[test]
+    fn glass_refracts_towards_normal() {
+        let wo = vec3(1.0, 0.0, 1.0).normalize();
+        let glass = Dielectric { ior: 1.5 };
+        //样本大于菲涅尔项时选择折射。
+        let s = glass
+            .scatter(&wo, &hit(), Rgb::white(), &vec2(0.99, 0.5))
+            .unwrap();
+        assert!(s.direction.z < 0.0);
+        let sin_t = s.direction.x.abs();
+        assert_relative_eq!(sin_t * 1.5, (0.5f32).sqrt(), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn lambertian_stays_in_hemisphere() {
+        let wo = Vector3::unit_z();
+        for &(x, y) in &[(0.1, 0.2), (0.9, 0.3), (0.5, 0.99)] {
+            let s = Lambertian
+                .scatter(&wo, &hit(), Rgb::white(), &vec2(x, y))
+                .unwrap();
+            assert!(s.direction.z > 0.0);
+            assert_relative_eq!(s.pdf, s.direction.z / f32::consts::PI, epsilon = 1e-5);
+        }
+    }
+}
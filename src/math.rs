@@ -2,6 +2,7 @@ extern crate cgmath;
 extern crate rand;
 
 pub use self::cgmath::*;
+use std::cmp::Ordering;
 use std::ops::{Add, Mul, Sub};
 
 pub type Vector4 = cgmath::Vector4<f32>;
@@ -28,3 +29,183 @@ pub fn make_pos(vec3: &Vector3) -> Vector4 {
 pub fn make_dir(vec3: &Vector3) -> Vector4 {
     vec3.extend(0.0)
 }
+
+/// 以 `n` 为 z 轴构造一组正交基，返回另外两个轴。
+pub fn coordinate_system(n: &Vector3) -> (Vector3, Vector3) {
+    let t = if n.x.abs() > n.y.abs() {
+        vec3(-n.z, 0.0, n.x) / (n.x * n.x + n.z * n.z).sqrt()
+    } else {
+        vec3(0.0, n.z, -n.y) / (n.y * n.y + n.z * n.z).sqrt()
+    };
+    (t, n.cross(t))
+}
+
+/// `dir` 关于法线 `n` 的镜面反射方向。
+pub fn reflect(dir: &Vector3, n: &Vector3) -> Vector3 {
+    dir - n * (2.0 * dir.dot(*n))
+}
+
+/// 按 Snell 定律折射，`eta` 为入射侧与透射侧折射率之比，`n` 与 `dir` 方向相反。
+/// 发生全反射时返回 `None`。
+pub fn refract(dir: &Vector3, n: &Vector3, eta: f32) -> Option<Vector3> {
+    let cos_i = -dir.dot(*n);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        None
+    } else {
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(dir * eta + n * (eta * cos_i - cos_t))
+    }
+}
+
+/// `a t^2 + b t + c = 0` 的两个实根，从小到大排列；没有实根时返回 `None`。
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    //避免 b 与判别式的平方根相近时相减损失精度。
+    let q = if b < 0.0 {
+        -0.5 * (b - discriminant.sqrt())
+    } else {
+        -0.5 * (b + discriminant.sqrt())
+    };
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// 仿射变换以及它的逆，可以表示非均匀缩放。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Affine {
+    pub fn identity() -> Affine {
+        Affine {
+            matrix: Matrix::identity(),
+            inverse: Matrix::identity(),
+        }
+    }
+
+    /// 不可逆的矩阵返回 `None`。
+    pub fn new(matrix: Matrix) -> Option<Affine> {
+        matrix.invert().map(|inverse| Affine { matrix, inverse })
+    }
+
+    pub fn scale(x: f32, y: f32, z: f32) -> Option<Affine> {
+        Affine::new(Matrix::from_nonuniform_scale(x, y, z))
+    }
+
+    pub fn translation(v: Vector3) -> Affine {
+        Affine {
+            matrix: Matrix::from_translation(v),
+            inverse: Matrix::from_translation(-v),
+        }
+    }
+
+    /// `rot` 是单位四元数。
+    pub fn rotation(rot: Quaternion) -> Affine {
+        Affine {
+            matrix: rot.into(),
+            inverse: rot.conjugate().into(),
+        }
+    }
+
+    /// 缩放为零的变换不可逆，返回 `None`。
+    pub fn from_transformation(t: &Transformation) -> Option<Affine> {
+        t.inverse_transform().map(|inverse| Affine {
+            matrix: (*t).into(),
+            inverse: inverse.into(),
+        })
+    }
+
+    /// 先做 `self` 再做 `next`。
+    pub fn then(&self, next: &Affine) -> Affine {
+        Affine {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Affine {
+        Affine {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: &Vector3) -> Vector3 {
+        (self.matrix * make_pos(p)).truncate()
+    }
+
+    pub fn vector(&self, v: &Vector3) -> Vector3 {
+        (self.matrix * make_dir(v)).truncate()
+    }
+
+    /// 法线要乘逆矩阵的转置才能在非均匀缩放下保持与表面垂直，结果没有归一化。
+    pub fn normal(&self, n: &Vector3) -> Vector3 {
+        (cgmath::Matrix::transpose(&self.inverse) * make_dir(n)).truncate()
+    }
+}
+
+/// 按时间插值的关键帧变换：位移和缩放线性插值，旋转用四元数球面插值。
+/// 早于第一帧或晚于最后一帧时取端点的变换。
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    keys: Vec<(f32, Transformation)>,
+}
+
+impl AnimatedTransform {
+    /// `keys` 为 (时间, 变换)，不要求有序；至少要有一帧，缩放都要大于零。
+    pub fn new(mut keys: Vec<(f32, Transformation)>) -> AnimatedTransform {
+        assert!(!keys.is_empty(), "animated transform needs at least one key");
+        assert!(
+            keys.iter().all(|k| k.1.scale > 0.0),
+            "animated transform keys need a positive scale"
+        );
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        AnimatedTransform { keys }
+    }
+
+    pub fn keys(&self) -> &[(f32, Transformation)] {
+        &self.keys
+    }
+
+    pub fn at(&self, time: f32) -> Transformation {
+        let i = self.keys.iter().take_while(|k| k.0 <= time).count();
+        if i == 0 {
+            return self.keys[0].1;
+        }
+        if i == self.keys.len() {
+            return self.keys[i - 1].1;
+        }
+        let (t0, a) = self.keys[i - 1];
+        let (t1, b) = self.keys[i];
+        let s = (time - t0) / (t1 - t0);
+        //q 与 -q 表示同一个旋转，取夹角较小的一侧插值。
+        let rot = if a.rot.dot(b.rot) < 0.0 { -b.rot } else { b.rot };
+        Transformation {
+            scale: lerp(s, a.scale, b.scale),
+            rot: a.rot.slerp(rot, s),
+            disp: lerp(s, a.disp, b.disp),
+        }
+    }
+
+    /// `at` 对应的仿射变换。关键帧的缩放都大于零，插值后总是可逆的。
+    pub fn affine_at(&self, time: f32) -> Affine {
+        Affine::from_transformation(&self.at(time)).expect("keyframe scale must be positive")
+    }
+}
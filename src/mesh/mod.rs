@@ -0,0 +1,119 @@
+use bvh::{BBox, Bvh, Primitive};
+use math::Vector3;
+use shapes::{HitRecord, MeshTriangle, Ray, Shape};
+use std::sync::Arc;
+use vertices::{Vertex, VertexNormal, VertexUV, VertexUvn};
+
+pub mod obj;
+pub mod ply;
+
+pub use self::obj::{load_obj, read_mtl, read_obj, ObjMaterial, ObjModel};
+pub use self::ply::{load_ply, read_ply};
+
+/// 共享一个顶点缓冲区的三角形网格，每个三角形是三个顶点下标。
+pub struct IndexedMesh<T: Vertex> {
+    vertices: Arc<[T]>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl<T: Vertex + 'static> IndexedMesh<T> {
+    /// `indices` 中的下标必须小于 `vertices.len()`。
+    pub fn new(vertices: Vec<T>, indices: Vec<[usize; 3]>) -> Self {
+        assert!(indices.iter().flatten().all(|&i| i < vertices.len()));
+        IndexedMesh {
+            vertices: Arc::from(vertices),
+            indices,
+        }
+    }
+
+    pub fn vertices(&self) -> &[T] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[[usize; 3]] {
+        &self.indices
+    }
+
+    pub fn triangles(&self) -> Vec<MeshTriangle<T>> {
+        self.indices
+            .iter()
+            .map(|&points| MeshTriangle::new(self.vertices.clone(), points))
+            .collect()
+    }
+
+    pub fn to_shape(&self) -> MeshShape<T> {
+        MeshShape {
+            bvh: Bvh::new(self.triangles()),
+        }
+    }
+}
+
+/// 把整个网格当作一个形状，内部用物体空间中的 BVH 求交。
+/// 放进 `Arc` 后可以被多个 `TexedShape` 共享，以不同的变换实例化多次。
+pub struct MeshShape<T: Vertex> {
+    bvh: Bvh<MeshTriangle<T>>,
+}
+
+impl<T: Vertex> Shape for MeshShape<T> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        self.bvh.hit(ray, tmin, tmax).map(|(_, hit)| hit)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        Primitive::bounding_box(&self.bvh)
+    }
+}
+
+/// 按文件里实际有的顶点属性选择顶点类型的网格。
+pub enum Mesh {
+    Position(IndexedMesh<Vector3>),
+    Uv(IndexedMesh<VertexUV>),
+    Normal(IndexedMesh<VertexNormal>),
+    Uvn(IndexedMesh<VertexUvn>),
+}
+
+macro_rules! each_mesh {
+    ($mesh: expr, $m: ident => $body: expr) => {
+        match $mesh {
+            Mesh::Position(ref $m) => $body,
+            Mesh::Uv(ref $m) => $body,
+            Mesh::Normal(ref $m) => $body,
+            Mesh::Uvn(ref $m) => $body,
+        }
+    };
+}
+
+impl Mesh {
+    /// 三角形的数量。
+    pub fn len(&self) -> usize {
+        each_mesh!(*self, m => m.indices().len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn indices(&self) -> &[[usize; 3]] {
+        each_mesh!(*self, m => m.indices())
+    }
+
+    pub fn positions(&self) -> Vec<Vector3> {
+        each_mesh!(*self, m => m.vertices().iter().map(|v| *v.get_pos()).collect())
+    }
+
+    /// 整个网格作为一个形状。
+    pub fn shape(&self) -> Arc<dyn Shape> {
+        each_mesh!(*self, m => Arc::new(m.to_shape()))
+    }
+
+    /// 每个三角形一个形状，顺序与 `indices` 相同。
+    pub fn shapes(&self) -> Vec<Box<dyn Shape>> {
+        fn boxed<T: Vertex + 'static>(mesh: &IndexedMesh<T>) -> Vec<Box<dyn Shape>> {
+            mesh.triangles()
+                .into_iter()
+                .map(|t| Box::new(t) as Box<dyn Shape>)
+                .collect()
+        }
+        each_mesh!(*self, m => boxed(m))
+    }
+}
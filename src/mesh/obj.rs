@@ -0,0 +1,539 @@
+use super::{IndexedMesh, Mesh};
+use material::{Dielectric, Emissive, Lambertian, Material, Mirror};
+use math::*;
+use rgb::Rgb;
+use shapes::TexedShape;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::{FromStr, SplitWhitespace};
+use std::sync::Arc;
+use texture::{ImageTexture, Multiply, PureColorTexture, Texture};
+use vertices::{VertexNormal, VertexUV, VertexUvn};
+
+/// MTL 文件中的一个材质，只保留渲染器能用上的参数。
+#[derive(Debug, Clone)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Kd`
+    pub diffuse: Rgb,
+    /// `Ks`
+    pub specular: Rgb,
+    /// `Ke`
+    pub emission: Rgb,
+    /// `Ni`
+    pub ior: f32,
+    /// `d`，或者 `1 - Tr`。
+    pub dissolve: f32,
+    pub illum: u32,
+    /// `map_Kd`，相对于 MTL 文件所在的目录。
+    pub diffuse_map: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    pub fn new(name: &str) -> Self {
+        ObjMaterial {
+            name: name.to_string(),
+            diffuse: Rgb::new(0.8, 0.8, 0.8),
+            specular: Rgb::black(),
+            emission: Rgb::black(),
+            ior: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        !self.emission.is_black()
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.dissolve < 1.0 || [4, 6, 7].contains(&self.illum)
+    }
+
+    fn is_mirror(&self) -> bool {
+        self.illum == 3 || self.illum == 5
+    }
+
+    /// 漫反射材质设置了 `map_Kd` 时读入图像，再乘上 `Kd`。
+    pub fn texture(&self) -> io::Result<Arc<dyn Texture>> {
+        let color = if self.is_emissive() {
+            self.emission / self.emission.max_component()
+        } else if self.is_transparent() {
+            Rgb::white()
+        } else if self.is_mirror() {
+            self.specular
+        } else if let Some(ref path) = self.diffuse_map {
+            let image = ImageTexture::open(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            return Ok(Arc::new(Multiply {
+                a: Box::new(PureColorTexture {
+                    color: self.diffuse,
+                }),
+                b: Box::new(image),
+            }));
+        } else {
+            self.diffuse
+        };
+        Ok(Arc::new(PureColorTexture { color }))
+    }
+
+    /// 按 `Ke`、`illum` 和 `d` 近似成渲染器已有的材质。
+    pub fn material(&self) -> Box<dyn Material> {
+        if self.is_emissive() {
+            Box::new(Emissive {
+                intensity: self.emission.max_component(),
+            })
+        } else if self.is_transparent() {
+            Box::new(Dielectric { ior: self.ior })
+        } else if self.is_mirror() {
+            Box::new(Mirror)
+        } else {
+            Box::new(Lambertian)
+        }
+    }
+}
+
+pub struct ObjModel {
+    pub mesh: Mesh,
+    pub materials: Vec<ObjMaterial>,
+    /// 每个三角形的材质在 `materials` 中的下标，`usemtl` 之前的面或者找不到的材质为 `None`。
+    pub triangle_materials: Vec<Option<usize>>,
+}
+
+impl ObjModel {
+    /// 每个三角形一个 `TexedShape`，没有材质的三角形使用 `ObjMaterial` 的默认值。
+    ///
+    /// 每个材质的纹理只读一次，由用到它的三角形共享；读不出 `map_Kd` 时返回错误。
+    pub fn texed_shapes(&self, transform: Affine) -> io::Result<Vec<TexedShape>> {
+        let default = ObjMaterial::new("");
+        let textures = self
+            .materials
+            .iter()
+            .map(ObjMaterial::texture)
+            .collect::<io::Result<Vec<_>>>()?;
+        let default_texture = default.texture()?;
+        Ok(self
+            .mesh
+            .shapes()
+            .into_iter()
+            .zip(&self.triangle_materials)
+            .map(|(shape, &id)| {
+                let material = id.map_or(&default, |i| &self.materials[i]);
+                let texture = id.map_or(&default_texture, |i| &textures[i]);
+                TexedShape {
+                    texture: Box::new(Arc::clone(texture)),
+                    material: material.material(),
+                    shape,
+                    transform,
+                    motion: None,
+                }
+            })
+            .collect())
+    }
+}
+
+fn invalid(line: usize, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, msg),
+    )
+}
+
+fn parse<T: FromStr>(line: usize, token: Option<&str>) -> io::Result<T> {
+    let token = token.ok_or_else(|| invalid(line, "missing value"))?;
+    token
+        .parse()
+        .map_err(|_| invalid(line, &format!("invalid number `{}`", token)))
+}
+
+fn parse_vec3(line: usize, tokens: &mut SplitWhitespace) -> io::Result<Vector3> {
+    Ok(vec3(
+        parse(line, tokens.next())?,
+        parse(line, tokens.next())?,
+        parse(line, tokens.next())?,
+    ))
+}
+
+fn parse_rgb(line: usize, tokens: &mut SplitWhitespace) -> io::Result<Rgb> {
+    let v = parse_vec3(line, tokens)?;
+    Ok(Rgb::new(v.x, v.y, v.z))
+}
+
+/// OBJ 的下标从 1 开始，负数表示从当前末尾往前数。
+fn resolve_index(line: usize, token: &str, count: usize) -> io::Result<usize> {
+    let index: isize = parse(line, Some(token))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as isize + index
+    };
+    if index == 0 || resolved < 0 || resolved as usize >= count {
+        return Err(invalid(line, &format!("index {} out of range", index)));
+    }
+    Ok(resolved as usize)
+}
+
+/// 面的一个角：位置、纹理坐标和法线的下标。
+type Corner = (usize, Option<usize>, Option<usize>);
+
+fn parse_corner(line: usize, token: &str, counts: [usize; 3]) -> io::Result<Corner> {
+    let mut parts = token.split('/');
+    let pos = resolve_index(line, parts.next().unwrap_or(""), counts[0])?;
+    let mut optional = |count| match parts.next() {
+        Some(s) if !s.is_empty() => resolve_index(line, s, count).map(Some),
+        _ => Ok(None),
+    };
+    let uv = optional(counts[1])?;
+    let normal = optional(counts[2])?;
+    Ok((pos, uv, normal))
+}
+
+fn split_token(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    (&s[..end], s[end..].trim_start())
+}
+
+/// 纹理贴图一行里选项后面的部分都是文件名，文件名中可以有空格。
+fn map_path(rest: &str) -> Option<PathBuf> {
+    let mut rest = rest.trim();
+    while rest.starts_with('-') {
+        let (option, tail) = split_token(rest);
+        //`-o`、`-s`、`-t` 后面跟 1 到 3 个数，`-mm` 跟 2 个，其他选项跟 1 个。
+        let (min, max) = match option {
+            "-o" | "-s" | "-t" => (1, 3),
+            "-mm" => (2, 2),
+            _ => (1, 1),
+        };
+        rest = tail;
+        for k in 0..max {
+            let (arg, tail) = split_token(rest);
+            if k >= min && arg.parse::<f32>().is_err() {
+                break;
+            }
+            rest = tail;
+        }
+    }
+    if rest.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(rest))
+    }
+}
+
+/// 读取 MTL 文件。
+pub fn read_mtl<R: BufRead>(input: R) -> io::Result<Vec<ObjMaterial>> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+        if keyword == "newmtl" {
+            let name = tokens
+                .next()
+                .ok_or_else(|| invalid(line_no, "missing name"))?;
+            materials.push(ObjMaterial::new(name));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(m) => m,
+            None => return Err(invalid(line_no, "material property before `newmtl`")),
+        };
+        match keyword {
+            "Kd" => material.diffuse = parse_rgb(line_no, &mut tokens)?,
+            "Ks" => material.specular = parse_rgb(line_no, &mut tokens)?,
+            "Ke" => material.emission = parse_rgb(line_no, &mut tokens)?,
+            "Ni" => material.ior = parse(line_no, tokens.next())?,
+            "d" => material.dissolve = parse(line_no, tokens.next())?,
+            "Tr" => material.dissolve = 1.0 - parse::<f32>(line_no, tokens.next())?,
+            "illum" => material.illum = parse(line_no, tokens.next())?,
+            "map_Kd" => material.diffuse_map = map_path(&line.trim_start()[keyword.len()..]),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+/// 读取 OBJ 文件，多边形按扇形拆成三角形。
+/// `mtllib` 引用的文件交给 `load_mtl` 读取。
+pub fn read_obj<R, F>(input: R, mut load_mtl: F) -> io::Result<ObjModel>
+where
+    R: BufRead,
+    F: FnMut(&str) -> io::Result<Vec<ObjMaterial>>,
+{
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut faces: Vec<[Corner; 3]> = Vec::new();
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    let mut triangle_materials = Vec::new();
+    let mut current = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => positions.push(parse_vec3(line_no, &mut tokens)?),
+            Some("vt") => {
+                let u = parse(line_no, tokens.next())?;
+                let v: f32 = tokens.next().map_or(Ok(0.0), |t| parse(line_no, Some(t)))?;
+                //OBJ 的 v 从图像底部往上数，`ImageTexture` 的 v 从顶部往下数。
+                uvs.push(vec2(u, 1.0 - v));
+            }
+            Some("vn") => normals.push(parse_vec3(line_no, &mut tokens)?.normalize()),
+            Some("f") => {
+                let counts = [positions.len(), uvs.len(), normals.len()];
+                let corners = tokens
+                    .map(|t| parse_corner(line_no, t, counts))
+                    .collect::<io::Result<Vec<_>>>()?;
+                if corners.len() < 3 {
+                    return Err(invalid(line_no, "face with fewer than 3 vertices"));
+                }
+                for k in 1..corners.len() - 1 {
+                    faces.push([corners[0], corners[k], corners[k + 1]]);
+                    triangle_materials.push(current);
+                }
+            }
+            Some("mtllib") => {
+                for name in tokens {
+                    materials.extend(load_mtl(name)?);
+                }
+            }
+            Some("usemtl") => {
+                current = tokens
+                    .next()
+                    .and_then(|name| materials.iter().rposition(|m| m.name == name));
+            }
+            _ => {}
+        }
+    }
+
+    //只有所有面都带某个属性时才保留它，否则整个网格都丢掉这个属性。
+    let corners = || faces.iter().flat_map(|f| f.iter());
+    let has_uv = !faces.is_empty() && corners().all(|c| c.1.is_some());
+    let has_normal = !faces.is_empty() && corners().all(|c| c.2.is_some());
+
+    let mut keys: HashMap<Corner, usize> = HashMap::new();
+    let mut order: Vec<Corner> = Vec::new();
+    let indices = faces
+        .iter()
+        .map(|face| {
+            let mut points = [0; 3];
+            for (point, &(p, t, n)) in points.iter_mut().zip(face) {
+                let key = (p, t.filter(|_| has_uv), n.filter(|_| has_normal));
+                *point = *keys.entry(key).or_insert_with(|| {
+                    order.push(key);
+                    order.len() - 1
+                });
+            }
+            points
+        })
+        .collect();
+
+    let pos = |c: &Corner| positions[c.0];
+    let uv = |c: &Corner| uvs[c.1.unwrap()];
+    let normal = |c: &Corner| normals[c.2.unwrap()];
+    let mesh = match (has_uv, has_normal) {
+        (false, false) => {
+            Mesh::Position(IndexedMesh::new(order.iter().map(pos).collect(), indices))
+        }
+        (true, false) => Mesh::Uv(IndexedMesh::new(
+            order.iter().map(|c| VertexUV::new(pos(c), uv(c))).collect(),
+            indices,
+        )),
+        (false, true) => Mesh::Normal(IndexedMesh::new(
+            order
+                .iter()
+                .map(|c| VertexNormal::new(pos(c), normal(c)))
+                .collect(),
+            indices,
+        )),
+        (true, true) => Mesh::Uvn(IndexedMesh::new(
+            order
+                .iter()
+                .map(|c| VertexUvn::new(pos(c), normal(c), uv(c)))
+                .collect(),
+            indices,
+        )),
+    };
+
+    Ok(ObjModel {
+        mesh,
+        materials,
+        triangle_materials,
+    })
+}
+
+/// 读取 OBJ 文件，`mtllib` 相对于 OBJ 文件所在的目录，`map_Kd` 相对于 MTL 文件所在的目录。
+pub fn load_obj<P: AsRef<Path>>(path: P) -> io::Result<ObjModel> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    let file = File::open(path)?;
+    read_obj(BufReader::new(file), |name| {
+        let mtl_path = dir.join(name);
+        let file = File::open(&mtl_path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", mtl_path.display(), e)))?;
+        let mut materials = read_mtl(BufReader::new(file))?;
+        let mtl_dir = mtl_path.parent().unwrap_or_else(|| Path::new(""));
+        for m in &mut materials {
+            m.diffuse_map = m.diffuse_map.take().map(|p| mtl_dir.join(p));
+        }
+        Ok(materials)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate image;
+
+    use super::*;
+    use RayBuilder;
+
+    const MTL: &str = "
+newmtl red
+Kd 1 0 0
+newmtl lamp
+Ke 4 2 0
+";
+
+    fn read(obj: &str) -> io::Result<ObjModel> {
+        read_obj(obj.as_bytes(), |name| {
+            assert_eq!(name, "scene.mtl");
+            read_mtl(MTL.as_bytes())
+        })
+    }
+
+    #[test]
+    fn quad_with_uv() {
+        let model = read(
+            "
+# 一个带纹理坐标的正方形
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1 2/2 3/3 4/4
+",
+        )
+        .unwrap();
+        assert_eq!(model.mesh.len(), 2);
+        match model.mesh {
+            Mesh::Uv(ref mesh) => {
+                assert_eq!(mesh.vertices().len(), 4);
+                assert_eq!(mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+            }
+            _ => panic!("expected a mesh with uv"),
+        }
+    }
+
+    #[test]
+    fn negative_indices_and_materials() {
+        let model = read(
+            "
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 0 0 2
+f 1//1 2//1 3//1
+usemtl lamp
+f -3//-1 -1//-1 -2//-1
+usemtl red
+f 1 2 3
+",
+        )
+        .unwrap();
+        //最后一个面没有法线，整个网格都不带法线。
+        match model.mesh {
+            Mesh::Position(ref mesh) => assert_eq!(mesh.vertices().len(), 3),
+            _ => panic!("expected a position only mesh"),
+        }
+        assert_eq!(model.mesh.indices()[1], [0, 2, 1]);
+        assert_eq!(model.triangle_materials, vec![None, Some(1), Some(0)]);
+        assert_relative_eq!(model.materials[1].emission.r, 4.0);
+        assert_eq!(model.texed_shapes(Affine::identity()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let err = read("v 0 0 0\nv 1 0 0\nf 1 2 5\n").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 3:"));
+        assert!(read("v 0 x 0\n").is_err());
+    }
+
+    #[test]
+    fn diffuse_map() {
+        let path = ::std::env::temp_dir().join(format!("rrt_obj_map_{}.png", ::std::process::id()));
+        image::ImageBuffer::from_pixel(1, 1, image::Rgb { data: [255, 0, 0] })
+            .save(&path)
+            .unwrap();
+        let mut material = read_mtl(MTL.as_bytes()).unwrap().remove(0);
+        material.diffuse = Rgb::new(0.5, 0.5, 0.5);
+        material.diffuse_map = Some(path.clone());
+        let texture = material.texture();
+        ::std::fs::remove_file(&path).unwrap();
+        let color = texture.unwrap().get_value(&vec3(0.0, 0.0, 0.0), &vec2(0.5, 0.5));
+        assert_eq!(color, Rgb::new(0.5, 0.0, 0.0));
+
+        material.diffuse_map = Some(PathBuf::from("no/such/map.png"));
+        assert!(material.texture().is_err());
+
+        let mtl = "newmtl a\nmap_Kd -s 2 2 -mm 0 1 -clamp on my map.png \n";
+        let material = read_mtl(mtl.as_bytes()).unwrap().remove(0);
+        assert_eq!(material.diffuse_map, Some(PathBuf::from("my map.png")));
+    }
+
+    #[test]
+    fn load_obj_with_map() {
+        use std::fs;
+
+        //MTL 放在子目录里，贴图相对于 MTL 所在的目录。
+        let dir = ::std::env::temp_dir().join(format!("rrt_obj_{}", ::std::process::id()));
+        fs::create_dir_all(dir.join("materials")).unwrap();
+        fs::write(dir.join("model.obj"), OBJ_WITH_MAP).unwrap();
+        fs::write(dir.join("materials/a.mtl"), "newmtl a\nKd 1 1 1\nmap_Kd top bottom.png\n")
+            .unwrap();
+        //上红下蓝。
+        image::ImageBuffer::from_fn(1, 2, |_, y| image::Rgb {
+            data: if y == 0 { [255, 0, 0] } else { [0, 0, 255] },
+        })
+        .save(dir.join("materials/top bottom.png"))
+        .unwrap();
+        let shapes =
+            load_obj(dir.join("model.obj")).and_then(|m| m.texed_shapes(Affine::identity()));
+        fs::remove_dir_all(&dir).unwrap();
+        let shapes = shapes.unwrap();
+
+        //v = 0.9 靠近图像顶部。
+        let ray = RayBuilder {
+            origin: vec3(0.25, 0.25, 1.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = shapes[0].shape.hit(&ray, 0.0, 10.0).unwrap();
+        let color = shapes[0].texture.get_value(&hit.pos, &hit.uv);
+        assert!(color.r > 0.5 && color.b < 0.5);
+    }
+
+    const OBJ_WITH_MAP: &str = "
+mtllib materials/a.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0.5 0.9
+usemtl a
+f 1/1 2/1 3/1
+";
+}
@@ -0,0 +1,555 @@
+use super::IndexedMesh;
+use material::Lambertian;
+use math::*;
+use rgb::{srgb_to_linear, Rgb};
+use shapes::{HitRecord, TexedShape};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use texture::{PureColorTexture, Texture};
+use vertices::{Vertex, VertexAttributes};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    /// 整数颜色分量的最大值，浮点数返回 `None`。
+    fn max_value(self) -> Option<f64> {
+        match self {
+            Scalar::I8 => Some(127.0),
+            Scalar::U8 => Some(255.0),
+            Scalar::I16 => Some(32767.0),
+            Scalar::U16 => Some(65535.0),
+            Scalar::I32 => Some(2147483647.0),
+            Scalar::U32 => Some(4294967295.0),
+            Scalar::F32 | Scalar::F64 => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match *self {
+            Property::Scalar(ref name, _) | Property::List(ref name, _, _) => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn header_error(line: usize, msg: &str) -> io::Error {
+    invalid(&format!("header line {}: {}", line, msg))
+}
+
+fn read_header<R: BufRead>(input: &mut R) -> io::Result<(Format, Vec<Element>)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut line = String::new();
+    for line_no in 1.. {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Err(header_error(line_no, "missing `end_header`"));
+        }
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next();
+        if line_no == 1 {
+            if keyword != Some("ply") {
+                return Err(header_error(line_no, "not a PLY file"));
+            }
+            continue;
+        }
+        match keyword {
+            Some("format") => {
+                format = Some(match tokens.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => return Err(header_error(line_no, "unknown format")),
+                });
+            }
+            Some("element") => {
+                let name = tokens.next();
+                let count = tokens.next().and_then(|c| c.parse().ok());
+                match (name, count) {
+                    (Some(name), Some(count)) => elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    }),
+                    _ => return Err(header_error(line_no, "invalid element")),
+                }
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| header_error(line_no, "property before element"))?;
+                let tokens: Vec<&str> = tokens.collect();
+                let ty = |name: &str| {
+                    Scalar::parse(name)
+                        .ok_or_else(|| header_error(line_no, &format!("unknown type `{}`", name)))
+                };
+                let property = match tokens[..] {
+                    ["list", count, item, name] => {
+                        Property::List(name.to_string(), ty(count)?, ty(item)?)
+                    }
+                    [scalar, name] => Property::Scalar(name.to_string(), ty(scalar)?),
+                    _ => return Err(header_error(line_no, "invalid property")),
+                };
+                element.properties.push(property);
+            }
+            Some("end_header") => break,
+            Some("comment") | Some("obj_info") | None => {}
+            Some(other) => {
+                return Err(header_error(
+                    line_no,
+                    &format!("unknown keyword `{}`", other),
+                ))
+            }
+        }
+    }
+    let format = format.ok_or_else(|| invalid("missing `format` in header"))?;
+    Ok((format, elements))
+}
+
+/// 按类型逐个读出文件体中的数值。
+trait Body {
+    fn read(&mut self, ty: Scalar) -> io::Result<f64>;
+}
+
+struct AsciiBody<R> {
+    input: R,
+    //当前行剩下的数，倒序存放。
+    tokens: Vec<String>,
+}
+
+impl<R: BufRead> Body for AsciiBody<R> {
+    fn read(&mut self, _ty: Scalar) -> io::Result<f64> {
+        while self.tokens.is_empty() {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Err(invalid("unexpected end of file"));
+            }
+            self.tokens = line.split_whitespace().rev().map(String::from).collect();
+        }
+        let token = self.tokens.pop().unwrap();
+        token
+            .parse()
+            .map_err(|_| invalid(&format!("invalid number `{}`", token)))
+    }
+}
+
+struct BinaryBody<R> {
+    input: R,
+    big_endian: bool,
+}
+
+macro_rules! read_binary {
+    ($self: ident, $ty: ty) => {{
+        let mut buf = [0u8; ::std::mem::size_of::<$ty>()];
+        $self.input.read_exact(&mut buf)?;
+        if $self.big_endian {
+            <$ty>::from_be_bytes(buf) as f64
+        } else {
+            <$ty>::from_le_bytes(buf) as f64
+        }
+    }};
+}
+
+impl<R: BufRead> Body for BinaryBody<R> {
+    fn read(&mut self, ty: Scalar) -> io::Result<f64> {
+        Ok(match ty {
+            Scalar::I8 => read_binary!(self, i8),
+            Scalar::U8 => read_binary!(self, u8),
+            Scalar::I16 => read_binary!(self, i16),
+            Scalar::U16 => read_binary!(self, u16),
+            Scalar::I32 => read_binary!(self, i32),
+            Scalar::U32 => read_binary!(self, u32),
+            Scalar::F32 => read_binary!(self, f32),
+            Scalar::F64 => read_binary!(self, f64),
+        })
+    }
+}
+
+/// 一个元素的所有标量属性，列表属性单独存放。
+struct Record {
+    scalars: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+}
+
+fn read_record(body: &mut dyn Body, element: &Element) -> io::Result<Record> {
+    let mut record = Record {
+        scalars: Vec::new(),
+        lists: Vec::new(),
+    };
+    for property in &element.properties {
+        match *property {
+            Property::Scalar(_, ty) => record.scalars.push(body.read(ty)?),
+            Property::List(_, count, item) => {
+                let count = body.read(count)?;
+                if count < 0.0 {
+                    return Err(invalid("negative list length"));
+                }
+                let list = (0..count as usize)
+                    .map(|_| body.read(item))
+                    .collect::<io::Result<_>>()?;
+                record.lists.push(list);
+            }
+        }
+    }
+    Ok(record)
+}
+
+/// 顶点属性在 `Record::scalars` 中的位置。
+struct VertexLayout {
+    pos: [usize; 3],
+    normal: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+    color: Option<([usize; 3], Option<f64>)>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> io::Result<VertexLayout> {
+        let scalars: Vec<(&str, Scalar)> = element
+            .properties
+            .iter()
+            .filter_map(|p| match *p {
+                Property::Scalar(ref name, ty) => Some((name.as_str(), ty)),
+                _ => None,
+            })
+            .collect();
+        let find = |name: &str| scalars.iter().position(|s| s.0 == name);
+        let find3 = |names: [&str; 3]| match (find(names[0]), find(names[1]), find(names[2])) {
+            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+            _ => None,
+        };
+        let uv = [
+            ["u", "v"],
+            ["s", "t"],
+            ["texture_u", "texture_v"],
+            ["texture_s", "texture_t"],
+        ]
+        .iter()
+        .filter_map(|names| match (find(names[0]), find(names[1])) {
+            (Some(a), Some(b)) => Some([a, b]),
+            _ => None,
+        })
+        .next();
+        let color = find3(["red", "green", "blue"])
+            .or_else(|| find3(["r", "g", "b"]))
+            .map(|c| (c, scalars[c[0]].1.max_value()));
+        Ok(VertexLayout {
+            pos: find3(["x", "y", "z"])
+                .ok_or_else(|| invalid("vertex element without x, y and z"))?,
+            normal: find3(["nx", "ny", "nz"]),
+            uv,
+            color,
+        })
+    }
+
+    fn vertex(&self, values: &[f64]) -> VertexAttributes {
+        let get = |i: usize| values[i] as f32;
+        let get3 = |i: [usize; 3]| vec3(get(i[0]), get(i[1]), get(i[2]));
+        let mut vertex = VertexAttributes::new(get3(self.pos));
+        vertex.normal = self.normal.map(|i| get3(i).normalize());
+        vertex.uv = self.uv.map(|i| vec2(get(i[0]), get(i[1])));
+        vertex.color = self.color.map(|(i, max)| match max {
+            //整数颜色按 sRGB 编码存放，浮点颜色当作线性值。
+            Some(max) => {
+                let c = |i: usize| srgb_to_linear((values[i] / max) as f32);
+                Rgb::new(c(i[0]), c(i[1]), c(i[2]))
+            }
+            None => Rgb::new(get(i[0]), get(i[1]), get(i[2])),
+        });
+        vertex
+    }
+}
+
+/// 读取 PLY 文件，支持 ASCII 和大小端的二进制格式。
+/// 多边形按扇形拆成三角形，`vertex` 和 `face` 以外的元素会被跳过。
+pub fn read_ply<R: BufRead>(mut input: R) -> io::Result<IndexedMesh<VertexAttributes>> {
+    let (format, elements) = read_header(&mut input)?;
+    let mut body: Box<dyn Body> = match format {
+        Format::Ascii => Box::new(AsciiBody {
+            input,
+            tokens: Vec::new(),
+        }),
+        _ => Box::new(BinaryBody {
+            input,
+            big_endian: format == Format::BinaryBigEndian,
+        }),
+    };
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let layout = VertexLayout::new(element)?;
+                for _ in 0..element.count {
+                    let record = read_record(&mut *body, element)?;
+                    vertices.push(layout.vertex(&record.scalars));
+                }
+            }
+            "face" => {
+                let list = element
+                    .properties
+                    .iter()
+                    .filter(|p| matches!(**p, Property::List(..)))
+                    .position(|p| p.name() == "vertex_indices" || p.name() == "vertex_index")
+                    .ok_or_else(|| invalid("face element without vertex_indices"))?;
+                for _ in 0..element.count {
+                    let record = read_record(&mut *body, element)?;
+                    let face = &record.lists[list];
+                    if face.len() < 3 {
+                        return Err(invalid("face with fewer than 3 vertices"));
+                    }
+                    let index = |i: f64| {
+                        if i >= 0.0 && i.fract() == 0.0 {
+                            Ok(i as usize)
+                        } else {
+                            Err(invalid(&format!("vertex index {} out of range", i)))
+                        }
+                    };
+                    for k in 1..face.len() - 1 {
+                        indices.push([index(face[0])?, index(face[k])?, index(face[k + 1])?]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    read_record(&mut *body, element)?;
+                }
+            }
+        }
+    }
+
+    if let Some(&i) = indices.iter().flatten().find(|&&i| i >= vertices.len()) {
+        return Err(invalid(&format!("vertex index {} out of range", i)));
+    }
+    Ok(IndexedMesh::new(vertices, indices))
+}
+
+pub fn load_ply<P: AsRef<Path>>(path: P) -> io::Result<IndexedMesh<VertexAttributes>> {
+    read_ply(BufReader::new(File::open(path)?))
+}
+
+/// 按交点的重心坐标插值三个顶点的颜色，没有交点时取平均。
+struct VertexColorTexture {
+    colors: [Rgb; 3],
+}
+
+impl Texture for VertexColorTexture {
+    fn get_value(&self, _pos: &Vector3, _uv: &Vector2) -> Rgb {
+        (self.colors[0] + self.colors[1] + self.colors[2]) / 3.0
+    }
+
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        let b = hit.barycentric;
+        self.colors[0] * b.x + self.colors[1] * b.y + self.colors[2] * b.z
+    }
+}
+
+impl IndexedMesh<VertexAttributes> {
+    /// 每个三角形一个漫反射的 `TexedShape`，带顶点颜色时按颜色插值，否则为灰色。
+    pub fn texed_shapes(&self, transform: Affine) -> Vec<TexedShape> {
+        let vertices = self.vertices();
+        self.triangles()
+            .into_iter()
+            .zip(self.indices())
+            .map(|(triangle, points)| {
+                let texture: Box<dyn Texture> = match (
+                    vertices[points[0]].get_color(),
+                    vertices[points[1]].get_color(),
+                    vertices[points[2]].get_color(),
+                ) {
+                    (Some(&c0), Some(&c1), Some(&c2)) => Box::new(VertexColorTexture {
+                        colors: [c0, c1, c2],
+                    }),
+                    _ => Box::new(PureColorTexture {
+                        color: Rgb::new(0.8, 0.8, 0.8),
+                    }),
+                };
+                TexedShape {
+                    texture,
+                    material: Box::new(Lambertian),
+                    shape: Box::new(triangle),
+                    transform,
+                    motion: None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    const ASCII: &str = "ply
+format ascii 1.0
+comment 一个带颜色的正方形和一个三角形
+element vertex 5
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 2
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 2 255 0 0
+1 0 0 0 0 1 0 255 0
+1 1 0 0 0 1 0 0 255
+0 1 0 0 0 1 255 255 255
+0 0 1 0 0 1 0 0 0
+4 0 1 2 3
+3 0 1 4
+";
+
+    //同样的数据写成二进制格式。
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let header = ASCII.split("end_header\n").next().unwrap();
+        let mut out = header.replace("ascii", format).into_bytes();
+        out.extend_from_slice(b"end_header\n");
+        for line in ASCII.split("end_header\n").nth(1).unwrap().lines() {
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(|v| v.parse().unwrap())
+                .collect();
+            if values.len() == 9 {
+                for &v in &values[..6] {
+                    out.extend_from_slice(&if big_endian {
+                        v.to_be_bytes()
+                    } else {
+                        v.to_le_bytes()
+                    });
+                }
+                out.extend(values[6..].iter().map(|&c| c as u8));
+            } else {
+                out.push(values[0] as u8);
+                for &i in &values[1..] {
+                    let i = i as i32;
+                    out.extend_from_slice(&if big_endian {
+                        i.to_be_bytes()
+                    } else {
+                        i.to_le_bytes()
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn ascii_and_binary_agree() {
+        let ascii = read_ply(ASCII.as_bytes()).unwrap();
+        assert_eq!(ascii.indices(), &[[0, 1, 2], [0, 2, 3], [0, 1, 4]]);
+        let v = &ascii.vertices()[0];
+        assert_eq!(v.normal, Some(Vector3::unit_z()));
+        assert_eq!(v.color, Some(Rgb::new(1.0, 0.0, 0.0)));
+        assert_eq!(v.uv, None);
+
+        for &big_endian in &[false, true] {
+            let mesh = read_ply(&binary(big_endian)[..]).unwrap();
+            assert_eq!(mesh.indices(), ascii.indices());
+            assert_eq!(mesh.vertices(), ascii.vertices());
+        }
+    }
+
+    #[test]
+    fn interpolates_vertex_colors() {
+        //顶点的纹理坐标保持不变，颜色只取决于命中点的重心坐标。
+        let mesh = read_ply(ASCII.as_bytes()).unwrap();
+        let vertices = mesh
+            .vertices()
+            .iter()
+            .map(|v| VertexAttributes {
+                uv: Some(vec2(0.5, 0.5)),
+                ..*v
+            })
+            .collect();
+        let mesh = IndexedMesh::new(vertices, mesh.indices().to_vec());
+        let moved = Affine::new(Matrix::from_translation(vec3(5.0, 0.0, 0.0))).unwrap();
+        let shapes = mesh.texed_shapes(moved);
+        let ray = RayBuilder {
+            origin: vec3(0.75, 0.5, 1.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = shapes[0].shape.hit(&ray, 0.0, 10.0).unwrap();
+        assert_eq!(hit.uv, vec2(0.5, 0.5));
+        let c = shapes[0].texture.get_at_hit(&hit);
+        assert_relative_eq!(c.r, 0.25, epsilon = 1e-6);
+        assert_relative_eq!(c.g, 0.25, epsilon = 1e-6);
+        assert_relative_eq!(c.b, 0.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(read_ply("obj\n".as_bytes()).is_err());
+        let bad_index = ASCII.replace("3 0 1 4", "3 0 1 5");
+        let err = read_ply(bad_index.as_bytes()).err().unwrap();
+        assert!(err.to_string().contains("out of range"));
+        for face in &["3 0 1 -1", "3 0 1 1.5"] {
+            let bad_index = ASCII.replace("3 0 1 4", face);
+            let err = read_ply(bad_index.as_bytes()).err().unwrap();
+            assert!(err.to_string().contains("out of range"));
+        }
+        let truncated = &ASCII[..ASCII.len() - 8];
+        assert!(read_ply(truncated.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use super::Noise;
+use math::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FractalKind {
+    /// 分形布朗运动，各层噪声直接叠加。
+    Fbm,
+    /// 各层取绝对值后叠加，得到翻滚的湍流，值域是 [0, 1]。
+    Turbulence,
+    /// Musgrave 的脊状多重分形：`(1 - |n|)^2` 形成尖锐的山脊，
+    /// 每层的权重取上一层的信号，山谷因此更平滑。值域是 [0, 1]。
+    Ridged,
+}
+
+/// 把同一个噪声按 `lacunarity` 倍的频率、`gain` 倍的振幅叠加 `octaves` 层，
+/// 结果除以振幅之和归一化。
+#[derive(Clone)]
+pub struct Fractal<N> {
+    pub noise: N,
+    pub kind: FractalKind,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl<N: Noise> Fractal<N> {
+    pub fn new(noise: N, kind: FractalKind, octaves: u32) -> Self {
+        Fractal {
+            noise,
+            kind,
+            octaves,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    pub fn fbm(noise: N, octaves: u32) -> Self {
+        Fractal::new(noise, FractalKind::Fbm, octaves)
+    }
+
+    pub fn turbulence(noise: N, octaves: u32) -> Self {
+        Fractal::new(noise, FractalKind::Turbulence, octaves)
+    }
+
+    pub fn ridged(noise: N, octaves: u32) -> Self {
+        Fractal::new(noise, FractalKind::Ridged, octaves)
+    }
+
+    fn sum<V, F>(&self, p: V, eval: F) -> (f32, V)
+    where
+        V: VectorSpace<Scalar = f32>,
+        F: Fn(V) -> (f32, V),
+    {
+        let (mut value, mut grad) = (0.0, V::zero());
+        let (mut amplitude, mut frequency, mut total) = (1.0, 1.0, 0.0);
+        let (mut weight, mut weight_grad) = (1.0, V::zero());
+        for _ in 0..self.octaves {
+            let (n, dn) = eval(p * frequency);
+            let dn = dn * frequency;
+            let (v, dv) = match self.kind {
+                FractalKind::Fbm => (n, dn),
+                FractalKind::Turbulence => (n.abs(), dn * n.signum()),
+                FractalKind::Ridged => {
+                    let r = 1.0 - n.abs();
+                    let signal = r * r * weight;
+                    let signal_grad = dn * (-2.0 * r * weight * n.signum()) + weight_grad * (r * r);
+                    if signal < 1.0 {
+                        weight = signal;
+                        weight_grad = signal_grad;
+                    } else {
+                        weight = 1.0;
+                        weight_grad = V::zero();
+                    }
+                    (signal, signal_grad)
+                }
+            };
+            value += amplitude * v;
+            grad = grad + dv * amplitude;
+            total += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+        if total > 0.0 {
+            (value / total, grad / total)
+        } else {
+            (0.0, V::zero())
+        }
+    }
+}
+
+impl<N: Noise> Noise for Fractal<N> {
+    fn eval2(&self, p: Vector2) -> (f32, Vector2) {
+        self.sum(p, |q| self.noise.eval2(q))
+    }
+
+    fn eval3(&self, p: Vector3) -> (f32, Vector3) {
+        self.sum(p, |q| self.noise.eval3(q))
+    }
+
+    fn eval4(&self, p: Vector4) -> (f32, Vector4) {
+        self.sum(p, |q| self.noise.eval4(q))
+    }
+}
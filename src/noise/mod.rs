@@ -0,0 +1,328 @@
+use math::{Vector2, Vector3, Vector4};
+use rand::Rng;
+use sample::Sampler;
+
+/// 给切片实现的噪声 `fn eval(&self, p: &[f32]) -> (f32, [f32; 4])` 套上 `Noise` 接口。
+macro_rules! impl_noise {
+    ($type: ty) => {
+        impl $crate::noise::Noise for $type {
+            fn eval2(&self, p: $crate::math::Vector2) -> (f32, $crate::math::Vector2) {
+                let (n, d) = self.eval(&[p.x, p.y]);
+                (n, $crate::math::vec2(d[0], d[1]))
+            }
+
+            fn eval3(&self, p: $crate::math::Vector3) -> (f32, $crate::math::Vector3) {
+                let (n, d) = self.eval(&[p.x, p.y, p.z]);
+                (n, $crate::math::vec3(d[0], d[1], d[2]))
+            }
+
+            fn eval4(&self, p: $crate::math::Vector4) -> (f32, $crate::math::Vector4) {
+                let (n, d) = self.eval(&[p.x, p.y, p.z, p.w]);
+                (n, $crate::math::vec4(d[0], d[1], d[2], d[3]))
+            }
+        }
+    };
+}
+
+mod fractal;
+mod perlin;
+mod simplex;
+mod value;
+mod worley;
+
+pub use self::fractal::{Fractal, FractalKind};
+pub use self::perlin::Perlin;
+pub use self::simplex::Simplex;
+pub use self::value::Value;
+pub use self::worley::{Worley, WorleyFeature};
+
+/// Ken Perlin 原始实现里的排列表。
+const PERMUTATIONS: [u8; 512] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180, 151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194,
+    233, 7, 225, 140, 36, 103, 30, 69, 142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234,
+    75, 0, 26, 197, 62, 94, 252, 219, 203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174,
+    20, 125, 136, 171, 168, 68, 175, 74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83,
+    111, 229, 122, 60, 211, 133, 230, 220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25,
+    63, 161, 1, 216, 80, 73, 209, 76, 132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188,
+    159, 86, 164, 100, 109, 198, 173, 186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147,
+    118, 126, 255, 82, 85, 212, 207, 206, 59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170,
+    213, 119, 248, 152, 2, 44, 154, 163, 70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253,
+    19, 98, 108, 110, 79, 113, 224, 232, 178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193,
+    238, 210, 144, 12, 191, 179, 162, 241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31,
+    181, 199, 106, 157, 184, 84, 204, 176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93,
+    222, 114, 67, 29, 24, 72, 243, 141, 128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+/// 打乱的 0..256 的排列，决定了噪声的具体样子。不同的种子得到互不相关的噪声。
+#[derive(Clone)]
+pub struct Permutation {
+    table: [u8; 512],
+}
+
+impl Permutation {
+    pub fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        Sampler::new(seed).rng().shuffle(&mut values);
+        let mut table = [0; 512];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = values[i & 255];
+        }
+        Permutation { table }
+    }
+
+    /// 把整数坐标逐维哈希到 0..256。
+    fn hash(&self, cell: &[i32]) -> usize {
+        cell.iter()
+            .fold(0, |h, &c| self.table[h + (c & 255) as usize] as usize)
+    }
+}
+
+impl Default for Permutation {
+    fn default() -> Self {
+        Permutation {
+            table: PERMUTATIONS,
+        }
+    }
+}
+
+/// 2、3、4 维噪声，第 4 维一般当作时间用来做动画。
+/// `eval*` 同时给出噪声值和它对输入坐标的解析梯度。
+pub trait Noise {
+    fn eval2(&self, p: Vector2) -> (f32, Vector2);
+    fn eval3(&self, p: Vector3) -> (f32, Vector3);
+    fn eval4(&self, p: Vector4) -> (f32, Vector4);
+
+    fn noise2(&self, p: Vector2) -> f32 {
+        self.eval2(p).0
+    }
+
+    fn noise3(&self, p: Vector3) -> f32 {
+        self.eval3(p).0
+    }
+
+    fn noise4(&self, p: Vector4) -> f32 {
+        self.eval4(p).0
+    }
+}
+
+impl<N: Noise + ?Sized> Noise for &N {
+    fn eval2(&self, p: Vector2) -> (f32, Vector2) {
+        (**self).eval2(p)
+    }
+
+    fn eval3(&self, p: Vector3) -> (f32, Vector3) {
+        (**self).eval3(p)
+    }
+
+    fn eval4(&self, p: Vector4) -> (f32, Vector4) {
+        (**self).eval4(p)
+    }
+}
+
+fn fade(n: f32) -> f32 {
+    n * n * n * (n * (n * 6.0 - 15.0) + 10.0)
+}
+
+fn fade_derivative(n: f32) -> f32 {
+    30.0 * n * n * (n - 1.0) * (n - 1.0)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// `n` 维的梯度方向。2 维取四个对角和四个轴向；
+/// 更高维取超立方体的棱中点，即一个分量为 0、其余为 ±1，3 维 12 个，4 维 32 个。
+fn gradient(h: usize, n: usize) -> [f32; 4] {
+    let sign = |bit: usize| if h >> bit & 1 == 0 { 1.0 } else { -1.0 };
+    let mut g = [0.0; 4];
+    if n == 2 {
+        if h & 4 == 0 {
+            g[0] = sign(0);
+            g[1] = sign(1);
+        } else {
+            g[h & 1] = sign(1);
+        }
+        return g;
+    }
+    let h = h % (n << (n - 1));
+    let zero = h >> (n - 1);
+    let mut bit = 0;
+    for (k, g) in g.iter_mut().enumerate().take(n) {
+        if k != zero {
+            *g = sign(bit);
+            bit += 1;
+        }
+    }
+    g
+}
+
+/// 格点噪声：对 `p` 所在格子的 2^n 个角用五次平滑曲线做多线性插值。
+/// `corner(hash, offset)` 给出角上的值，以及这个值对 `offset`（`p` 减去角的坐标）的梯度。
+fn lattice<F>(perm: &Permutation, p: &[f32], corner: F) -> (f32, [f32; 4])
+where
+    F: Fn(usize, &[f32]) -> (f32, [f32; 4]),
+{
+    let n = p.len();
+    let (mut cell, mut frac) = ([0; 4], [0.0; 4]);
+    for k in 0..n {
+        let floor = p[k].floor();
+        cell[k] = floor as i32;
+        frac[k] = p[k] - floor;
+    }
+    let (mut value, mut grad) = (0.0, [0.0; 4]);
+    for c in 0..1 << n {
+        let (mut corner_cell, mut offset) = (cell, frac);
+        //每个轴上的插值权重和它对坐标的导数。
+        let (mut w, mut dw) = ([1.0; 4], [0.0; 4]);
+        for k in 0..n {
+            let s = fade(frac[k]);
+            if c >> k & 1 == 1 {
+                corner_cell[k] += 1;
+                offset[k] -= 1.0;
+                w[k] = s;
+                dw[k] = fade_derivative(frac[k]);
+            } else {
+                w[k] = 1.0 - s;
+                dw[k] = -fade_derivative(frac[k]);
+            }
+        }
+        let (v, dv) = corner(perm.hash(&corner_cell[..n]), &offset[..n]);
+        let weight: f32 = w.iter().product();
+        value += weight * v;
+        for k in 0..n {
+            let others: f32 = (0..n).filter(|&j| j != k).map(|j| w[j]).product();
+            grad[k] += dw[k] * others * v + weight * dv[k];
+        }
+    }
+    (value, grad)
+}
+
+/// 默认排列表下的三维 Perlin 噪声，映射到 [0, 1]。
+pub fn perlin_noise(point: &Vector3) -> f32 {
+    static PERLIN: Perlin = Perlin {
+        perm: Permutation {
+            table: PERMUTATIONS,
+        },
+    };
+    (PERLIN.noise3(*point) + 1.0) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+
+    //确定性的伪随机采样点。
+    fn points() -> Vec<Vector4> {
+        (1..200)
+            .map(|i| {
+                let i = i as f32;
+                vec4(
+                    (i * 0.737).sin() * 7.3,
+                    (i * 1.311).cos() * 5.1 - 2.0,
+                    i * 0.173 - 11.0,
+                    (i * 0.419).sin() * 3.7,
+                )
+            })
+            .collect()
+    }
+
+    /// 函数 `f` 在 0 处的导数和 `expected` 一致。湍流和山脊在 `n = 0` 处有尖点，
+    /// 把 [-h, h] 分成四段，各段斜率对不上时说明中间有尖点，跳过。
+    fn check_derivative<F: Fn(f32) -> f32>(f: F, expected: f32) {
+        let h = 1e-3;
+        let x = [-h, -h / 2.0, 0.0, h / 2.0, h];
+        let y: Vec<f32> = x.iter().map(|&x| f(x)).collect();
+        let slopes: Vec<f32> = (0..4).map(|i| (y[i + 1] - y[i]) / (h / 2.0)).collect();
+        let central = (y[4] - y[0]) / (2.0 * h);
+        let (min, max) = slopes
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(a, b), &s| {
+                (a.min(s), b.max(s))
+            });
+        if max - min > 0.1 * central.abs().max(1.0) {
+            return;
+        }
+        assert_relative_eq!(expected, central, epsilon = 5e-2, max_relative = 5e-2);
+    }
+
+    fn check_gradient<N: Noise>(noise: &N) {
+        for p in points() {
+            let (_, g) = noise.eval4(p);
+            let axes = [
+                Vector4::unit_x(),
+                Vector4::unit_y(),
+                Vector4::unit_z(),
+                Vector4::unit_w(),
+            ];
+            for (k, e) in axes.iter().enumerate() {
+                check_derivative(|h| noise.noise4(p + e * h), g[k]);
+            }
+            let q = p.truncate();
+            let (_, g) = noise.eval3(q);
+            for (k, e) in axes.iter().take(3).enumerate() {
+                check_derivative(|h| noise.noise3(q + e.truncate() * h), g[k]);
+            }
+            let q = q.truncate();
+            let (_, g) = noise.eval2(q);
+            for (k, e) in axes.iter().take(2).enumerate() {
+                check_derivative(|h| noise.noise2(q + e.truncate().truncate() * h), g[k]);
+            }
+        }
+    }
+
+    #[test]
+    fn analytic_gradients() {
+        check_gradient(&Perlin::new(1));
+        check_gradient(&Simplex::new(2));
+        check_gradient(&Value::new(3));
+        check_gradient(&Fractal::fbm(Perlin::new(4), 4));
+        check_gradient(&Fractal::turbulence(Simplex::new(5), 3));
+        check_gradient(&Fractal::ridged(Perlin::new(6), 4));
+    }
+
+    #[test]
+    fn value_ranges() {
+        let (perlin, simplex, value) = (Perlin::new(7), Simplex::new(7), Value::new(7));
+        let worley = Worley::new(7, WorleyFeature::F2MinusF1);
+        let ridged = Fractal::ridged(Simplex::new(7), 5);
+        for p in points() {
+            let q = p.truncate();
+            let values = [
+                perlin.noise3(q),
+                simplex.noise3(q),
+                value.noise3(q),
+                simplex.noise4(p),
+            ];
+            assert!(values.iter().all(|n| n.abs() <= 1.0), "{:?}", values);
+            assert!(worley.noise3(q) >= 0.0);
+            let r = ridged.noise4(p);
+            assert!((0.0..=1.0).contains(&r), "{}", r);
+            assert!((0.0..=1.0).contains(&perlin_noise(&q)));
+        }
+        //格点上梯度噪声为 0。
+        assert_eq!(perlin.noise3(vec3(3.0, -2.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn seeds() {
+        let p = vec3(1.3, 2.7, -0.4);
+        assert_eq!(Simplex::new(42).noise3(p), Simplex::new(42).noise3(p));
+        assert_ne!(Simplex::new(42).noise3(p), Simplex::new(43).noise3(p));
+        //默认排列表就是原来的 perlin_noise 用的那一张。
+        assert_eq!(perlin_noise(&p), (Perlin::default().noise3(p) + 1.0) / 2.0);
+    }
+}
@@ -0,0 +1,24 @@
+use super::{dot, gradient, lattice, Permutation};
+
+/// Ken Perlin 的改进噪声：格点上放随机梯度，五次曲线插值，值域大约是 [-1, 1]。
+#[derive(Clone, Default)]
+pub struct Perlin {
+    pub perm: Permutation,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Perlin {
+            perm: Permutation::new(seed),
+        }
+    }
+
+    fn eval(&self, p: &[f32]) -> (f32, [f32; 4]) {
+        lattice(&self.perm, p, |h, offset| {
+            let g = gradient(h, offset.len());
+            (dot(&g, offset), g)
+        })
+    }
+}
+
+impl_noise!(Perlin);
@@ -0,0 +1,74 @@
+use super::{dot, gradient, Permutation};
+
+/// 单纯形噪声：把空间剖分成单纯形，只在 n + 1 个顶点上求和，高维时比 Perlin 快得多，
+/// 也没有沿坐标轴的方向性。值域大约是 [-1, 1]。
+#[derive(Clone, Default)]
+pub struct Simplex {
+    pub perm: Permutation,
+}
+
+impl Simplex {
+    pub fn new(seed: u64) -> Self {
+        Simplex {
+            perm: Permutation::new(seed),
+        }
+    }
+
+    fn eval(&self, p: &[f32]) -> (f32, [f32; 4]) {
+        let n = p.len();
+        let nf = n as f32;
+        let skew = ((nf + 1.0).sqrt() - 1.0) / nf;
+        let unskew = (1.0 - 1.0 / (nf + 1.0).sqrt()) / nf;
+        //每个顶点的影响半径的平方和归一化系数。
+        let (radius2, scale) = match n {
+            2 => (0.5, 70.0),
+            3 => (0.5, 74.0),
+            _ => (0.5, 60.0),
+        };
+
+        let s = p.iter().sum::<f32>() * skew;
+        let mut cell = [0; 4];
+        for k in 0..n {
+            cell[k] = (p[k] + s).floor() as i32;
+        }
+        let t = cell[..n].iter().sum::<i32>() as f32 * unskew;
+        let mut x0 = [0.0; 4];
+        for k in 0..n {
+            x0[k] = p[k] - (cell[k] as f32 - t);
+        }
+        //按分量从大到小排名，第 k 个顶点在排名前 k 的轴上加 1。
+        let mut rank = [0; 4];
+        for k in 0..n {
+            rank[k] = (0..n)
+                .filter(|&j| x0[j] > x0[k] || (x0[j] == x0[k] && j < k))
+                .count();
+        }
+
+        let (mut value, mut grad) = (0.0, [0.0; 4]);
+        for corner in 0..n + 1 {
+            let (mut corner_cell, mut x) = (cell, [0.0; 4]);
+            for k in 0..n {
+                let offset = if rank[k] < corner { 1 } else { 0 };
+                corner_cell[k] += offset;
+                x[k] = x0[k] - offset as f32 + corner as f32 * unskew;
+            }
+            let t = radius2 - dot(&x[..n], &x[..n]);
+            if t <= 0.0 {
+                continue;
+            }
+            let g = gradient(self.perm.hash(&corner_cell[..n]), n);
+            let gx = dot(&g[..n], &x[..n]);
+            let t2 = t * t;
+            value += t2 * t2 * gx;
+            for k in 0..n {
+                grad[k] += t2 * t2 * g[k] - 8.0 * t2 * t * gx * x[k];
+            }
+        }
+        for g in &mut grad {
+            *g *= scale;
+        }
+        (value * scale, grad)
+    }
+}
+
+impl_noise!(Simplex);
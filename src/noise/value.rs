@@ -0,0 +1,21 @@
+use super::{lattice, Permutation};
+
+/// 值噪声：格点上放 [-1, 1] 的随机值再插值，比梯度噪声更“块状”，但计算最省。
+#[derive(Clone, Default)]
+pub struct Value {
+    pub perm: Permutation,
+}
+
+impl Value {
+    pub fn new(seed: u64) -> Self {
+        Value {
+            perm: Permutation::new(seed),
+        }
+    }
+
+    fn eval(&self, p: &[f32]) -> (f32, [f32; 4]) {
+        lattice(&self.perm, p, |h, _| (h as f32 / 127.5 - 1.0, [0.0; 4]))
+    }
+}
+
+impl_noise!(Value);
@@ -0,0 +1,118 @@
+use super::Permutation;
+
+/// Worley 噪声取的特征量。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorleyFeature {
+    /// 到最近特征点的距离。
+    F1,
+    /// 到第二近特征点的距离。
+    F2,
+    /// 两者之差，在细胞边界上为 0，适合做裂纹和鳞片。
+    F2MinusF1,
+}
+
+/// Worley（细胞）噪声：每个格子里随机放一个特征点，取到附近特征点的距离。
+/// 值不小于 0，F1 一般不超过 1。
+#[derive(Clone)]
+pub struct Worley {
+    pub perm: Permutation,
+    pub feature: WorleyFeature,
+}
+
+impl Worley {
+    pub fn new(seed: u64, feature: WorleyFeature) -> Self {
+        Worley {
+            perm: Permutation::new(seed),
+            feature,
+        }
+    }
+
+    /// 格子 `cell` 里的特征点，每个分量在格子内的位置单独哈希。
+    fn feature_point(&self, cell: &[i32], k: usize) -> f32 {
+        let mut key = [0; 5];
+        key[..cell.len()].copy_from_slice(cell);
+        key[cell.len()] = k as i32;
+        cell[k] as f32 + (self.perm.hash(&key[..cell.len() + 1]) as f32 + 0.5) / 256.0
+    }
+
+    fn eval(&self, p: &[f32]) -> (f32, [f32; 4]) {
+        let n = p.len();
+        let mut cell = [0; 4];
+        for k in 0..n {
+            cell[k] = p[k].floor() as i32;
+        }
+        //最近和第二近的特征点：距离和从特征点指向 p 的单位向量，即距离的梯度。
+        let mut nearest = [(f32::INFINITY, [0.0; 4]); 2];
+        for i in 0..3usize.pow(n as u32) {
+            let mut neighbor = cell;
+            let mut digits = i;
+            for c in neighbor.iter_mut().take(n) {
+                *c += (digits % 3) as i32 - 1;
+                digits /= 3;
+            }
+            let mut diff = [0.0; 4];
+            for k in 0..n {
+                diff[k] = p[k] - self.feature_point(&neighbor[..n], k);
+            }
+            let d = diff.iter().map(|d| d * d).sum::<f32>().sqrt();
+            if d < nearest[1].0 {
+                if d > 0.0 {
+                    for x in &mut diff {
+                        *x /= d;
+                    }
+                }
+                nearest[1] = (d, diff);
+                if d < nearest[0].0 {
+                    nearest.swap(0, 1);
+                }
+            }
+        }
+        let [(d1, g1), (d2, g2)] = nearest;
+        match self.feature {
+            WorleyFeature::F1 => (d1, g1),
+            WorleyFeature::F2 => (d2, g2),
+            WorleyFeature::F2MinusF1 => {
+                let mut g = [0.0; 4];
+                for k in 0..n {
+                    g[k] = g2[k] - g1[k];
+                }
+                (d2 - d1, g)
+            }
+        }
+    }
+}
+
+impl Default for Worley {
+    fn default() -> Self {
+        Worley {
+            perm: Permutation::default(),
+            feature: WorleyFeature::F1,
+        }
+    }
+}
+
+impl_noise!(Worley);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use noise::Noise;
+
+    #[test]
+    fn worley_cells() {
+        let worley = Worley::new(9, WorleyFeature::F1);
+        //在特征点上 F1 为 0，附近的距离和梯度都指向它。
+        let cell = [2, -3];
+        let center = vec2(
+            worley.feature_point(&cell, 0),
+            worley.feature_point(&cell, 1),
+        );
+        assert_relative_eq!(worley.noise2(center), 0.0);
+        let (d, g) = worley.eval2(center + vec2(0.01, 0.0));
+        assert_relative_eq!(d, 0.01, epsilon = 1e-5);
+        assert_relative_eq!(g, vec2(1.0, 0.0), epsilon = 1e-3);
+        let f2 = Worley::new(9, WorleyFeature::F2).noise2(center);
+        assert!(f2 > 0.0);
+    }
+}
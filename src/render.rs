@@ -0,0 +1,233 @@
+use film::{Film, FilmTile, PixelBounds};
+use integrator::Integrator;
+use math::*;
+use sample::{concentric_disk, PixelSampler, Sampler};
+use scene::Scene;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// 把 `Film` 切成分块，在多个线程上渲染。
+/// 每个分块有自己的 `Sampler`，种子只和 `seed` 与分块编号有关，所以结果与线程数无关。
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    pub spp: u32,
+    pub seed: u64,
+    /// 为 0 时使用全部可用的 CPU。
+    pub threads: usize,
+    pub tile_size: u32,
+    pub sampler: PixelSampler,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer {
+            spp: 16,
+            seed: 0,
+            threads: 0,
+            tile_size: 16,
+            sampler: PixelSampler::Random,
+        }
+    }
+}
+
+impl Renderer {
+    fn tiles(&self, film: &Film) -> Vec<PixelBounds> {
+        let (width, height) = (film.width(), film.height());
+        let size = self.tile_size.max(1);
+        let mut tiles = Vec::new();
+        for y0 in (0..height).step_by(size as usize) {
+            for x0 in (0..width).step_by(size as usize) {
+                tiles.push(PixelBounds {
+                    x0,
+                    y0,
+                    x1: (x0 + size).min(width),
+                    y1: (y0 + size).min(height),
+                });
+            }
+        }
+        tiles
+    }
+
+    fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        }
+    }
+
+    fn render_tile(
+        &self,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        film: &Film,
+        bounds: &PixelBounds,
+        index: usize,
+    ) -> FilmTile {
+        let mut tile = film.tile(bounds);
+        let mut sampler = Sampler::with_stream(self.seed, index as u64);
+        let (width, height) = (film.width(), film.height());
+        let mut offsets = Vec::with_capacity(self.spp as usize);
+        //一个像素里有多个样本时，相邻样本之间的距离比一个像素小。
+        let spacing = (1.0 / (self.spp as f32).sqrt()).max(0.125);
+        for y in bounds.y0..bounds.y1 {
+            for x in bounds.x0..bounds.x1 {
+                self.sampler.generate(&mut sampler, self.spp, &mut offsets);
+                for offset in &offsets {
+                    let raster = vec2(x as f32, y as f32) + offset;
+                    let lens = concentric_disk(&sampler.get_2d()) * 0.5;
+                    let time = scene.camera.sample_time(sampler.get_1d());
+                    let mut ray = scene.camera.raster_ray(&raster, width, height, &lens, time);
+                    ray.scale_differentials(spacing);
+                    let radiance = integrator.radiance(scene, &ray, &mut sampler);
+                    tile.add_sample(&raster, radiance);
+                }
+            }
+        }
+        tile
+    }
+
+    /// 每个像素取 `spp` 个样本，累积到 `film` 上。
+    pub fn render(&self, scene: &Scene, integrator: &dyn Integrator, film: &mut Film) {
+        self.render_with_progress(scene, integrator, film, |_, _| {});
+    }
+
+    /// 同 `render`，每完成一个分块就用已完成数和总数调用一次 `progress`，调用可能来自任意线程。
+    pub fn render_with_progress<F>(
+        &self,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        film: &mut Film,
+        progress: F,
+    ) where
+        F: Fn(usize, usize) + Sync,
+    {
+        let tiles = self.tiles(film);
+        let next = AtomicUsize::new(0);
+        let completed = AtomicUsize::new(0);
+        let shared: &Film = film;
+
+        let finished: Vec<Vec<(usize, FilmTile)>> = thread::scope(|s| {
+            let workers: Vec<_> = (0..self.thread_count().min(tiles.len()))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            if index >= tiles.len() {
+                                break done;
+                            }
+                            let tile =
+                                self.render_tile(scene, integrator, shared, &tiles[index], index);
+                            done.push((index, tile));
+                            progress(completed.fetch_add(1, Ordering::Relaxed) + 1, tiles.len());
+                        }
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        //按分块编号合并，浮点累加的顺序固定下来，结果才可复现。
+        let mut ordered: Vec<Option<FilmTile>> = tiles.iter().map(|_| None).collect();
+        for (index, tile) in finished.into_iter().flatten() {
+            ordered[index] = Some(tile);
+        }
+        for tile in ordered.into_iter().flatten() {
+            film.merge_tile(tile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::{Camera, CameraBuilder, ThinLens};
+    use film::{BoxFilter, GaussianFilter};
+    use integrator::PathTracer;
+    use material::Emissive;
+    use rgb::Rgb;
+    use shapes::{pure_color_shape, Sphere};
+    use std::f32;
+
+    fn camera() -> Camera {
+        CameraBuilder {
+            lens: ThinLens {
+                radius: 0.0,
+                center: Vector3::zero(),
+                focal_length: 1.0,
+            },
+            at: Vector3::zero(),
+            target: -Vector3::unit_z(),
+            up: Vector3::unit_y(),
+            aspect_ratio: 1.0,
+            fov: f32::consts::PI / 4.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }.build()
+    }
+
+    #[test]
+    fn inside_white_furnace() {
+        //相机被包在一个发光的球里，看到的亮度应该等于发光强度。
+        let mut sky = pure_color_shape(Rgb::white(), Sphere::new(Vector3::zero(), 100.0));
+        sky.material = Box::new(Emissive { intensity: 0.5 });
+        let scene = Scene::new(camera(), vec![sky], Vec::new());
+
+        let mut film = Film::new(4, 4, Box::new(BoxFilter::default()));
+        let renderer = Renderer {
+            spp: 2,
+            ..Renderer::default()
+        };
+        renderer.render(&scene, &PathTracer::default(), &mut film);
+        for p in film.resolve() {
+            assert_relative_eq!(p.r, 0.5, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn deterministic_across_thread_counts() {
+        let mut sky = pure_color_shape(Rgb::white(), Sphere::new(Vector3::zero(), 50.0));
+        sky.material = Box::new(Emissive { intensity: 1.0 });
+        let shapes = vec![
+            pure_color_shape(
+                Rgb::new(0.8, 0.3, 0.3),
+                Sphere::new(vec3(0.0, 0.0, -3.0), 1.0),
+            ),
+            pure_color_shape(
+                Rgb::new(0.3, 0.8, 0.3),
+                Sphere::new(vec3(0.0, -101.0, -3.0), 100.0),
+            ),
+            sky,
+        ];
+        let scene = Scene::new(camera(), shapes, Vec::new());
+
+        let render = |threads| {
+            let mut film = Film::new(
+                13,
+                9,
+                Box::new(GaussianFilter {
+                    radius: vec2(1.5, 1.5),
+                    alpha: 2.0,
+                }),
+            );
+            let renderer = Renderer {
+                spp: 4,
+                seed: 42,
+                threads,
+                tile_size: 4,
+                sampler: PixelSampler::Jitter,
+            };
+            renderer.render(&scene, &PathTracer::default(), &mut film);
+            film.resolve()
+        };
+
+        let single = render(1);
+        let multi = render(3);
+        for (a, b) in single.iter().zip(multi.iter()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+}
@@ -2,14 +2,14 @@ extern crate image;
 
 use std::ops::{Add, Div, Mul, Sub, AddAssign};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Rgb {
     pub r: f32,
     pub g: f32,
     pub b: f32,
 }
 
-impl<'a, 'b> Add<&'b Rgb> for &'a Rgb {
+impl<'b> Add<&'b Rgb> for &Rgb {
     type Output = Rgb;
 
     fn add(self, rhs: &'b Rgb) -> Self::Output {
@@ -23,7 +23,7 @@ impl AddAssign for Rgb {
     }
 }
 
-impl<'a, 'b> Sub<&'b Rgb> for &'a Rgb {
+impl<'b> Sub<&'b Rgb> for &Rgb {
     type Output = Rgb;
 
     fn sub(self, rhs: &'b Rgb) -> Self::Output {
@@ -31,9 +31,18 @@ impl<'a, 'b> Sub<&'b Rgb> for &'a Rgb {
     }
 }
 
+impl<'b> Mul<&'b Rgb> for &Rgb {
+    type Output = Rgb;
+
+    fn mul(self, rhs: &'b Rgb) -> Self::Output {
+        Rgb::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b)
+    }
+}
+
 //TODO: 从语义上说应该只有 Vector3 有这些操作。
 impl_binop!(impl Add add for Rgb);
 impl_binop!(impl Sub sub for Rgb);
+impl_binop!(impl Mul mul for Rgb);
 
 impl Mul<f32> for Rgb {
     type Output = Rgb;
@@ -75,20 +84,55 @@ impl Rgb {
     pub fn new(r: f32, g: f32, b: f32) -> Rgb {
         Rgb { r, g, b }
     }
+
+    pub fn is_black(&self) -> bool {
+        self.r == 0.0 && self.g == 0.0 && self.b == 0.0
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// Rec. 709 亮度。
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+}
+
+/// sRGB 传递函数：线性值编码为显示用的非线性值，输入输出都在 `[0, 1]` 内。
+pub fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.040_45 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
 }
 
+/// 截断到 `[0, 1]` 后做 sRGB 编码。需要曝光或色调映射时用 `tonemap::ToneMapper`。
 impl From<Rgb> for image::Rgb<u8> {
     fn from(original: Rgb) -> image::Rgb<u8> {
-        let r2u8 = |r: f32| -> u8 { (r * 255.0) as u8 };
+        let r2u8 = |r: f32| -> u8 {
+            //NaN 经过 clamp 仍是 NaN，转成整数时得到 0。
+            (linear_to_srgb(r.clamp(0.0, 1.0)) * 255.0).round() as u8
+        };
         image::Rgb {
             data: [r2u8(original.r), r2u8(original.g), r2u8(original.b)],
         }
     }
 }
 
+/// 把 sRGB 编码的 8 位颜色解码为线性值。
 impl From<image::Rgb<u8>> for Rgb {
     fn from(original: image::Rgb<u8>) -> Rgb {
-        let u82r = |u: u8| -> f32 { u as f32 / 255.0 };
+        let u82r = |u: u8| -> f32 { srgb_to_linear(f32::from(u) / 255.0) };
         Rgb::new(u82r(original[0]), u82r(original[1]), u82r(original[2]))
     }
 }
@@ -1,7 +1,49 @@
 extern crate rand;
 
-use self::rand::Rng;
-use math::Vector2;
+use self::rand::{Rng, SeedableRng, XorShiftRng};
+use math::{Vector2, Vector3};
+use std::f32;
+
+//相近的种子会让 xorshift 的前几个输出高度相关，先用 splitmix64 打散。
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// 可复现的随机数来源，相同的种子产生相同的样本序列。
+pub struct Sampler {
+    rng: XorShiftRng,
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Sampler {
+        Sampler::with_stream(seed, 0)
+    }
+
+    /// 同一个种子下互不相关的第 `stream` 个序列，用于给每个渲染分块一个独立的随机数来源。
+    pub fn with_stream(seed: u64, stream: u64) -> Sampler {
+        let a = splitmix64(seed ^ splitmix64(stream));
+        let b = splitmix64(a);
+        let seed = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32 | 1];
+        Sampler {
+            rng: XorShiftRng::from_seed(seed),
+        }
+    }
+
+    pub fn get_1d(&mut self) -> f32 {
+        self.rng.gen()
+    }
+
+    pub fn get_2d(&mut self) -> Vector2 {
+        Vector2::new(self.get_1d(), self.get_1d())
+    }
+
+    pub fn rng(&mut self) -> &mut XorShiftRng {
+        &mut self.rng
+    }
+}
 
 pub fn random<T: Rng>(rng: &mut T, samples: u32) -> Vec<Vector2> {
     rng.gen_iter().take(samples as usize).collect()
@@ -34,9 +76,51 @@ pub fn nrooks<T: Rng>(rng: &mut T, result: &mut Vec<Vector2>, samples: u32) {
     rng.shuffle(result);
 }
 
-pub fn box_filter(samples: &mut Vec<Vector2>) {
-    for sample in samples {
-        sample.x -= 0.5;
-        sample.y -= 0.5;
+/// 像素内样本位置的分布方式。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelSampler {
+    Random,
+    /// 分层抖动，`count` 不是平方数时多出来的样本随机放置。
+    Jitter,
+    NRooks,
+}
+
+impl PixelSampler {
+    /// 生成 `count` 个 `[0, 1)^2` 上的样本，写入 `result`。
+    pub fn generate(&self, sampler: &mut Sampler, count: u32, result: &mut Vec<Vector2>) {
+        match *self {
+            PixelSampler::Random => {
+                result.clear();
+                result.extend(random(sampler.rng(), count));
+            }
+            PixelSampler::Jitter => {
+                jitter(sampler.rng(), result, count);
+                let rest = count - result.len() as u32;
+                result.extend(random(sampler.rng(), rest));
+            }
+            PixelSampler::NRooks => nrooks(sampler.rng(), result, count),
+        }
+    }
+}
+
+/// 把 `[0, 1)^2` 上的样本均匀地映射到单位圆盘上（Shirley 同心映射）。
+pub fn concentric_disk(sample: &Vector2) -> Vector2 {
+    let x = 2.0 * sample.x - 1.0;
+    let y = 2.0 * sample.y - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Vector2::new(0.0, 0.0);
     }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, f32::consts::FRAC_PI_4 * (y / x))
+    } else {
+        (y, f32::consts::FRAC_PI_2 - f32::consts::FRAC_PI_4 * (x / y))
+    };
+    Vector2::new(r * theta.cos(), r * theta.sin())
+}
+
+/// 以 z 轴为中心、按余弦加权的半球方向，概率密度为 `cos(theta) / pi`。
+pub fn cosine_hemisphere(sample: &Vector2) -> Vector3 {
+    let d = concentric_disk(sample);
+    let z = (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt();
+    Vector3::new(d.x, d.y, z)
 }
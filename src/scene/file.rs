@@ -0,0 +1,1344 @@
+//! 场景描述文件。
+//!
+//! 文件由若干块组成，块里每行一个属性，属性名后面跟着它的值，`#` 之后是注释：
+//!
+//! ```text
+//! camera {
+//!     at 0 1 4
+//!     target 0 0.5 0
+//!     fov 22.5
+//!     shutter 0 1
+//! }
+//! render {
+//!     width 300
+//!     height 200
+//!     integrator path
+//! }
+//! light point {
+//!     position 3 4 3
+//!     intensity 10
+//! }
+//! texture checker tiles {
+//!     even 0.9
+//!     odd 0.2 0.2 0.3
+//!     scale 0.5
+//! }
+//! texture image label {
+//!     file "label.png"
+//!     wrap clamp
+//!     filter bicubic
+//!     mip ewa
+//! }
+//! texture marble veins {
+//!     ramp 0 0.2
+//!     ramp 1 0.9 0.9 0.8
+//! }
+//! shape plane {
+//!     point 0 0 0
+//!     normal 0 1 0
+//!     texture tiles
+//! }
+//! shape sphere {
+//!     center 0 0.5 0
+//!     radius 0.5
+//!     texture veins
+//!     material dielectric 1.5
+//!     keyframe 0 0 0 0
+//!     keyframe 1 0.2 0 0
+//! }
+//! mesh "bunny.obj" {
+//!     scale 2
+//!     rotate 0 1 0 90
+//! }
+//! ```
+
+use super::{FilterKind, IntegratorKind, RenderSettings, Scene};
+use camera::{CameraBuilder, ThinLens};
+use light::{DirectionalLight, Light, PointLight, SpotLight};
+use material::{Dielectric, Emissive, Lambertian, Material, Mirror};
+use math::*;
+use mesh::{load_obj, load_ply};
+use rgb::Rgb;
+use noise::{Fractal, Noise, Perlin, Simplex, Value, Worley, WorleyFeature};
+use shapes::{AaBox, Cone, Cylinder, Disk, Plane, Rect, Shape, Sphere, TexedShape, Torus, Triangle};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use texture::{Checkerboard, ColorRamp, Domain, Gradient, Grid, ImageTexture, Marble, MipFilter, Mix,
+              Multiply, NoiseTexture, PositionTransform, PureColorTexture, Remap, Stripes,
+              TexelFilter, Texture, UvTransform, WrapMode, Wood};
+
+fn error(line: usize, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, msg),
+    )
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    quoted: bool,
+}
+
+impl Token {
+    fn is(&self, symbol: &str) -> bool {
+        !self.quoted && self.text == symbol
+    }
+}
+
+fn tokenize(source: &str) -> io::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let mut rest = line;
+        loop {
+            rest = rest.trim_start();
+            let token = match rest.chars().next() {
+                None | Some('#') => break,
+                Some(c @ '{') | Some(c @ '}') => {
+                    rest = &rest[1..];
+                    Token {
+                        text: c.to_string(),
+                        line: line_no,
+                        quoted: false,
+                    }
+                }
+                Some('"') => {
+                    let end = rest[1..]
+                        .find('"')
+                        .ok_or_else(|| error(line_no, "unterminated string"))?;
+                    let text = rest[1..end + 1].to_string();
+                    rest = &rest[end + 2..];
+                    Token {
+                        text,
+                        line: line_no,
+                        quoted: true,
+                    }
+                }
+                Some(_) => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "{}#\"".contains(c))
+                        .unwrap_or(rest.len());
+                    let text = rest[..end].to_string();
+                    rest = &rest[end..];
+                    Token {
+                        text,
+                        line: line_no,
+                        quoted: false,
+                    }
+                }
+            };
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+/// 块里的一行。
+struct Property {
+    name: String,
+    args: Vec<Token>,
+    line: usize,
+}
+
+impl Property {
+    fn parse<T: FromStr>(&self, token: &Token, what: &str) -> io::Result<T> {
+        token.text.parse().map_err(|_| {
+            error(
+                self.line,
+                &format!("`{}` expects {}, got `{}`", self.name, what, token.text),
+            )
+        })
+    }
+
+    fn numbers(&self, count: usize) -> io::Result<Vec<f32>> {
+        if self.args.len() != count {
+            return Err(error(
+                self.line,
+                &format!(
+                    "`{}` expects {} number(s), got {}",
+                    self.name,
+                    count,
+                    self.args.len()
+                ),
+            ));
+        }
+        self.args
+            .iter()
+            .map(|t| self.parse(t, "a number"))
+            .collect()
+    }
+
+    fn number(&self) -> io::Result<f32> {
+        Ok(self.numbers(1)?[0])
+    }
+
+    fn integer<T: FromStr>(&self) -> io::Result<T> {
+        match self.args[..] {
+            [ref token] => self.parse(token, "a non-negative integer"),
+            _ => Err(error(
+                self.line,
+                &format!("`{}` expects one integer", self.name),
+            )),
+        }
+    }
+
+    fn vec3(&self) -> io::Result<Vector3> {
+        let v = self.numbers(3)?;
+        Ok(vec3(v[0], v[1], v[2]))
+    }
+
+    /// 一个数表示灰色。
+    fn rgb(&self) -> io::Result<Rgb> {
+        if self.args.len() == 1 {
+            let v = self.number()?;
+            Ok(Rgb::new(v, v, v))
+        } else {
+            let v = self.numbers(3)?;
+            Ok(Rgb::new(v[0], v[1], v[2]))
+        }
+    }
+
+    /// 第一个值必须是 `options` 之一，其余的值留给调用者。
+    fn keyword(&self, options: &[&str]) -> io::Result<&str> {
+        match self.args.first() {
+            Some(token) if options.contains(&token.text.as_str()) => Ok(&token.text),
+            Some(token) => Err(error(
+                self.line,
+                &format!(
+                    "unknown {} `{}`, expected one of: {}",
+                    self.name,
+                    token.text,
+                    options.join(", ")
+                ),
+            )),
+            None => Err(error(
+                self.line,
+                &format!("`{}` expects one of: {}", self.name, options.join(", ")),
+            )),
+        }
+    }
+}
+
+struct Block {
+    kind: String,
+    args: Vec<Token>,
+    line: usize,
+    properties: Vec<Property>,
+}
+
+impl Block {
+    fn name(&self) -> String {
+        self.args
+            .iter()
+            .fold(self.kind.clone(), |name, arg| name + " " + &arg.text)
+    }
+
+    fn unknown(&self, property: &Property, expected: &[&[&str]]) -> io::Error {
+        let expected: Vec<&str> = expected
+            .iter()
+            .flat_map(|names| names.iter().cloned())
+            .collect();
+        error(
+            property.line,
+            &format!(
+                "unknown property `{}` in `{}`, expected one of: {}",
+                property.name,
+                self.name(),
+                expected.join(", ")
+            ),
+        )
+    }
+
+    fn required<T>(&self, value: Option<T>, name: &str) -> io::Result<T> {
+        value.ok_or_else(|| {
+            error(
+                self.line,
+                &format!("`{}` is missing `{}`", self.name(), name),
+            )
+        })
+    }
+
+    /// `shape`、`light` 这类块的第一个参数是类型。
+    fn subtype(&self, options: &[&str]) -> io::Result<&str> {
+        match self.args.first() {
+            Some(token) if options.contains(&token.text.as_str()) => Ok(&token.text),
+            _ => Err(error(
+                self.line,
+                &format!(
+                    "`{}` needs a type, one of: {}",
+                    self.kind,
+                    options.join(", ")
+                ),
+            )),
+        }
+    }
+
+    fn no_args(&self) -> io::Result<()> {
+        if self.args.is_empty() {
+            Ok(())
+        } else {
+            Err(error(
+                self.line,
+                &format!("`{}` takes no arguments", self.kind),
+            ))
+        }
+    }
+}
+
+fn parse_blocks(tokens: Vec<Token>) -> io::Result<Vec<Block>> {
+    let mut tokens = tokens.into_iter();
+    let mut blocks = Vec::new();
+    while let Some(first) = tokens.next() {
+        if first.is("{") || first.is("}") {
+            return Err(error(first.line, &format!("unexpected `{}`", first.text)));
+        }
+        let mut args = Vec::new();
+        loop {
+            match tokens.next() {
+                Some(ref t) if t.is("{") => break,
+                Some(ref t) if t.is("}") => return Err(error(t.line, "unexpected `}`")),
+                Some(t) => args.push(t),
+                None => {
+                    return Err(error(
+                        first.line,
+                        &format!("`{}` is missing `{{`", first.text),
+                    ))
+                }
+            }
+        }
+
+        let mut properties: Vec<Property> = Vec::new();
+        loop {
+            let token = match tokens.next() {
+                Some(t) => t,
+                None => {
+                    return Err(error(
+                        first.line,
+                        &format!("`{}` is missing `}}`", first.text),
+                    ))
+                }
+            };
+            if token.is("}") {
+                break;
+            }
+            if token.is("{") {
+                return Err(error(token.line, "unexpected `{`"));
+            }
+            //属性和它的值写在同一行。
+            match properties.last_mut() {
+                Some(ref mut p) if p.line == token.line => {
+                    p.args.push(token);
+                    continue;
+                }
+                _ => {}
+            }
+            properties.push(Property {
+                name: token.text,
+                args: Vec::new(),
+                line: token.line,
+            });
+        }
+        blocks.push(Block {
+            kind: first.text,
+            args,
+            line: first.line,
+            properties,
+        });
+    }
+    Ok(blocks)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MaterialKind {
+    Lambertian,
+    Mirror,
+    Dielectric(f32),
+    Emissive(f32),
+}
+
+impl MaterialKind {
+    fn build(self) -> Box<dyn Material> {
+        match self {
+            MaterialKind::Lambertian => Box::new(Lambertian),
+            MaterialKind::Mirror => Box::new(Mirror),
+            MaterialKind::Dielectric(ior) => Box::new(Dielectric { ior }),
+            MaterialKind::Emissive(intensity) => Box::new(Emissive { intensity }),
+        }
+    }
+}
+
+const SURFACE_PROPERTIES: &[&str] = &[
+    "color",
+    "texture",
+    "material",
+    "translate",
+    "rotate",
+    "scale",
+    "keyframe",
+];
+
+/// `translate`、`rotate` 和 `scale`，其他属性返回 `None`。
+fn transform(property: &Property) -> io::Result<Option<Affine>> {
+    let transform = match property.name.as_str() {
+        "translate" => Affine::translation(property.vec3()?),
+        "rotate" => {
+            let v = property.numbers(4)?;
+            let axis = vec3(v[0], v[1], v[2]);
+            if axis.magnitude2() == 0.0 {
+                return Err(error(property.line, "rotation axis must not be zero"));
+            }
+            Affine::rotation(Quaternion::from_axis_angle(axis.normalize(), Deg(v[3])))
+        }
+        "scale" => {
+            //一个数是均匀缩放，三个数分别缩放各个轴。
+            let s = if property.args.len() == 1 {
+                let s = property.number()?;
+                vec3(s, s, s)
+            } else {
+                property.vec3()?
+            };
+            Affine::scale(s.x, s.y, s.z)
+                .ok_or_else(|| error(property.line, "scale must not be zero"))?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(transform))
+}
+
+/// 前面的 `texture` 块定义的纹理，按名字查找。
+type Textures = HashMap<String, Arc<dyn Texture>>;
+
+fn named_texture(property: &Property, textures: &Textures) -> io::Result<Arc<dyn Texture>> {
+    let name = match property.args[..] {
+        [ref name] => &name.text,
+        _ => {
+            return Err(error(
+                property.line,
+                &format!("`{}` expects a texture name", property.name),
+            ))
+        }
+    };
+    textures
+        .get(name)
+        .cloned()
+        .ok_or_else(|| error(property.line, &format!("unknown texture `{}`", name)))
+}
+
+/// 纹理的输入可以是前面定义过的纹理名，也可以直接写颜色。
+fn texture_input(property: &Property, textures: &Textures) -> io::Result<Box<dyn Texture>> {
+    match property.args[..] {
+        [ref token] if token.text.parse::<f32>().is_err() => {
+            Ok(Box::new(named_texture(property, textures)?))
+        }
+        _ => Ok(Box::new(PureColorTexture {
+            color: property.rgb()?,
+        })),
+    }
+}
+
+/// 形状和网格共有的属性。
+struct Surface {
+    texture: Option<Arc<dyn Texture>>,
+    material: Option<MaterialKind>,
+    transform: Affine,
+    keyframes: Vec<(f32, Transformation)>,
+}
+
+impl Surface {
+    fn new() -> Self {
+        Surface {
+            texture: None,
+            material: None,
+            transform: Affine::identity(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    /// 不认识的属性返回 `false`。
+    fn parse(&mut self, property: &Property, textures: &Textures) -> io::Result<bool> {
+        //变换按书写顺序依次作用。
+        if let Some(next) = transform(property)? {
+            self.transform = self.transform.then(&next);
+            return Ok(true);
+        }
+        match property.name.as_str() {
+            "color" => {
+                self.texture = Some(Arc::new(PureColorTexture {
+                    color: property.rgb()?,
+                }))
+            }
+            "texture" => self.texture = Some(named_texture(property, textures)?),
+            "material" => {
+                let kind = property.keyword(&["lambertian", "mirror", "dielectric", "emissive"])?;
+                let value = |default| match property.args.get(1) {
+                    Some(token) => property.parse(token, "a number"),
+                    None => Ok(default),
+                };
+                self.material = Some(match kind {
+                    "lambertian" => MaterialKind::Lambertian,
+                    "mirror" => MaterialKind::Mirror,
+                    "dielectric" => MaterialKind::Dielectric(value(1.5)?),
+                    _ => MaterialKind::Emissive(value(1.0)?),
+                });
+            }
+            "keyframe" => {
+                //`keyframe time x y z [ax ay az deg]`，在其他变换之后按时刻插值的位移和旋转。
+                let count = property.args.len();
+                if count != 4 && count != 8 {
+                    return Err(error(
+                        property.line,
+                        &format!("`keyframe` expects 4 or 8 numbers, got {}", count),
+                    ));
+                }
+                let v = property.numbers(count)?;
+                let mut key = Transformation {
+                    disp: vec3(v[1], v[2], v[3]),
+                    ..Transformation::one()
+                };
+                if v.len() == 8 {
+                    let axis = vec3(v[4], v[5], v[6]);
+                    if axis.magnitude2() == 0.0 {
+                        return Err(error(property.line, "rotation axis must not be zero"));
+                    }
+                    key.rot = Quaternion::from_axis_angle(axis.normalize(), Deg(v[7]));
+                }
+                self.keyframes.push((v[0], key));
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn apply(&self, shape: &mut TexedShape) {
+        if let Some(ref texture) = self.texture {
+            shape.texture = Box::new(texture.clone());
+        }
+        if let Some(material) = self.material {
+            shape.material = material.build();
+        }
+        if !self.keyframes.is_empty() {
+            shape.motion = Some(AnimatedTransform::new(self.keyframes.clone()));
+        }
+    }
+}
+
+fn shape(block: &Block, textures: &Textures) -> io::Result<TexedShape> {
+    let kind = block.subtype(&[
+        "sphere", "triangle", "plane", "disk", "rect", "box", "cylinder", "cone", "torus",
+    ])?;
+    let mut surface = Surface::new();
+    let own: &[&str] = match kind {
+        "sphere" => &["center", "radius"],
+        "triangle" => &["p0", "p1", "p2"],
+        "plane" => &["point", "normal"],
+        "disk" => &["center", "normal", "radius"],
+        "rect" => &["corner", "edge0", "edge1"],
+        "box" => &["min", "max"],
+        "cylinder" | "cone" => &["center", "radius", "height"],
+        _ => &["center", "major_radius", "minor_radius"],
+    };
+    let mut values: Vec<&Property> = Vec::new();
+    for p in &block.properties {
+        if own.contains(&p.name.as_str()) {
+            values.push(p);
+        } else if !surface.parse(p, textures)? {
+            return Err(block.unknown(p, &[own, SURFACE_PROPERTIES]));
+        }
+    }
+    let find = |name: &str| {
+        let value = values.iter().rev().find(|p| p.name == name);
+        block.required(value, name)
+    };
+    let v = |name: &str| find(name)?.vec3();
+    let n = |name: &str| find(name)?.number();
+
+    let geometry: Box<dyn Shape> = match kind {
+        "sphere" => Box::new(Sphere::new(v("center")?, n("radius")?)),
+        "triangle" => Box::new(Triangle::new(v("p0")?, v("p1")?, v("p2")?)),
+        "plane" => Box::new(Plane::new(v("point")?, v("normal")?)),
+        "disk" => Box::new(Disk::new(v("center")?, v("normal")?, n("radius")?)),
+        "rect" => Box::new(Rect::new(v("corner")?, v("edge0")?, v("edge1")?)),
+        "box" => Box::new(AaBox::new(v("min")?, v("max")?)),
+        "cylinder" => Box::new(Cylinder::new(v("center")?, n("radius")?, n("height")?)),
+        "cone" => Box::new(Cone::new(v("center")?, n("radius")?, n("height")?)),
+        _ => Box::new(Torus::new(
+            v("center")?,
+            n("major_radius")?,
+            n("minor_radius")?,
+        )),
+    };
+    let mut shape = TexedShape {
+        texture: Box::new(PureColorTexture {
+            color: Rgb::new(0.8, 0.8, 0.8),
+        }),
+        material: Box::new(Lambertian),
+        shape: geometry,
+        transform: surface.transform,
+        motion: None,
+    };
+    surface.apply(&mut shape);
+    Ok(shape)
+}
+
+fn mesh(block: &Block, dir: &Path, textures: &Textures) -> io::Result<Vec<TexedShape>> {
+    let file = match block.args[..] {
+        [ref file] => &file.text,
+        _ => return Err(error(block.line, "`mesh` expects one file name")),
+    };
+    let mut surface = Surface::new();
+    for p in &block.properties {
+        if !surface.parse(p, textures)? {
+            return Err(block.unknown(p, &[SURFACE_PROPERTIES]));
+        }
+    }
+
+    let path = dir.join(file);
+    let loaded = match path.extension().and_then(|e| e.to_str()) {
+        Some("obj") => load_obj(&path).and_then(|model| model.texed_shapes(surface.transform)),
+        Some("ply") => load_ply(&path).map(|mesh| mesh.texed_shapes(surface.transform)),
+        _ => {
+            return Err(error(
+                block.line,
+                &format!("unsupported mesh format `{}`, expected .obj or .ply", file),
+            ))
+        }
+    };
+    let mut shapes = loaded.map_err(|e| {
+        error(
+            block.line,
+            &format!("cannot load `{}`: {}", path.display(), e),
+        )
+    })?;
+    for shape in &mut shapes {
+        surface.apply(shape);
+    }
+    Ok(shapes)
+}
+
+const TEXTURE_KINDS: &[&str] = &[
+    "image",
+    "noise",
+    "turbulence",
+    "marble",
+    "wood",
+    "checker",
+    "grid",
+    "stripes",
+    "gradient",
+    "mix",
+    "multiply",
+    "remap",
+];
+
+/// 所有纹理都可以摆放位置，也可以变换纹理坐标。
+const TEXTURE_PROPERTIES: &[&str] = &[
+    "translate",
+    "rotate",
+    "scale",
+    "uv_scale",
+    "uv_rotate",
+    "uv_offset",
+];
+
+fn noise_with_octaves<N>(noise: N, octaves: u32) -> Box<dyn Noise + Send + Sync>
+where
+    N: Noise + Send + Sync + 'static,
+{
+    if octaves > 1 {
+        Box::new(Fractal::fbm(noise, octaves))
+    } else {
+        Box::new(noise)
+    }
+}
+
+/// `texture 类型 名字 { ... }`，返回名字和纹理。
+fn texture(
+    block: &Block,
+    dir: &Path,
+    textures: &Textures,
+) -> io::Result<(String, Arc<dyn Texture>)> {
+    let kind = block.subtype(TEXTURE_KINDS)?;
+    let name = match block.args[..] {
+        [_, ref name] => name.text.clone(),
+        _ => {
+            return Err(error(
+                block.line,
+                &format!("`texture {}` needs a name", kind),
+            ))
+        }
+    };
+    let own: &[&str] = match kind {
+        "image" => &["file", "wrap", "filter", "mip"],
+        "noise" => &["type", "seed", "octaves", "ramp"],
+        "turbulence" => &["seed", "octaves", "ramp"],
+        "marble" | "wood" => &["seed", "strength", "ramp"],
+        "checker" => &["even", "odd", "domain"],
+        "grid" => &["line", "fill", "width", "domain"],
+        "stripes" => &["a", "b", "direction", "domain"],
+        "gradient" => &["direction", "ramp", "domain"],
+        "mix" => &["a", "b", "amount"],
+        "multiply" => &["a", "b"],
+        _ => &["input", "range", "ramp"],
+    };
+    let mut values: Vec<&Property> = Vec::new();
+    let mut stops = Vec::new();
+    let mut placement = None;
+    let (mut uv_scale, mut uv_rotate, mut uv_offset) = (vec2(1.0, 1.0), 0.0, Vector2::zero());
+    let mut uv_transformed = false;
+    for p in &block.properties {
+        if p.name == "ramp" && own.contains(&"ramp") {
+            //`ramp 位置 颜色`，可以写多行。
+            let v = p.numbers(if p.args.len() == 2 { 2 } else { 4 })?;
+            let color = if v.len() == 2 {
+                Rgb::new(v[1], v[1], v[1])
+            } else {
+                Rgb::new(v[1], v[2], v[3])
+            };
+            stops.push((v[0], color));
+        } else if own.contains(&p.name.as_str()) {
+            values.push(p);
+        } else if let Some(next) = transform(p)? {
+            placement = Some(placement.unwrap_or_else(Affine::identity).then(&next));
+        } else {
+            match p.name.as_str() {
+                "uv_scale" => {
+                    uv_scale = if p.args.len() == 1 {
+                        let s = p.number()?;
+                        vec2(s, s)
+                    } else {
+                        let v = p.numbers(2)?;
+                        vec2(v[0], v[1])
+                    }
+                }
+                "uv_rotate" => uv_rotate = p.number()?.to_radians(),
+                "uv_offset" => {
+                    let v = p.numbers(2)?;
+                    uv_offset = vec2(v[0], v[1]);
+                }
+                _ => return Err(block.unknown(p, &[own, TEXTURE_PROPERTIES])),
+            }
+            uv_transformed = true;
+        }
+    }
+    let find = |name: &str| values.iter().rev().find(|p| p.name == name);
+    let input = |name: &str| texture_input(block.required(find(name), name)?, textures);
+    let number = |name: &str, default| find(name).map_or(Ok(default), |p| p.number());
+    let vector = |name: &str, default| find(name).map_or(Ok(default), |p| p.vec3());
+    let seed = find("seed").map_or(Ok(0), |p| p.integer())?;
+    let octaves = |default| find("octaves").map_or(Ok(default), |p| p.integer());
+    let domain = match find("domain") {
+        Some(p) if p.keyword(&["uv", "position"])? == "uv" => Domain::Uv,
+        _ => Domain::Position,
+    };
+    let ramp = if stops.is_empty() {
+        ColorRamp::default()
+    } else {
+        ColorRamp::new(stops)
+    };
+
+    let mut result: Box<dyn Texture> = match kind {
+        "image" => {
+            let file = block.required(find("file"), "file")?;
+            let path = match file.args[..] {
+                [ref name] => dir.join(&name.text),
+                _ => return Err(error(file.line, "`file` expects one file name")),
+            };
+            let mut image = ImageTexture::open(&path).map_err(|e| {
+                error(
+                    file.line,
+                    &format!("cannot load `{}`: {}", path.display(), e),
+                )
+            })?;
+            if let Some(p) = find("wrap") {
+                image.wrap = match p.keyword(&["repeat", "clamp", "mirror"])? {
+                    "repeat" => WrapMode::Repeat,
+                    "clamp" => WrapMode::Clamp,
+                    _ => WrapMode::Mirror,
+                };
+            }
+            if let Some(p) = find("filter") {
+                image.filter = match p.keyword(&["nearest", "bilinear", "bicubic"])? {
+                    "nearest" => TexelFilter::Nearest,
+                    "bilinear" => TexelFilter::Bilinear,
+                    _ => TexelFilter::Bicubic,
+                };
+            }
+            if let Some(p) = find("mip") {
+                image.mip = match p.keyword(&["none", "trilinear", "ewa"])? {
+                    "none" => MipFilter::None,
+                    "trilinear" => MipFilter::Trilinear,
+                    _ => MipFilter::Ewa,
+                };
+            }
+            Box::new(image)
+        }
+        "noise" => {
+            let octaves = octaves(1)?;
+            let kind = match find("type") {
+                Some(p) => p.keyword(&["perlin", "simplex", "value", "worley"])?,
+                None => "perlin",
+            };
+            let (noise, range) = match kind {
+                "perlin" => (noise_with_octaves(Perlin::new(seed), octaves), (-1.0, 1.0)),
+                "simplex" => (noise_with_octaves(Simplex::new(seed), octaves), (-1.0, 1.0)),
+                "value" => (noise_with_octaves(Value::new(seed), octaves), (-1.0, 1.0)),
+                _ => (
+                    noise_with_octaves(Worley::new(seed, WorleyFeature::F1), octaves),
+                    (0.0, 1.0),
+                ),
+            };
+            Box::new(NoiseTexture { noise, range, ramp })
+        }
+        "turbulence" => Box::new(NoiseTexture {
+            ramp,
+            ..NoiseTexture::turbulence(seed, octaves(6)?)
+        }),
+        "marble" => {
+            let marble = Marble::new(seed);
+            Box::new(Marble {
+                strength: number("strength", marble.strength)?,
+                ramp,
+                ..marble
+            })
+        }
+        "wood" => {
+            let wood = Wood::new(seed);
+            Box::new(Wood {
+                strength: number("strength", wood.strength)?,
+                ramp,
+                ..wood
+            })
+        }
+        "checker" => Box::new(Checkerboard {
+            even: input("even")?,
+            odd: input("odd")?,
+            domain,
+        }),
+        "grid" => Box::new(Grid {
+            line: input("line")?,
+            fill: input("fill")?,
+            width: number("width", 0.05)?,
+            domain,
+        }),
+        "stripes" => Box::new(Stripes {
+            a: input("a")?,
+            b: input("b")?,
+            direction: vector("direction", Vector3::unit_x())?,
+            domain,
+        }),
+        "gradient" => Box::new(Gradient {
+            ramp,
+            direction: vector("direction", Vector3::unit_y())?,
+            domain,
+        }),
+        "mix" => Box::new(Mix {
+            a: input("a")?,
+            b: input("b")?,
+            amount: input("amount")?,
+        }),
+        "multiply" => Box::new(Multiply {
+            a: input("a")?,
+            b: input("b")?,
+        }),
+        _ => {
+            let range = match find("range") {
+                Some(p) => {
+                    let v = p.numbers(2)?;
+                    (v[0], v[1])
+                }
+                None => (0.0, 1.0),
+            };
+            Box::new(Remap {
+                input: input("input")?,
+                range,
+                ramp,
+            })
+        }
+    };
+    if let Some(placement) = placement {
+        result = Box::new(PositionTransform::new(result, &placement));
+    }
+    if uv_transformed {
+        result = Box::new(UvTransform {
+            texture: result,
+            scale: uv_scale,
+            rotation: uv_rotate,
+            offset: uv_offset,
+        });
+    }
+    Ok((name, Arc::from(result)))
+}
+
+fn light(block: &Block) -> io::Result<Box<dyn Light>> {
+    let kind = block.subtype(&["point", "directional", "spot"])?;
+    let own: &[&str] = match kind {
+        "point" => &["position", "intensity"],
+        "directional" => &["direction", "radiance"],
+        _ => &[
+            "position",
+            "direction",
+            "intensity",
+            "falloff_start",
+            "total_width",
+        ],
+    };
+    let mut vectors: Vec<(&str, Vector3)> = Vec::new();
+    let mut colors: Vec<(&str, Rgb)> = Vec::new();
+    let mut angles: Vec<(&str, f32)> = Vec::new();
+    for p in &block.properties {
+        let name = p.name.as_str();
+        if !own.contains(&name) {
+            return Err(block.unknown(p, &[own]));
+        }
+        match name {
+            "position" | "direction" => vectors.push((name, p.vec3()?)),
+            "intensity" | "radiance" => colors.push((name, p.rgb()?)),
+            _ => angles.push((name, p.number()?.to_radians())),
+        }
+    }
+    let get = |list: &[(&str, Vector3)], name| {
+        block.required(list.iter().rev().find(|v| v.0 == name).map(|v| v.1), name)
+    };
+    let color = |name| block.required(colors.iter().rev().find(|v| v.0 == name).map(|v| v.1), name);
+    let angle = |name| block.required(angles.iter().rev().find(|v| v.0 == name).map(|v| v.1), name);
+
+    Ok(match kind {
+        "point" => Box::new(PointLight {
+            pos: get(&vectors, "position")?,
+            intensity: color("intensity")?,
+        }),
+        "directional" => Box::new(DirectionalLight {
+            direction: get(&vectors, "direction")?,
+            radiance: color("radiance")?,
+        }),
+        _ => Box::new(SpotLight {
+            pos: get(&vectors, "position")?,
+            direction: get(&vectors, "direction")?,
+            intensity: color("intensity")?,
+            falloff_start: angle("falloff_start")?,
+            total_width: angle("total_width")?,
+        }),
+    })
+}
+
+fn render(block: &Block, settings: &mut RenderSettings) -> io::Result<()> {
+    block.no_args()?;
+    for p in &block.properties {
+        match p.name.as_str() {
+            "width" => settings.width = p.integer()?,
+            "height" => settings.height = p.integer()?,
+            "spp" => settings.renderer.spp = p.integer()?,
+            "seed" => settings.renderer.seed = p.integer()?,
+            "threads" => settings.renderer.threads = p.integer()?,
+            "tile_size" => settings.renderer.tile_size = p.integer()?,
+            "max_depth" => settings.max_depth = Some(p.integer()?),
+            "background" => settings.background = p.rgb()?,
+            "integrator" => {
+                settings.integrator = match p.keyword(&["path", "whitted"])? {
+                    "path" => IntegratorKind::Path,
+                    _ => IntegratorKind::Whitted,
+                }
+            }
+            "filter" => {
+                settings.filter = match p.keyword(&["box", "tent", "gaussian", "mitchell"])? {
+                    "box" => FilterKind::Box,
+                    "tent" => FilterKind::Tent,
+                    "gaussian" => FilterKind::Gaussian,
+                    _ => FilterKind::Mitchell,
+                }
+            }
+            _ => {
+                return Err(block.unknown(
+                    p,
+                    &[&[
+                        "width",
+                        "height",
+                        "spp",
+                        "seed",
+                        "threads",
+                        "tile_size",
+                        "integrator",
+                        "max_depth",
+                        "background",
+                        "filter",
+                    ]],
+                ))
+            }
+        }
+    }
+    if settings.width == 0 || settings.height == 0 {
+        return Err(error(block.line, "image size must not be zero"));
+    }
+    Ok(())
+}
+
+/// 没写的项取默认值，`aspect_ratio` 默认与图像的宽高比一致。
+fn camera(block: Option<&Block>, settings: &RenderSettings) -> io::Result<CameraBuilder> {
+    let mut builder = CameraBuilder {
+        lens: ThinLens {
+            radius: 0.0,
+            center: Vector3::zero(),
+            focal_length: 1.0,
+        },
+        at: Vector3::zero(),
+        target: -Vector3::unit_z(),
+        up: Vector3::unit_y(),
+        aspect_ratio: settings.width as f32 / settings.height as f32,
+        fov: 22.5f32.to_radians(),
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    };
+    if let Some(block) = block {
+        block.no_args()?;
+        for p in &block.properties {
+            match p.name.as_str() {
+                "at" => builder.at = p.vec3()?,
+                "target" => builder.target = p.vec3()?,
+                "up" => builder.up = p.vec3()?,
+                "fov" => builder.fov = p.number()?.to_radians(),
+                "aspect_ratio" => builder.aspect_ratio = p.number()?,
+                "lens_radius" => builder.lens.radius = p.number()?,
+                "focal_length" => builder.lens.focal_length = p.number()?,
+                "shutter" => {
+                    let v = p.numbers(2)?;
+                    if v[0] > v[1] {
+                        return Err(error(p.line, "shutter must not close before it opens"));
+                    }
+                    builder.shutter_open = v[0];
+                    builder.shutter_close = v[1];
+                }
+                _ => {
+                    return Err(block.unknown(
+                        p,
+                        &[&[
+                            "at",
+                            "target",
+                            "up",
+                            "fov",
+                            "aspect_ratio",
+                            "lens_radius",
+                            "focal_length",
+                            "shutter",
+                        ]],
+                    ))
+                }
+            }
+        }
+        if (builder.target - builder.at).magnitude2() == 0.0 {
+            return Err(error(block.line, "camera `target` must differ from `at`"));
+        }
+    }
+    builder.lens.center = builder.at;
+    Ok(builder)
+}
+
+pub fn parse(source: &str, dir: &Path) -> io::Result<Scene> {
+    let blocks = parse_blocks(tokenize(source)?)?;
+
+    let mut settings = RenderSettings::default();
+    let mut camera_block = None;
+    let mut render_line = None;
+    let mut shapes = Vec::new();
+    let mut lights = Vec::new();
+    let mut textures = Textures::new();
+    for block in &blocks {
+        match block.kind.as_str() {
+            "camera" => {
+                if camera_block.is_some() {
+                    return Err(error(block.line, "duplicate `camera` block"));
+                }
+                camera_block = Some(block);
+            }
+            "render" => {
+                if render_line.is_some() {
+                    return Err(error(block.line, "duplicate `render` block"));
+                }
+                render_line = Some(block.line);
+                render(block, &mut settings)?;
+            }
+            "light" => lights.push(light(block)?),
+            "texture" => {
+                let (name, texture) = texture(block, dir, &textures)?;
+                if textures.insert(name.clone(), texture).is_some() {
+                    return Err(error(block.line, &format!("duplicate texture `{}`", name)));
+                }
+            }
+            "shape" => shapes.push(shape(block, &textures)?),
+            "mesh" => shapes.extend(mesh(block, dir, &textures)?),
+            other => {
+                return Err(error(
+                    block.line,
+                    &format!(
+                        "unknown block `{}`, expected one of: camera, render, light, texture, \
+                         shape, mesh",
+                        other
+                    ),
+                ))
+            }
+        }
+    }
+
+    let camera = camera(camera_block, &settings)?.build();
+    let mut scene = Scene::new(camera, shapes, lights);
+    scene.settings = settings;
+    Ok(scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> io::Result<Scene> {
+        parse(source, Path::new(""))
+    }
+
+    fn message(source: &str) -> String {
+        parse_str(source).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn full_scene() {
+        let scene = parse_str(
+            r#"
+# 注释
+camera {
+    at 0 1 4
+    target 0 0.5 0
+    fov 30
+}
+render {
+    width 40
+    height 20
+    spp 4
+    integrator whitted
+    filter mitchell
+}
+light point {
+    position 3 4 3
+    intensity 10
+}
+light spot {
+    position 0 4 0
+    direction 0 -1 0
+    intensity 1 2 3
+    falloff_start 20
+    total_width 30
+}
+shape sphere {
+    center 0 0 0
+    radius 0.5
+    color 0.7 0.2 0.2
+    material dielectric 1.3
+    translate 0 0.5 0
+}
+shape triangle { p0 0 0 0
+    p1 1 0 0
+    p2 0 1 0
+    material emissive 4
+}
+"#,
+        )
+        .unwrap();
+        assert_eq!(scene.settings.width, 40);
+        assert_eq!(scene.settings.renderer.spp, 4);
+        assert_eq!(scene.settings.integrator, IntegratorKind::Whitted);
+        assert_eq!(scene.settings.filter, FilterKind::Mitchell);
+        assert_eq!(scene.lights.len(), 2);
+        assert_eq!(scene.shapes().len(), 2);
+        let sphere = scene
+            .shapes()
+            .iter()
+            .find(|s| s.transform.point(&Vector3::zero()).y == 0.5)
+            .unwrap();
+        assert_relative_eq!(sphere.bounding_box().max, vec3(0.5, 1.0, 0.5));
+    }
+
+    #[test]
+    fn transforms_apply_in_order() {
+        let scene = parse_str(
+            "shape sphere {\n center 1 0 0\n radius 1\n translate 1 0 0\n rotate 0 0 1 90\n}",
+        )
+        .unwrap();
+        let center = scene.shapes()[0].bounding_box().centroid();
+        assert_relative_eq!(center, vec3(0.0, 2.0, 0.0), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn analytic_shapes() {
+        let scene = parse_str(
+            "shape box {\n min 0 0 0\n max 1 2 3\n}\n\
+             shape cylinder {\n center 0 0 0\n radius 1\n height 2\n}\n\
+             shape torus {\n center 0 0 0\n major_radius 2\n minor_radius 0.5\n}",
+        )
+        .unwrap();
+        let mut extents: Vec<Vector3> = scene
+            .shapes()
+            .iter()
+            .map(|s| s.bounding_box().diagonal())
+            .collect();
+        extents.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        assert_relative_eq!(extents[0], vec3(1.0, 2.0, 3.0));
+        assert_relative_eq!(extents[1], vec3(2.0, 2.0, 2.0));
+        assert_relative_eq!(extents[2], vec3(5.0, 1.0, 5.0));
+
+        assert_eq!(
+            message("shape cone {\n center 0 0 0\n radius 1\n}"),
+            "line 1: `shape cone` is missing `height`"
+        );
+    }
+
+    #[test]
+    fn keyframes_and_shutter() {
+        let scene = parse_str(
+            "camera {\n shutter 0 1\n}\n\
+             shape sphere {\n center 0 0 0\n radius 1\n translate 0 1 0\n \
+             keyframe 0 0 0 0\n keyframe 1 2 0 0 0 1 0 180\n}",
+        )
+        .unwrap();
+        assert_eq!(scene.camera.sample_time(0.5), 0.5);
+        let shape = &scene.shapes()[0];
+        let center = |time| shape.transform_at(time).point(&Vector3::zero());
+        assert_relative_eq!(center(0.0), vec3(0.0, 1.0, 0.0), epsilon = 1e-5);
+        assert_relative_eq!(center(1.0), vec3(2.0, 1.0, 0.0), epsilon = 1e-5);
+        let bbox = shape.bounding_box();
+        assert!(bbox.max.x >= 3.0 && bbox.min.x <= -1.0);
+
+        assert_eq!(
+            message("shape sphere {\n radius 1\n keyframe 0 1 2\n}"),
+            "line 3: `keyframe` expects 4 or 8 numbers, got 3"
+        );
+        assert_eq!(
+            message("camera {\n shutter 1 0\n}"),
+            "line 2: shutter must not close before it opens"
+        );
+    }
+
+    #[test]
+    fn textures() {
+        let scene = parse_str(
+            "texture checker tiles {\n even 1\n odd 0 0 1\n scale 2\n}\n\
+             texture gradient fade {\n direction 1 0 0\n ramp 0 0\n ramp 1 1 0 0\n}\n\
+             texture mix both {\n a tiles\n b fade\n amount 0.5\n}\n\
+             texture stripes bands {\n a 1\n b 0\n domain uv\n uv_scale 4\n}\n\
+             shape sphere {\n center 0 0 0\n radius 1\n texture both\n}\n\
+             shape sphere {\n center 0 0 0\n radius 1\n texture bands\n}",
+        )
+        .unwrap();
+        let value =
+            |i: usize, p: Vector3, uv: Vector2| scene.shapes()[i].texture.get_value(&p, &uv);
+        //放大两倍的棋盘格，(1, 0.5, 0.5) 还在原点所在的格子里。
+        let c = value(0, vec3(1.0, 0.5, 0.5), Vector2::zero());
+        assert_relative_eq!(c.r, 1.0);
+        assert_relative_eq!(c.b, 0.5);
+        let c = value(0, vec3(2.5, 0.5, 0.5), Vector2::zero());
+        assert_relative_eq!(c.r, 0.5);
+        assert_relative_eq!(c.b, 0.5);
+        assert_eq!(value(1, Vector3::zero(), vec2(0.1, 0.0)).r, 1.0);
+        assert_eq!(value(1, Vector3::zero(), vec2(0.3, 0.0)).r, 0.0);
+
+        assert_eq!(
+            message("shape sphere {\n radius 1\n texture wood\n}"),
+            "line 3: unknown texture `wood`"
+        );
+        assert_eq!(
+            message("texture marble {\n}"),
+            "line 1: `texture marble` needs a name"
+        );
+        assert_eq!(
+            message("texture wood a {\n}\ntexture wood a {\n}"),
+            "line 3: duplicate texture `a`"
+        );
+        assert_eq!(
+            message("texture checker c {\n even 1\n}"),
+            "line 1: `texture checker c` is missing `odd`"
+        );
+        assert_eq!(
+            message("texture grid g {\n line 1\n fill 0\n seed 2\n}"),
+            "line 4: unknown property `seed` in `texture grid g`, expected one of: \
+             line, fill, width, domain, translate, rotate, scale, uv_scale, uv_rotate, uv_offset"
+        );
+    }
+
+    #[test]
+    fn image_texture() {
+        extern crate image;
+
+        let dir = ::std::env::temp_dir();
+        let name = format!("rrt_scene_image_texture_{}.png", ::std::process::id());
+        let path = dir.join(&name);
+        image::ImageBuffer::from_pixel(2, 2, image::Rgb { data: [255, 0, 0] })
+            .save(&path)
+            .unwrap();
+        let source = format!(
+            "texture image red {{\n file \"{}\"\n wrap mirror\n filter nearest\n mip none\n}}\n\
+             shape sphere {{\n center 0 0 0\n radius 1\n texture red\n}}",
+            name
+        );
+        let scene = parse(&source, &dir);
+        ::std::fs::remove_file(&path).unwrap();
+        let c = scene.unwrap().shapes()[0]
+            .texture
+            .get_value(&Vector3::zero(), &vec2(1.5, -0.2));
+        assert_eq!(c, Rgb::new(1.0, 0.0, 0.0));
+
+        //后面接的是操作系统给出的错误，各平台不同。
+        assert!(message("texture image a {\n file \"missing.png\"\n}")
+            .starts_with("line 2: cannot load `missing.png`: "));
+        assert_eq!(
+            message("texture image a {\n wrap clamp\n}"),
+            "line 1: `texture image a` is missing `file`"
+        );
+    }
+
+    #[test]
+    fn example_scene() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/spheres.scene");
+        let scene = Scene::load(path).unwrap();
+        assert_eq!(scene.shapes().len(), 9);
+        assert_eq!(scene.settings.renderer.spp, 64);
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/primitives.scene");
+        let scene = Scene::load(path).unwrap();
+        assert_eq!(scene.shapes().len(), 7);
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/textures.scene");
+        let scene = Scene::load(path).unwrap();
+        assert_eq!(scene.shapes().len(), 6);
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(
+            message("shape sphere {\n  center 0 0 0\n  radus 1\n}"),
+            "line 3: unknown property `radus` in `shape sphere`, expected one of: \
+             center, radius, color, texture, material, translate, rotate, scale, keyframe"
+        );
+        assert_eq!(
+            message("\nshape sphere {\n  center 0 0 0\n}"),
+            "line 2: `shape sphere` is missing `radius`"
+        );
+        assert_eq!(
+            message("light point {\n position 0 0\n}"),
+            "line 2: `position` expects 3 number(s), got 2"
+        );
+        assert_eq!(
+            message("render {\n spp many\n}"),
+            "line 2: `spp` expects a non-negative integer, got `many`"
+        );
+        assert_eq!(
+            message("shape cube {\n}"),
+            "line 1: `shape` needs a type, one of: sphere, triangle, plane, disk, rect, box, \
+             cylinder, cone, torus"
+        );
+        assert_eq!(
+            message("camera {\n at 0 0 0\n"),
+            "line 1: `camera` is missing `}`"
+        );
+        assert!(message("mesh \"missing.obj\" {\n}").starts_with("line 1: cannot load"));
+    }
+}
@@ -0,0 +1,201 @@
+use bvh::{Bvh, Primitive};
+use camera::Camera;
+use film::{BoxFilter, Film, Filter, GaussianFilter, MitchellFilter, TentFilter};
+use integrator::{Integrator, PathTracer, Whitted};
+use light::Light;
+use math::*;
+use render::Renderer;
+use rgb::Rgb;
+use shapes::{HitRecord, Ray, TexedShape};
+use std::f32;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+mod file;
+
+/// 避免射线与出发的表面自相交。
+pub const RAY_EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegratorKind {
+    Path,
+    Whitted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
+
+/// 场景文件里 `render` 块给出的设置。
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub renderer: Renderer,
+    pub integrator: IntegratorKind,
+    /// 为 `None` 时使用积分器的默认值。
+    pub max_depth: Option<u32>,
+    pub background: Rgb,
+    pub filter: FilterKind,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 512,
+            height: 512,
+            renderer: Renderer::default(),
+            integrator: IntegratorKind::Path,
+            max_depth: None,
+            background: Rgb::black(),
+            filter: FilterKind::Box,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn integrator(&self) -> Box<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::Path => {
+                let default = PathTracer::default();
+                Box::new(PathTracer {
+                    max_depth: self.max_depth.unwrap_or(default.max_depth),
+                    background: self.background,
+                    ..default
+                })
+            }
+            IntegratorKind::Whitted => Box::new(Whitted {
+                max_depth: self.max_depth.unwrap_or(Whitted::default().max_depth),
+                background: self.background,
+            }),
+        }
+    }
+
+    pub fn filter(&self) -> Box<dyn Filter> {
+        match self.filter {
+            FilterKind::Box => Box::new(BoxFilter::default()),
+            FilterKind::Tent => Box::new(TentFilter {
+                radius: vec2(1.0, 1.0),
+            }),
+            FilterKind::Gaussian => Box::new(GaussianFilter {
+                radius: vec2(1.5, 1.5),
+                alpha: 2.0,
+            }),
+            FilterKind::Mitchell => Box::new(MitchellFilter {
+                radius: vec2(2.0, 2.0),
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+        }
+    }
+
+    pub fn film(&self) -> Film {
+        Film::new(self.width, self.height, self.filter())
+    }
+}
+
+pub struct Scene {
+    pub camera: Camera,
+    pub lights: Vec<Box<dyn Light>>,
+    pub settings: RenderSettings,
+    shapes: Bvh<TexedShape>,
+}
+
+impl Scene {
+    pub fn new(camera: Camera, shapes: Vec<TexedShape>, lights: Vec<Box<dyn Light>>) -> Scene {
+        Scene {
+            camera,
+            lights,
+            settings: RenderSettings::default(),
+            shapes: Bvh::new(shapes),
+        }
+    }
+
+    /// 读取场景文件，格式见 `scenes/` 下的例子。模型文件的路径相对于场景文件所在的目录。
+    /// 出错时的信息带有文件名和行号。
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Scene> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::parse(&source, dir)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// 解析场景描述，`dir` 是模型文件路径的基准目录。
+    pub fn parse(source: &str, dir: &Path) -> io::Result<Scene> {
+        file::parse(source, dir)
+    }
+
+    pub fn shapes(&self) -> &[TexedShape] {
+        self.shapes.primitives()
+    }
+
+    /// 最近的交点以及与之相交的物体。
+    /// 射线带有微分时一并求出交点处的 `footprint`。
+    pub fn intersect(&self, ray: &Ray) -> Option<(&TexedShape, HitRecord)> {
+        let (shape, mut hit) = self.shapes.hit(ray, RAY_EPSILON, f32::INFINITY)?;
+        hit.compute_differentials(ray);
+        Some((shape, hit))
+    }
+
+    /// 射线在到达 `distance` 之前是否被挡住，用于阴影测试。
+    pub fn occluded(&self, ray: &Ray, distance: f32) -> bool {
+        Primitive::hit(&self.shapes, ray, RAY_EPSILON, distance - RAY_EPSILON).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camera::{CameraBuilder, ThinLens};
+    use rgb::Rgb;
+    use shapes::{pure_color_shape, RayBuilder, Sphere};
+
+    fn camera() -> Camera {
+        CameraBuilder {
+            lens: ThinLens {
+                radius: 0.0,
+                center: Vector3::zero(),
+                focal_length: 1.0,
+            },
+            at: Vector3::zero(),
+            target: -Vector3::unit_z(),
+            up: Vector3::unit_y(),
+            aspect_ratio: 1.0,
+            fov: f32::consts::PI / 4.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }.build()
+    }
+
+    #[test]
+    fn nearest_shape_wins() {
+        let far = pure_color_shape(
+            Rgb::new(1.0, 0.0, 0.0),
+            Sphere::new(vec3(0.0, 0.0, -10.0), 2.0),
+        );
+        let near = pure_color_shape(
+            Rgb::new(0.0, 1.0, 0.0),
+            Sphere::new(vec3(0.0, 0.0, -5.0), 1.0),
+        );
+        let scene = Scene::new(camera(), vec![far, near], Vec::new());
+
+        let ray = RayBuilder {
+            origin: Vector3::zero(),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let (shape, hit) = scene.intersect(&ray).unwrap();
+        assert_relative_eq!(hit.t, 4.0, epsilon = 1e-4);
+        let color = shape.texture.get_value(&hit.pos, &hit.uv);
+        assert_relative_eq!(color.g, 1.0);
+
+        assert!(scene.occluded(&ray, 100.0));
+        assert!(!scene.occluded(&ray, 3.0));
+    }
+}
@@ -0,0 +1,125 @@
+use math::*;
+use {HitRecord, Interval, Ray, Shape};
+use bvh::BBox;
+
+/// 轴对齐的长方体。每个面上的纹理坐标取另外两个轴按边长归一化后的值。
+#[derive(Copy, Clone)]
+pub struct AaBox {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl AaBox {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        AaBox { min, max }
+    }
+
+    /// slab 法求射线所在直线进入和离开的 `t`，以及分别穿过的是哪个轴的面。
+    fn slabs(&self, ray: &Ray) -> Option<((f32, usize), (f32, usize))> {
+        let (mut near, mut far) = (f32::NEG_INFINITY, f32::INFINITY);
+        let (mut near_axis, mut far_axis) = (0, 0);
+        for i in 0..3 {
+            let t0 = (self.min[i] - ray.origin[i]) * ray.dir_inv[i];
+            let t1 = (self.max[i] - ray.origin[i]) * ray.dir_inv[i];
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            //起点恰好在面上并且射线平行于它时是 NaN，跳过。
+            if t0 > near {
+                near = t0;
+                near_axis = i;
+            }
+            if t1 < far {
+                far = t1;
+                far_axis = i;
+            }
+        }
+        if near > far {
+            None
+        } else {
+            Some(((near, near_axis), (far, far_axis)))
+        }
+    }
+
+    /// 进入的面朝着射线来的方向，离开的面朝着射线去的方向，法线都是向外的。
+    fn face_hit(&self, ray: &Ray, t: f32, axis: usize, entering: bool) -> HitRecord {
+        let pos = ray.origin + ray.direction * t;
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let size = self.max - self.min;
+        let uv = vec2(
+            (pos[a] - self.min[a]) / size[a],
+            (pos[b] - self.min[b]) / size[b],
+        );
+        let mut normal = Vector3::zero();
+        normal[axis] = if ray.neg[axis] == entering { 1.0 } else { -1.0 };
+        let (mut dpdu, mut dpdv) = (Vector3::zero(), Vector3::zero());
+        dpdu[a] = size[a];
+        dpdv[b] = size[b];
+        HitRecord::new(t, pos, normal, uv, &dpdu, &dpdv)
+    }
+}
+
+impl Shape for AaBox {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let ((near, near_axis), (far, far_axis)) = self.slabs(ray)?;
+        if near >= tmin && near <= tmax {
+            Some(self.face_hit(ray, near, near_axis, true))
+        } else if far >= tmin && far <= tmax {
+            Some(self.face_hit(ray, far, far_axis, false))
+        } else {
+            None
+        }
+    }
+
+    fn intervals(&self, ray: &Ray, tmin: f32, tmax: f32) -> Vec<Interval> {
+        match self.slabs(ray) {
+            Some(((near, near_axis), (far, far_axis))) if near <= tmax && far >= tmin => {
+                vec![Interval {
+                    t0: near.max(tmin),
+                    t1: far.min(tmax),
+                    enter: if near >= tmin {
+                        Some(self.face_hit(ray, near, near_axis, true))
+                    } else {
+                        None
+                    },
+                    exit: if far <= tmax {
+                        Some(self.face_hit(ray, far, far_axis, false))
+                    } else {
+                        None
+                    },
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::new(self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    #[test]
+    fn outside_and_inside() {
+        let cube = AaBox::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+        let ray = RayBuilder {
+            origin: vec3(0.5, 0.0, 5.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = cube.hit(&ray, 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 4.0);
+        assert_relative_eq!(hit.normal, Vector3::unit_z());
+        //z 面上 u 沿 x，v 沿 y。
+        assert_relative_eq!(hit.uv, vec2(0.75, 0.5));
+        assert_relative_eq!(hit.tangent, Vector3::unit_x());
+
+        //tmin 越过了入射面，得到背面的交点，法线仍然朝外。
+        let hit = cube.hit(&ray, 5.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 6.0);
+        assert_relative_eq!(hit.normal, -Vector3::unit_z());
+        assert!(cube.hit(&ray, 0.0, 3.0).is_none());
+        assert!(cube.hit(&ray, 6.5, 100.0).is_none());
+    }
+}
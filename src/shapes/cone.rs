@@ -0,0 +1,98 @@
+use math::*;
+use {HitRecord, Ray, Shape};
+use bvh::BBox;
+use super::azimuth;
+use std::f32;
+
+/// 沿 y 轴、不带底面的圆锥侧面，`center` 是底面圆心，顶点在 `center` 上方 `height` 处。
+/// u 是绕 y 轴的角度，v 是沿 y 轴的高度比例。
+#[derive(Copy, Clone)]
+pub struct Cone {
+    pub center: Vector3,
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Cone {
+    pub fn new(center: Vector3, radius: f32, height: f32) -> Self {
+        Cone {
+            center,
+            radius,
+            height,
+        }
+    }
+}
+
+impl Shape for Cone {
+    // x^2 + z^2 - (k (h - y))^2 = 0, k = R / h, 0 <= y <= h
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let oy = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z + k2 * oy * d.y);
+        let c = o.x * o.x + o.z * o.z - k2 * oy * oy;
+        let (t0, t1) = solve_quadratic(a, b, c)?;
+        for &t in &[t0, t1] {
+            if t < tmin || t > tmax {
+                continue;
+            }
+            let p = o + d * t;
+            //方程还包含顶点上方的另一半锥面。
+            if p.y < 0.0 || p.y > self.height {
+                continue;
+            }
+            let phi = azimuth(&p);
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            //侧面法线与径向成固定角度，在顶点处也有定义。
+            let normal = vec3(cos_phi, k, sin_phi).normalize();
+            let radius = k * (self.height - p.y);
+            return Some(HitRecord::new(
+                t,
+                ray.origin + d * t,
+                normal,
+                vec2(phi / (2.0 * f32::consts::PI), p.y / self.height),
+                &(vec3(-sin_phi, 0.0, cos_phi) * (2.0 * f32::consts::PI * radius)),
+                &(vec3(-k * cos_phi, 1.0, -k * sin_phi) * self.height),
+            ));
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let r = self.radius;
+        BBox::new(
+            self.center + vec3(-r, 0.0, -r),
+            self.center + vec3(r, self.height, r),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    #[test]
+    fn side_normal() {
+        let cone = Cone::new(Vector3::zero(), 1.0, 2.0);
+        let ray = RayBuilder {
+            origin: vec3(0.0, 1.0, 5.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = cone.hit(&ray, 0.0, 100.0).unwrap();
+        //高度一半处半径是 0.5。
+        assert_relative_eq!(hit.t, 4.5, epsilon = 1e-5);
+        assert_relative_eq!(hit.normal, vec3(0.0, 0.5, 1.0).normalize(), epsilon = 1e-5);
+        assert_relative_eq!(hit.uv, vec2(0.25, 0.5), epsilon = 1e-5);
+
+        //顶点上方的另一半锥面不算。
+        let above = RayBuilder {
+            origin: vec3(0.0, 3.0, 5.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        assert!(cone.hit(&above, 0.0, 100.0).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use bvh::BBox;
+use {HitRecord, Interval, Ray, Shape};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// 从第一个形状中挖掉第二个。
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            CsgOp::Union => a || b,
+            CsgOp::Intersection => a && b,
+            CsgOp::Difference => a && !b,
+        }
+    }
+}
+
+/// 两个形状的布尔运算。两个形状都要是封闭的并且法线朝外，见 `Shape::intervals`。
+pub struct Csg {
+    pub op: CsgOp,
+    pub a: Box<dyn Shape>,
+    pub b: Box<dyn Shape>,
+}
+
+impl Csg {
+    pub fn new<A: Shape + 'static, B: Shape + 'static>(op: CsgOp, a: A, b: B) -> Self {
+        Csg {
+            op,
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+    }
+
+    pub fn union<A: Shape + 'static, B: Shape + 'static>(a: A, b: B) -> Self {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection<A: Shape + 'static, B: Shape + 'static>(a: A, b: B) -> Self {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference<A: Shape + 'static, B: Shape + 'static>(a: A, b: B) -> Self {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+/// 区间的一个端点。
+struct Event {
+    t: f32,
+    from_a: bool,
+    entering: bool,
+    hit: Option<HitRecord>,
+}
+
+fn events(intervals: Vec<Interval>, from_a: bool, out: &mut Vec<Event>) {
+    for interval in intervals {
+        //在查询范围起点就已经在内部的区间没有入口事件，由初始状态表示。
+        if interval.enter.is_some() {
+            out.push(Event {
+                t: interval.t0,
+                from_a,
+                entering: true,
+                hit: interval.enter,
+            });
+        }
+        out.push(Event {
+            t: interval.t1,
+            from_a,
+            entering: false,
+            hit: interval.exit,
+        });
+    }
+}
+
+/// 挖掉的形状的表面翻过来成为结果的表面，法线要朝向另一侧。
+fn flip(hit: &mut HitRecord) {
+    hit.normal = -hit.normal;
+    hit.shading_normal = -hit.shading_normal;
+    hit.bitangent = -hit.bitangent;
+}
+
+impl Shape for Csg {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        //结果的第一个边界：起点在内部时是出口，否则是入口。
+        self.intervals(ray, tmin, tmax)
+            .into_iter()
+            .filter_map(|interval| interval.enter.or(interval.exit))
+            .next()
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let (a, b) = (self.a.bounding_box(), self.b.bounding_box());
+        match self.op {
+            CsgOp::Union => a.union(&b),
+            CsgOp::Intersection => a.intersection(&b),
+            CsgOp::Difference => a,
+        }
+    }
+
+    /// 把两边的区间端点按 `t` 排序后扫描一遍，记录两个形状各自的内外状态。
+    fn intervals(&self, ray: &Ray, tmin: f32, tmax: f32) -> Vec<Interval> {
+        let (a, b) = (
+            self.a.intervals(ray, tmin, tmax),
+            self.b.intervals(ray, tmin, tmax),
+        );
+        let starts_inside = |v: &[Interval]| v.first().is_some_and(|i| i.enter.is_none());
+        let (mut in_a, mut in_b) = (starts_inside(&a), starts_inside(&b));
+
+        let mut list = Vec::with_capacity(2 * (a.len() + b.len()));
+        events(a, true, &mut list);
+        events(b, false, &mut list);
+        list.sort_by(|x, y| x.t.partial_cmp(&y.t).unwrap_or(::std::cmp::Ordering::Equal));
+
+        let mut result = Vec::new();
+        let mut inside = self.op.inside(in_a, in_b);
+        let mut open: Option<(f32, Option<HitRecord>)> =
+            if inside { Some((tmin, None)) } else { None };
+        for event in list {
+            if event.from_a {
+                in_a = event.entering;
+            } else {
+                in_b = event.entering;
+            }
+            let now = self.op.inside(in_a, in_b);
+            if now == inside {
+                continue;
+            }
+            inside = now;
+            let mut hit = event.hit;
+            if let Some(ref mut hit) = hit {
+                if self.op == CsgOp::Difference && !event.from_a {
+                    flip(hit);
+                }
+            }
+            match open.take() {
+                Some((t0, enter)) => result.push(Interval {
+                    t0,
+                    t1: event.t,
+                    enter,
+                    exit: hit,
+                }),
+                None => open = Some((event.t, hit)),
+            }
+        }
+        if let Some((t0, enter)) = open {
+            result.push(Interval {
+                t0,
+                t1: tmax,
+                enter,
+                exit: None,
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use shapes::{AaBox, Sphere, Transformed};
+    use RayBuilder;
+
+    fn ray_x(y: f32, z: f32) -> Ray {
+        RayBuilder {
+            origin: vec3(-5.0, y, z),
+            direction: Vector3::unit_x(),
+        }.build()
+    }
+
+    #[test]
+    fn lens() {
+        //两个球相交得到的双凸透镜，厚度为 1。
+        let lens = Csg::intersection(
+            Sphere::new(vec3(-1.5, 0.0, 0.0), 2.0),
+            Sphere::new(vec3(1.5, 0.0, 0.0), 2.0),
+        );
+        let intervals = lens.intervals(&ray_x(0.0, 0.0), 0.0, 100.0);
+        assert_eq!(intervals.len(), 1);
+        assert_relative_eq!(intervals[0].t0, 4.5, epsilon = 1e-5);
+        assert_relative_eq!(intervals[0].t1, 5.5, epsilon = 1e-5);
+
+        let hit = lens.hit(&ray_x(0.0, 0.0), 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.normal, -Vector3::unit_x(), epsilon = 1e-5);
+        //从内部出发打到出口。
+        let exit = lens.hit(&ray_x(0.0, 0.0), 5.0, 100.0).unwrap();
+        assert_relative_eq!(exit.t, 5.5, epsilon = 1e-5);
+        assert_relative_eq!(exit.normal, Vector3::unit_x(), epsilon = 1e-5);
+        assert!(lens.hit(&ray_x(1.9, 0.0), 0.0, 100.0).is_none());
+    }
+
+    #[test]
+    fn drilled_block() {
+        //沿 y 轴打了一个方孔的方块，孔壁的法线朝向孔的中心。
+        let block = AaBox::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+        let drill = AaBox::new(vec3(-0.5, -2.0, -0.5), vec3(0.5, 2.0, 0.5));
+        let part = Csg::difference(block, Csg::union(drill, Sphere::new(Vector3::zero(), 0.1)));
+
+        let intervals = part.intervals(&ray_x(0.0, 0.0), 0.0, 100.0);
+        assert_eq!(intervals.len(), 2);
+        assert_relative_eq!(intervals[0].t1, 4.5, epsilon = 1e-4);
+        assert_relative_eq!(intervals[1].t0, 5.5, epsilon = 1e-4);
+        let wall = intervals[0].exit.as_ref().unwrap();
+        assert_relative_eq!(wall.normal, Vector3::unit_x(), epsilon = 1e-4);
+        assert_relative_eq!(
+            wall.normal.cross(wall.tangent),
+            wall.bitangent,
+            epsilon = 1e-4
+        );
+        let wall = intervals[1].enter.as_ref().unwrap();
+        assert_relative_eq!(wall.normal, -Vector3::unit_x(), epsilon = 1e-4);
+
+        //孔外面的射线只穿过实心部分。
+        let solid = part.intervals(&ray_x(0.0, 0.8), 0.0, 100.0);
+        assert_eq!(solid.len(), 1);
+        assert_relative_eq!(solid[0].t1 - solid[0].t0, 2.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn transformed_operands() {
+        let block = AaBox::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+        //把长方体转到 z 轴方向，射线横穿这个孔。
+        let rotate = Affine::rotation(Quaternion::from_angle_x(Deg(90.0)));
+        let drill = AaBox::new(vec3(-0.5, -2.0, -0.5), vec3(0.5, 2.0, 0.5));
+        let drill = Transformed::new(drill, rotate);
+        let part = Csg::difference(block, drill);
+        assert_eq!(part.intervals(&ray_x(0.0, 0.0), 0.0, 100.0).len(), 2);
+        assert_eq!(part.intervals(&ray_x(0.7, 0.0), 0.0, 100.0).len(), 1);
+        let hit = part.hit(&ray_x(0.0, 0.0), 5.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 5.5, epsilon = 1e-4);
+        assert_relative_eq!(hit.normal, -Vector3::unit_x(), epsilon = 1e-4);
+    }
+}
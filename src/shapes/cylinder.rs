@@ -0,0 +1,98 @@
+use math::*;
+use {HitRecord, Ray, Shape};
+use bvh::BBox;
+use super::azimuth;
+use std::f32;
+
+/// 沿 y 轴、不带底面的圆柱侧面，`center` 是底面圆心。
+/// u 是绕 y 轴的角度，v 是沿 y 轴的高度比例。
+#[derive(Copy, Clone)]
+pub struct Cylinder {
+    pub center: Vector3,
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Cylinder {
+    pub fn new(center: Vector3, radius: f32, height: f32) -> Self {
+        Cylinder {
+            center,
+            radius,
+            height,
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    // x^2 + z^2 - R^2 = 0, 0 <= y <= h
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let o = ray.origin - self.center;
+        let d = ray.direction;
+        let a = d.x * d.x + d.z * d.z;
+        if a == 0.0 {
+            return None;
+        }
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        let (t0, t1) = solve_quadratic(a, b, c)?;
+        for &t in &[t0, t1] {
+            if t < tmin || t > tmax {
+                continue;
+            }
+            let p = o + d * t;
+            if p.y < 0.0 || p.y > self.height {
+                continue;
+            }
+            let phi = azimuth(&p);
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let normal = vec3(p.x, 0.0, p.z) / self.radius;
+            return Some(HitRecord::new(
+                t,
+                ray.origin + d * t,
+                normal,
+                vec2(phi / (2.0 * f32::consts::PI), p.y / self.height),
+                &(vec3(-sin_phi, 0.0, cos_phi) * (2.0 * f32::consts::PI * self.radius)),
+                &vec3(0.0, self.height, 0.0),
+            ));
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let r = self.radius;
+        BBox::new(
+            self.center + vec3(-r, 0.0, -r),
+            self.center + vec3(r, self.height, r),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    #[test]
+    fn side_and_open_ends() {
+        let cylinder = Cylinder::new(vec3(0.0, -1.0, 0.0), 1.0, 2.0);
+        let ray = RayBuilder {
+            origin: vec3(0.0, 0.5, 5.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = cylinder.hit(&ray, 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 4.0);
+        assert_relative_eq!(hit.normal, Vector3::unit_z());
+        assert_relative_eq!(hit.uv, vec2(0.25, 0.75));
+        //越过前面的交点后打到内壁，法线仍然朝外。
+        let hit = cylinder.hit(&ray, 4.5, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 6.0);
+        assert_relative_eq!(hit.normal, -Vector3::unit_z());
+
+        //从开口的一端穿过，不会碰到侧面。
+        let axial = RayBuilder {
+            origin: vec3(0.5, 5.0, 0.0),
+            direction: -Vector3::unit_y(),
+        }.build();
+        assert!(cylinder.hit(&axial, 0.0, 100.0).is_none());
+    }
+}
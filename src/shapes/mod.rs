@@ -1,16 +1,53 @@
-use math::{Matrix, Vector3, Transformation, Transform};
-use super::texture::{PureColorTexture, Texture};
+use math::{coordinate_system, Affine, AnimatedTransform, InnerSpace, Vector2, Vector3, Zero};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use super::texture::{Footprint, PureColorTexture, Texture};
+use material::{Lambertian, Material};
+use bvh::{BBox, Primitive};
 use rgb::Rgb;
 
 pub mod triangle;
 pub mod sphere;
+pub mod plane;
+pub mod aabox;
+pub mod cylinder;
+pub mod cone;
+pub mod torus;
+pub mod sdf;
+pub mod csg;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ray {
     pub origin: Vector3,
     pub direction: Vector3,
     pub dir_inv: Vector3,
     pub neg: [bool; 3],
+    /// 射线所处的时刻，用于运动的形状。
+    pub time: f32,
+    /// 相邻像素的射线，用来估计交点处纹理的覆盖范围。
+    pub differentials: Option<RayDifferential>,
+}
+
+/// 沿像平面 x、y 方向各偏移一个像素后得到的两条射线。
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferential {
+    pub rx_origin: Vector3,
+    pub rx_direction: Vector3,
+    pub ry_origin: Vector3,
+    pub ry_direction: Vector3,
+}
+
+impl Ray {
+    /// 把相邻射线与主射线的偏移乘以 `s`，每个像素取多个样本时相邻样本更近。
+    pub fn scale_differentials(&mut self, s: f32) {
+        let (o, d) = (self.origin, self.direction);
+        if let Some(ref mut r) = self.differentials {
+            r.rx_origin = o + (r.rx_origin - o) * s;
+            r.rx_direction = d + (r.rx_direction - d) * s;
+            r.ry_origin = o + (r.ry_origin - o) * s;
+            r.ry_direction = d + (r.ry_direction - d) * s;
+        }
+    }
 }
 
 pub struct RayBuilder {
@@ -20,6 +57,10 @@ pub struct RayBuilder {
 
 impl RayBuilder {
     pub fn build(&self) -> Ray {
+        self.build_at(0.0)
+    }
+
+    pub fn build_at(&self, time: f32) -> Ray {
         Ray {
             origin: self.origin,
             direction: self.direction,
@@ -29,6 +70,8 @@ impl RayBuilder {
                 self.direction.y < 0.0,
                 self.direction.z < 0.0,
             ],
+            time,
+            differentials: None,
         }
     }
 }
@@ -38,33 +81,513 @@ impl RayBuilder {
 pub struct HitRecord {
     pub t: f32,
     pub pos: Vector3,
+    /// 几何法线。
     pub normal: Vector3,
+    /// 着色用的法线，网格上由顶点法线插值得到，其他形状与 `normal` 相同。
+    pub shading_normal: Vector3,
+    pub uv: Vector2,
+    /// 三角形上的重心坐标，依次是三个顶点的权重；其他形状为零。
+    pub barycentric: Vector3,
+    /// 与 `shading_normal` 正交、沿 u 增大方向的单位切线。
+    pub tangent: Vector3,
+    /// `shading_normal.cross(tangent)`
+    pub bitangent: Vector3,
+    /// 位置对纹理坐标的偏导数，没有纹理坐标的形状为零。
+    pub dpdu: Vector3,
+    pub dpdv: Vector3,
+    /// 由射线微分得到，射线没有微分时为零，见 `compute_differentials`。
+    pub footprint: Footprint,
+}
+
+impl HitRecord {
+    /// 解析形状用的交点：着色法线就是几何法线，切线由 `dpdu` 得到。
+    pub fn new(
+        t: f32,
+        pos: Vector3,
+        normal: Vector3,
+        uv: Vector2,
+        dpdu: &Vector3,
+        dpdv: &Vector3,
+    ) -> HitRecord {
+        let mut hit = HitRecord {
+            t,
+            pos,
+            normal,
+            shading_normal: normal,
+            uv,
+            barycentric: Vector3::zero(),
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            dpdu: *dpdu,
+            dpdv: *dpdv,
+            footprint: Footprint::zero(),
+        };
+        hit.set_tangents(dpdu);
+        hit
+    }
+
+    /// 求出相邻射线与交点切平面的交点，得到位置和纹理坐标随像素的变化。
+    /// `ray` 要和交点在同一个空间里，`Scene::intersect` 在世界空间里调用。
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        let r = match ray.differentials {
+            Some(ref r) => r,
+            None => return,
+        };
+        let n = self.normal;
+        let on_plane = |o: Vector3, d: Vector3| {
+            let cos = n.dot(d);
+            if cos == 0.0 {
+                None
+            } else {
+                Some(o + d * (n.dot(self.pos - o) / cos) - self.pos)
+            }
+        };
+        let (dpdx, dpdy) = match (
+            on_plane(r.rx_origin, r.rx_direction),
+            on_plane(r.ry_origin, r.ry_direction),
+        ) {
+            (Some(x), Some(y)) => (x, y),
+            _ => return,
+        };
+
+        //dp = dpdu * du + dpdv * dv 是超定的，丢掉法线分量最大的那一维再解。
+        let (a, b) = if n.x.abs() > n.y.abs() && n.x.abs() > n.z.abs() {
+            (1, 2)
+        } else if n.y.abs() > n.z.abs() {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let (du, dv) = (self.dpdu, self.dpdv);
+        let det = du[a] * dv[b] - dv[a] * du[b];
+        let solve = |dp: Vector3| {
+            if det.abs() < 1e-12 {
+                Vector2::zero()
+            } else {
+                Vector2::new(
+                    (dv[b] * dp[a] - dv[a] * dp[b]) / det,
+                    (du[a] * dp[b] - du[b] * dp[a]) / det,
+                )
+            }
+        };
+        self.footprint = Footprint {
+            dpdx,
+            dpdy,
+            duvdx: solve(dpdx),
+            duvdy: solve(dpdy),
+        };
+    }
+
+    /// 由 `dpdu` 得到切线和副切线，`dpdu` 退化时任取一组正交基。
+    pub fn set_tangents(&mut self, dpdu: &Vector3) {
+        let n = self.shading_normal;
+        let t = dpdu - n * n.dot(*dpdu);
+        self.tangent = if t.magnitude2() > 1e-12 {
+            t.normalize()
+        } else {
+            coordinate_system(&n).0
+        };
+        self.bitangent = n.cross(self.tangent);
+    }
 }
 
-pub trait Shape {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32, transform: &Matrix) -> Option<HitRecord>;
+/// 绕 y 轴的角度，从 x 轴转向 z 轴，范围 [0, 2pi)，与球面的 u 一致。
+fn azimuth(p: &Vector3) -> f32 {
+    let phi = p.z.atan2(p.x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+/// 射线位于形状内部的一段 `[t0, t1]`。
+#[derive(Debug)]
+pub struct Interval {
+    pub t0: f32,
+    pub t1: f32,
+    /// 进入处的交点，`None` 表示在查询范围的起点就已经在内部。
+    pub enter: Option<HitRecord>,
+    /// 离开处的交点，`None` 表示到查询范围的终点仍在内部。
+    pub exit: Option<HitRecord>,
+}
+
+/// 同一条射线上紧接着 `t` 之后继续查找的位置。
+fn step_past(t: f32) -> f32 {
+    t + 1e-5 * t.abs().max(1.0)
+}
+
+/// 物体空间中的几何形状，位置和朝向由 `TexedShape::transform` 决定。
+pub trait Shape: Send + Sync {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord>;
+    fn bounding_box(&self) -> BBox;
+
+    /// 射线在 `[tmin, tmax]` 内位于形状内部的各段，按 `t` 排序且互不重叠。
+    /// 默认实现沿射线反复调用 `hit`，由法线与射线方向判断是进入还是离开，
+    /// 所以要求法线朝外；平面这样不封闭的形状会被当作法线背面的半空间。
+    fn intervals(&self, ray: &Ray, tmin: f32, tmax: f32) -> Vec<Interval> {
+        let mut intervals = Vec::new();
+        let mut open: Option<(f32, Option<HitRecord>)> = None;
+        let mut t = tmin;
+        //防止法线方向不一致时来回打转。
+        for _ in 0..64 {
+            let hit = match self.hit(ray, t, tmax) {
+                Some(hit) => hit,
+                None => break,
+            };
+            t = step_past(hit.t);
+            let entering = hit.normal.dot(ray.direction) < 0.0;
+            match (open.take(), entering) {
+                (None, true) => open = Some((hit.t, Some(hit))),
+                (None, false) => {
+                    //第一个交点是出口，说明起点在内部。
+                    if intervals.is_empty() {
+                        intervals.push(Interval {
+                            t0: tmin,
+                            t1: hit.t,
+                            enter: None,
+                            exit: Some(hit),
+                        });
+                    }
+                }
+                (Some((t0, enter)), false) => intervals.push(Interval {
+                    t0,
+                    t1: hit.t,
+                    enter,
+                    exit: Some(hit),
+                }),
+                //连续两次进入，保留先前的入口。
+                (Some(first), true) => open = Some(first),
+            }
+        }
+        if let Some((t0, enter)) = open {
+            intervals.push(Interval {
+                t0,
+                t1: tmax,
+                enter,
+                exit: None,
+            });
+        }
+        intervals
+    }
+}
+
+/// 多个 `TexedShape` 可以共享同一个形状，各自用不同的变换摆放。
+impl<S: Shape + ?Sized> Shape for Arc<S> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        (**self).hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        (**self).bounding_box()
+    }
+
+    fn intervals(&self, ray: &Ray, tmin: f32, tmax: f32) -> Vec<Interval> {
+        (**self).intervals(ray, tmin, tmax)
+    }
+}
+
+/// 变换到 `transform` 的物体空间中的射线。
+fn local_ray(transform: &Affine, ray: &Ray) -> Ray {
+    let inverse = transform.inverse();
+    RayBuilder {
+        origin: inverse.point(&ray.origin),
+        direction: inverse.vector(&ray.direction),
+    }.build_at(ray.time)
+}
+
+/// 把物体空间中的交点变换回 `ray` 所在的空间。
+fn hit_to_world(transform: &Affine, ray: &Ray, hit: &mut HitRecord) {
+    hit.pos = ray.origin + ray.direction * hit.t;
+    hit.normal = transform.normal(&hit.normal).normalize();
+    hit.shading_normal = transform.normal(&hit.shading_normal).normalize();
+    let tangent = transform.vector(&hit.tangent);
+    hit.set_tangents(&tangent);
+    hit.dpdu = transform.vector(&hit.dpdu);
+    hit.dpdv = transform.vector(&hit.dpdv);
+}
+
+/// 带变换的形状，用来在 `Csg` 中摆放各个部分。
+pub struct Transformed<S> {
+    pub shape: S,
+    /// 从 `shape` 的物体空间到外层空间的变换。
+    pub transform: Affine,
+}
+
+impl<S: Shape> Transformed<S> {
+    pub fn new(shape: S, transform: Affine) -> Self {
+        Transformed { shape, transform }
+    }
+}
+
+impl<S: Shape> Shape for Transformed<S> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let local = local_ray(&self.transform, ray);
+        let mut hit = self.shape.hit(&local, tmin, tmax)?;
+        hit_to_world(&self.transform, ray, &mut hit);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.shape.bounding_box().transform(&self.transform)
+    }
+
+    fn intervals(&self, ray: &Ray, tmin: f32, tmax: f32) -> Vec<Interval> {
+        let local = local_ray(&self.transform, ray);
+        let mut intervals = self.shape.intervals(&local, tmin, tmax);
+        for interval in &mut intervals {
+            for hit in interval.enter.iter_mut().chain(interval.exit.iter_mut()) {
+                hit_to_world(&self.transform, ray, hit);
+            }
+        }
+        intervals
+    }
 }
 
 //optimization: isDirty?
 pub struct TexedShape {
-    pub texture: Box<Texture>,
-    pub shape: Box<Shape>,
-    pub transform: Transformation
+    pub texture: Box<dyn Texture>,
+    pub material: Box<dyn Material>,
+    pub shape: Box<dyn Shape>,
+    /// 从物体空间到世界空间的变换。
+    pub transform: Affine,
+    /// 在 `transform` 之后按射线的时刻施加的运动，`None` 表示静止。
+    pub motion: Option<AnimatedTransform>,
 }
 
 impl TexedShape {
+    /// 把射线变换到物体空间求交，再把交点变换回世界空间。
+    /// 变换不要求保持长度，所以 `t` 在两个空间里是同一个值。
     pub fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
-        self.shape.hit(ray, tmin, tmax, &self.transform.into())
+        let t = &self.transform_at(ray.time);
+        let mut hit = self.shape.hit(&local_ray(t, ray), tmin, tmax)?;
+        hit_to_world(t, ray, &mut hit);
+        Some(hit)
+    }
+
+    /// `time` 时刻从物体空间到世界空间的变换。
+    pub fn transform_at(&self, time: f32) -> Affine {
+        match self.motion {
+            Some(ref motion) => self.transform.then(&motion.affine_at(time)),
+            None => self.transform,
+        }
+    }
+
+    /// 运动的形状返回整个运动过程扫过的范围。
+    pub fn bounding_box(&self) -> BBox {
+        let bbox = self.shape.bounding_box().transform(&self.transform);
+        match self.motion {
+            Some(ref motion) => bbox.sweep(motion),
+            None => bbox,
+        }
+    }
+}
+
+impl Primitive for TexedShape {
+    fn bounding_box(&self) -> BBox {
+        TexedShape::bounding_box(self)
+    }
+
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        TexedShape::hit(self, ray, tmin, tmax)
     }
 }
 
 pub fn pure_color_shape<T: Shape + 'static>(color: Rgb, shape: T) -> TexedShape {
     TexedShape {
         texture: Box::new(PureColorTexture { color }),
+        material: Box::new(Lambertian),
         shape: Box::new(shape),
-        transform: Transformation::one(),
+        transform: Affine::identity(),
+        motion: None,
     }
 }
 
 pub use triangle::*;
-pub use sphere::*;
\ No newline at end of file
+pub use sphere::*;
+pub use plane::*;
+pub use aabox::*;
+pub use cylinder::*;
+pub use cone::*;
+pub use torus::*;
+pub use sdf::{Sdf, SdfShape};
+pub use csg::{Csg, CsgOp};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+
+    #[test]
+    fn non_uniform_scale() {
+        let mut ellipsoid = pure_color_shape(Rgb::white(), Sphere::new(Vector3::zero(), 1.0));
+        ellipsoid.transform = Affine::scale(2.0, 1.0, 1.0).unwrap();
+        let bbox = ellipsoid.bounding_box();
+        assert_relative_eq!(bbox.max, vec3(2.0, 1.0, 1.0));
+
+        let x = 2.0f32.sqrt();
+        let ray = RayBuilder {
+            origin: vec3(x, 5.0, 0.0),
+            direction: -Vector3::unit_y(),
+        }.build();
+        let hit = ellipsoid.hit(&ray, 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.pos, vec3(x, 0.5f32.sqrt(), 0.0), epsilon = 1e-5);
+        //椭球面 x^2 / 4 + y^2 = 1 的法线与 (x / 4, y) 同向。
+        let expected = vec3(x / 4.0, 0.5f32.sqrt(), 0.0).normalize();
+        assert_relative_eq!(hit.normal, expected, epsilon = 1e-5);
+        assert_relative_eq!(hit.tangent.dot(hit.normal), 0.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn shared_shape_instances() {
+        let shape: Arc<dyn Shape> = Arc::new(Triangle::new(
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ));
+        let instance = |x: f32| TexedShape {
+            texture: Box::new(PureColorTexture { color: Rgb::white() }),
+            material: Box::new(Lambertian),
+            shape: Box::new(shape.clone()),
+            transform: Affine::translation(vec3(x, 0.0, 0.0)),
+            motion: None,
+        };
+        for &x in &[0.0, 10.0] {
+            let ray = RayBuilder {
+                origin: vec3(x + 0.25, 0.25, 1.0),
+                direction: -Vector3::unit_z(),
+            }.build();
+            assert!(instance(x).hit(&ray, 0.0, 10.0).is_some());
+            assert!(instance(x + 5.0).hit(&ray, 0.0, 10.0).is_none());
+        }
+    }
+
+    #[test]
+    fn moving_shape() {
+        let mut ball = pure_color_shape(Rgb::white(), Sphere::new(Vector3::zero(), 1.0));
+        let at = |x: f32| Transformation {
+            disp: vec3(x, 0.0, 0.0),
+            ..Transformation::one()
+        };
+        ball.motion = Some(AnimatedTransform::new(vec![(1.0, at(10.0)), (0.0, at(0.0))]));
+        let bbox = ball.bounding_box();
+        assert_relative_eq!(bbox.min, vec3(-1.0, -1.0, -1.0), epsilon = 1e-5);
+        assert_relative_eq!(bbox.max, vec3(11.0, 1.0, 1.0), epsilon = 1e-5);
+
+        let ray = |x: f32, time: f32| {
+            RayBuilder {
+                origin: vec3(x, 0.0, 5.0),
+                direction: -Vector3::unit_z(),
+            }.build_at(time)
+        };
+        assert!(ball.hit(&ray(5.0, 0.5), 0.0, 10.0).is_some());
+        assert!(ball.hit(&ray(5.0, 0.0), 0.0, 10.0).is_none());
+        assert!(ball.hit(&ray(5.0, 1.0), 0.0, 10.0).is_none());
+        //时间范围之外保持在端点。
+        assert!(ball.hit(&ray(10.0, 2.0), 0.0, 10.0).is_some());
+    }
+
+    #[test]
+    fn partial_derivatives() {
+        //相邻两条射线的交点之差应该等于 dpdu du + dpdv dv。
+        let shapes: Vec<(Box<dyn Shape>, Vector3)> = vec![
+            (Box::new(Sphere::new(Vector3::zero(), 2.0)), vec3(0.3, 0.4, 5.0)),
+            (
+                Box::new(Disk::new(Vector3::zero(), Vector3::unit_z(), 2.0)),
+                vec3(0.7, -0.4, 5.0),
+            ),
+            (
+                Box::new(Rect::new(Vector3::zero(), vec3(2.0, 0.0, 0.0), vec3(0.5, 1.0, 0.0))),
+                vec3(0.9, 0.3, 5.0),
+            ),
+            (
+                Box::new(AaBox::new(vec3(-1.0, -2.0, -1.0), vec3(1.0, 1.0, 3.0))),
+                vec3(0.2, -0.3, 5.0),
+            ),
+            (
+                Box::new(Cylinder::new(vec3(0.0, -1.0, 0.0), 1.5, 2.0)),
+                vec3(0.4, 0.2, 5.0),
+            ),
+            (Box::new(Cone::new(vec3(0.0, -1.0, 0.0), 1.5, 2.0)), vec3(0.4, 0.2, 5.0)),
+            (Box::new(Torus::new(Vector3::zero(), 2.0, 0.5)), vec3(0.3, 0.2, 5.0)),
+            (
+                Box::new(Triangle::new(
+                    vec3(-1.0, -1.0, 0.0),
+                    vec3(2.0, -1.0, 0.5),
+                    vec3(0.0, 2.0, 0.0),
+                )),
+                vec3(0.2, 0.1, 5.0),
+            ),
+        ];
+        for (shape, origin) in &shapes {
+            let hit = |dx: f32, dy: f32| {
+                let ray = RayBuilder {
+                    origin: origin + vec3(dx, dy, 0.0),
+                    direction: -Vector3::unit_z(),
+                }.build();
+                shape.hit(&ray, 0.0, 100.0).unwrap()
+            };
+            let base = hit(0.0, 0.0);
+            for &(dx, dy) in &[(1e-3, 0.0), (0.0, 1e-3)] {
+                let next = hit(dx, dy);
+                let duv = next.uv - base.uv;
+                let expected = base.dpdu * duv.x + base.dpdv * duv.y;
+                assert_relative_eq!(next.pos - base.pos, expected, epsilon = 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn footprint() {
+        let mut rect = pure_color_shape(
+            Rgb::white(),
+            Rect::new(vec3(-1.0, -1.0, -1.0), vec3(2.0, 0.0, 0.0), vec3(0.0, 2.0, 0.0)),
+        );
+        let mut ray = RayBuilder {
+            origin: Vector3::zero(),
+            direction: -Vector3::unit_z(),
+        }.build();
+        ray.differentials = Some(RayDifferential {
+            rx_origin: vec3(0.01, 0.0, 0.0),
+            rx_direction: -Vector3::unit_z(),
+            ry_origin: Vector3::zero(),
+            ry_direction: vec3(0.0, 0.01, -1.0),
+        });
+        let mut hit = rect.hit(&ray, 0.0, 10.0).unwrap();
+        hit.compute_differentials(&ray);
+        assert_relative_eq!(hit.footprint.dpdx, vec3(0.01, 0.0, 0.0), epsilon = 1e-6);
+        assert_relative_eq!(hit.footprint.duvdx, vec2(0.005, 0.0), epsilon = 1e-6);
+        assert_relative_eq!(hit.footprint.duvdy, vec2(0.0, 0.005), epsilon = 1e-6);
+
+        //拉宽之后同样的偏移在纹理坐标上变小。
+        rect.transform = Affine::scale(2.0, 1.0, 1.0).unwrap();
+        let mut hit = rect.hit(&ray, 0.0, 10.0).unwrap();
+        hit.compute_differentials(&ray);
+        assert_relative_eq!(hit.footprint.duvdx, vec2(0.0025, 0.0), epsilon = 1e-6);
+
+        ray.differentials = None;
+        let mut hit = rect.hit(&ray, 0.0, 10.0).unwrap();
+        hit.compute_differentials(&ray);
+        assert!(hit.footprint.is_zero());
+    }
+
+    #[test]
+    fn rotating_bounds() {
+        let mut rod = pure_color_shape(
+            Rgb::white(),
+            Triangle::new(vec3(2.0, 0.0, 0.0), vec3(2.0, 0.1, 0.0), vec3(1.9, 0.0, 0.0)),
+        );
+        let turn = |deg: f32| Transformation {
+            rot: Quaternion::from_angle_y(Deg(deg)),
+            ..Transformation::one()
+        };
+        rod.motion = Some(AnimatedTransform::new(vec![(0.0, turn(0.0)), (1.0, turn(120.0))]));
+        let bbox = rod.bounding_box();
+        //转到 90 度时三角形位于 z = -2 附近，只取两端会漏掉这一段。
+        for i in 0..=32 {
+            let p = rod.transform_at(i as f32 / 32.0).point(&vec3(2.0, 0.0, 0.0));
+            assert!(p.z >= bbox.min.z && p.z <= bbox.max.z);
+            assert!(p.x >= bbox.min.x && p.x <= bbox.max.x);
+        }
+    }
+}
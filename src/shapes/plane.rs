@@ -0,0 +1,222 @@
+use math::*;
+use {HitRecord, Ray, Shape};
+use bvh::BBox;
+use std::f32;
+
+/// 射线与过 `point`、法线为 `normal` 的平面的交点参数，平行时返回 `None`。
+fn plane_t(point: &Vector3, normal: &Vector3, ray: &Ray, tmin: f32, tmax: f32) -> Option<f32> {
+    let denom = normal.dot(ray.direction);
+    if denom == 0.0 {
+        return None;
+    }
+    let t = normal.dot(point - ray.origin) / denom;
+    if t < tmin || t > tmax {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+/// 无限大的平面，纹理坐标是平面内以 `point` 为原点的坐标。
+#[derive(Copy, Clone)]
+pub struct Plane {
+    pub point: Vector3,
+    pub normal: Vector3,
+}
+
+impl Plane {
+    pub fn new(point: Vector3, normal: Vector3) -> Self {
+        Plane {
+            point,
+            normal: normal.normalize(),
+        }
+    }
+}
+
+impl Shape for Plane {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let t = plane_t(&self.point, &self.normal, ray, tmin, tmax)?;
+        let pos = ray.origin + ray.direction * t;
+        let (s, b) = coordinate_system(&self.normal);
+        let d = pos - self.point;
+        Some(HitRecord::new(
+            t,
+            pos,
+            self.normal,
+            vec2(d.dot(s), d.dot(b)),
+            &s,
+            &b,
+        ))
+    }
+
+    /// 法线沿坐标轴时在该轴上是扁的，否则是整个空间。
+    fn bounding_box(&self) -> BBox {
+        let mut bbox = BBox::infinite();
+        for i in 0..3 {
+            if self.normal[i].abs() == 1.0 {
+                bbox.min[i] = self.point[i];
+                bbox.max[i] = self.point[i];
+            }
+        }
+        bbox
+    }
+}
+
+/// 圆盘，u 是绕法线的角度，v 是到圆心的距离与半径之比。
+#[derive(Copy, Clone)]
+pub struct Disk {
+    pub center: Vector3,
+    pub normal: Vector3,
+    pub radius: f32,
+}
+
+impl Disk {
+    pub fn new(center: Vector3, normal: Vector3, radius: f32) -> Self {
+        Disk {
+            center,
+            normal: normal.normalize(),
+            radius,
+        }
+    }
+}
+
+impl Shape for Disk {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let t = plane_t(&self.center, &self.normal, ray, tmin, tmax)?;
+        let pos = ray.origin + ray.direction * t;
+        let d = pos - self.center;
+        let dist2 = d.magnitude2();
+        if dist2 > self.radius * self.radius {
+            return None;
+        }
+        let (s, b) = coordinate_system(&self.normal);
+        let mut phi = d.dot(b).atan2(d.dot(s));
+        if phi < 0.0 {
+            phi += 2.0 * f32::consts::PI;
+        }
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let dist = dist2.sqrt();
+        Some(HitRecord::new(
+            t,
+            pos,
+            self.normal,
+            vec2(phi / (2.0 * f32::consts::PI), dist / self.radius),
+            &((b * cos_phi - s * sin_phi) * (2.0 * f32::consts::PI * dist)),
+            &((s * cos_phi + b * sin_phi) * self.radius),
+        ))
+    }
+
+    fn bounding_box(&self) -> BBox {
+        //圆盘在每个轴上的半宽是 r * sqrt(1 - n_i^2)。
+        let n = self.normal;
+        let extent = vec3(1.0 - n.x * n.x, 1.0 - n.y * n.y, 1.0 - n.z * n.z);
+        let extent = vec3(
+            extent.x.max(0.0).sqrt(),
+            extent.y.max(0.0).sqrt(),
+            extent.z.max(0.0).sqrt(),
+        ) * self.radius;
+        BBox::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// 以 `corner` 为顶点、两边为 `edge0` 和 `edge1` 的平行四边形，两边方向分别是 u 和 v。
+#[derive(Copy, Clone)]
+pub struct Rect {
+    pub corner: Vector3,
+    pub edge0: Vector3,
+    pub edge1: Vector3,
+}
+
+impl Rect {
+    pub fn new(corner: Vector3, edge0: Vector3, edge1: Vector3) -> Self {
+        Rect {
+            corner,
+            edge0,
+            edge1,
+        }
+    }
+}
+
+impl Shape for Rect {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let normal = self.edge0.cross(self.edge1);
+        let area2 = normal.magnitude2();
+        if area2 == 0.0 {
+            return None;
+        }
+        let t = plane_t(&self.corner, &normal, ray, tmin, tmax)?;
+        let pos = ray.origin + ray.direction * t;
+        //平面内的坐标：d = u * e0 + v * e1。
+        let d = pos - self.corner;
+        let u = d.cross(self.edge1).dot(normal) / area2;
+        let v = self.edge0.cross(d).dot(normal) / area2;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        Some(HitRecord::new(
+            t,
+            pos,
+            normal / area2.sqrt(),
+            vec2(u, v),
+            &self.edge0,
+            &self.edge1,
+        ))
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let c = self.corner;
+        BBox::from_point(c)
+            .union_point(&(c + self.edge0))
+            .union_point(&(c + self.edge1))
+            .union_point(&(c + self.edge0 + self.edge1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bvh::Bvh;
+    use shapes::{pure_color_shape, Sphere};
+    use rgb::Rgb;
+    use RayBuilder;
+
+    fn down(x: f32, z: f32) -> Ray {
+        RayBuilder {
+            origin: vec3(x, 2.0, z),
+            direction: -Vector3::unit_y(),
+        }.build()
+    }
+
+    #[test]
+    fn planar_shapes() {
+        let plane = Plane::new(Vector3::zero(), Vector3::unit_y());
+        let hit = plane.hit(&down(3.0, -4.0), 0.0, 10.0).unwrap();
+        assert_relative_eq!(hit.t, 2.0);
+        assert_relative_eq!(hit.normal, Vector3::unit_y());
+        assert!(plane.hit(&down(3.0, -4.0), 0.0, 1.0).is_none());
+
+        let disk = Disk::new(Vector3::zero(), Vector3::unit_y(), 1.0);
+        let hit = disk.hit(&down(0.5, 0.0), 0.0, 10.0).unwrap();
+        assert_relative_eq!(hit.uv.y, 0.5, epsilon = 1e-6);
+        assert_relative_eq!(hit.tangent.dot(hit.normal), 0.0);
+        assert!(disk.hit(&down(0.8, 0.8), 0.0, 10.0).is_none());
+        assert_relative_eq!(disk.bounding_box().max, vec3(1.0, 0.0, 1.0));
+
+        let rect = Rect::new(Vector3::zero(), vec3(2.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0));
+        let hit = rect.hit(&down(0.5, 0.25), 0.0, 10.0).unwrap();
+        assert_relative_eq!(hit.uv, vec2(0.25, 0.25), epsilon = 1e-6);
+        assert_relative_eq!(hit.normal, -Vector3::unit_y());
+        assert_relative_eq!(hit.tangent, Vector3::unit_x());
+        assert!(rect.hit(&down(2.5, 0.5), 0.0, 10.0).is_none());
+    }
+
+    #[test]
+    fn infinite_plane_in_bvh() {
+        let ground = pure_color_shape(Rgb::white(), Plane::new(Vector3::zero(), Vector3::unit_y()));
+        assert!(!ground.bounding_box().is_finite());
+        let ball = pure_color_shape(Rgb::white(), Sphere::new(vec3(0.0, 1.0, 0.0), 0.5));
+        let bvh = Bvh::new(vec![ground, ball]);
+        assert_relative_eq!(bvh.hit(&down(0.0, 0.0), 0.0, 10.0).unwrap().1.t, 0.5);
+        assert_relative_eq!(bvh.hit(&down(100.0, 0.0), 0.0, 10.0).unwrap().1.t, 2.0);
+    }
+}
@@ -0,0 +1,464 @@
+//! 用球面追踪求交的隐式曲面。
+//!
+//! 形状由有向距离函数描述，可以用 `union`、`intersection`、`difference`、`smooth_union`
+//! 和 `displace` 组合，最后放进 `SdfShape` 当作普通的 `Shape` 使用。
+
+use bvh::BBox;
+use math::*;
+use noise::perlin_noise;
+use {HitRecord, Ray, Shape};
+
+/// 有向距离函数，表面外为正、内部为负。
+/// 返回值的绝对值除以 `lipschitz` 后不能超过到表面的真实距离，否则追踪会越过表面。
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: &Vector3) -> f32;
+
+    /// 包含整个表面的包围盒，追踪只在盒内进行。
+    fn bounding_box(&self) -> BBox;
+
+    /// 距离函数梯度大小的上界，精确的距离函数为 1。
+    fn lipschitz(&self) -> f32 {
+        1.0
+    }
+
+    fn union<B: Sdf>(self, other: B) -> Union<Self, B>
+    where
+        Self: Sized,
+    {
+        Union(self, other)
+    }
+
+    fn intersection<B: Sdf>(self, other: B) -> Intersection<Self, B>
+    where
+        Self: Sized,
+    {
+        Intersection(self, other)
+    }
+
+    /// 从 `self` 中挖掉 `other`。
+    fn difference<B: Sdf>(self, other: B) -> Difference<Self, B>
+    where
+        Self: Sized,
+    {
+        Difference(self, other)
+    }
+
+    /// 两个形状在距离 `k` 以内平滑地融合在一起。
+    fn smooth_union<B: Sdf>(self, other: B, k: f32) -> SmoothUnion<Self, B>
+    where
+        Self: Sized,
+    {
+        SmoothUnion {
+            a: self,
+            b: other,
+            k,
+        }
+    }
+
+    /// 沿法线方向加上 `amplitude` 倍的 Perlin 噪声，`frequency` 是噪声在空间中的频率。
+    fn displace(self, amplitude: f32, frequency: f32) -> Displace<Self>
+    where
+        Self: Sized,
+    {
+        Displace {
+            sdf: self,
+            amplitude,
+            frequency,
+        }
+    }
+}
+
+impl Sdf for Box<dyn Sdf> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        (**self).distance(p)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        (**self).bounding_box()
+    }
+
+    fn lipschitz(&self) -> f32 {
+        (**self).lipschitz()
+    }
+}
+
+fn expand(bbox: &BBox, amount: f32) -> BBox {
+    let pad = vec3(amount, amount, amount);
+    BBox::new(bbox.min - pad, bbox.max + pad)
+}
+
+pub struct SdfSphere {
+    pub center: Vector3,
+    pub radius: f32,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Vector3) -> f32 {
+        (p - self.center).magnitude() - self.radius
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let r = vec3(self.radius, self.radius, self.radius);
+        BBox::new(self.center - r, self.center + r)
+    }
+}
+
+/// 轴对齐的长方体，`rounding` 是棱角的圆角半径，包含在 `half_size` 之内。
+pub struct SdfBox {
+    pub center: Vector3,
+    pub half_size: Vector3,
+    pub rounding: f32,
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: &Vector3) -> f32 {
+        let r = self.rounding;
+        let d = p - self.center;
+        let q = vec3(d.x.abs(), d.y.abs(), d.z.abs()) - self.half_size + vec3(r, r, r);
+        let outside = vec3(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        outside + inside - r
+    }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::new(self.center - self.half_size, self.center + self.half_size)
+    }
+}
+
+/// 绕 y 轴的圆环。
+pub struct SdfTorus {
+    pub center: Vector3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Vector3) -> f32 {
+        let d = p - self.center;
+        let ring = vec2(d.x, d.z).magnitude() - self.major_radius;
+        vec2(ring, d.y).magnitude() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let extent = vec3(big_r + small_r, small_r, big_r + small_r);
+        BBox::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// 用闭包给出的距离函数，需要自己提供包围盒。
+pub struct SdfFn<F> {
+    pub function: F,
+    pub bbox: BBox,
+    pub lipschitz: f32,
+}
+
+impl<F: Fn(&Vector3) -> f32 + Send + Sync> SdfFn<F> {
+    pub fn new(bbox: BBox, function: F) -> Self {
+        SdfFn {
+            function,
+            bbox,
+            lipschitz: 1.0,
+        }
+    }
+}
+
+impl<F: Fn(&Vector3) -> f32 + Send + Sync> Sdf for SdfFn<F> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        (self.function)(p)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.bbox
+    }
+
+    fn lipschitz(&self) -> f32 {
+        self.lipschitz
+    }
+}
+
+pub struct Union<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Union<A, B> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        self.0.distance(p).min(self.1.distance(p))
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.0.bounding_box().union(&self.1.bounding_box())
+    }
+
+    fn lipschitz(&self) -> f32 {
+        self.0.lipschitz().max(self.1.lipschitz())
+    }
+}
+
+pub struct Intersection<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Intersection<A, B> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        self.0.distance(p).max(self.1.distance(p))
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.0.bounding_box().intersection(&self.1.bounding_box())
+    }
+
+    fn lipschitz(&self) -> f32 {
+        self.0.lipschitz().max(self.1.lipschitz())
+    }
+}
+
+pub struct Difference<A, B>(pub A, pub B);
+
+impl<A: Sdf, B: Sdf> Sdf for Difference<A, B> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        self.0.distance(p).max(-self.1.distance(p))
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.0.bounding_box()
+    }
+
+    fn lipschitz(&self) -> f32 {
+        self.0.lipschitz().max(self.1.lipschitz())
+    }
+}
+
+/// 多项式形式的平滑最小值，比 `min` 最多小 `k / 4`。
+pub struct SmoothUnion<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f32,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return a.min(b);
+        }
+        let h = (0.5 + 0.5 * (b - a) / self.k).clamp(0.0, 1.0);
+        lerp(h, b, a) - self.k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let bbox = self.a.bounding_box().union(&self.b.bounding_box());
+        expand(&bbox, self.k.max(0.0) / 4.0)
+    }
+
+    fn lipschitz(&self) -> f32 {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
+}
+
+/// Perlin 噪声梯度大小的一个保守上界，采样到的最大值约为 3.09。
+const NOISE_LIPSCHITZ: f32 = 3.5;
+
+pub struct Displace<S> {
+    pub sdf: S,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl<S: Sdf> Sdf for Displace<S> {
+    fn distance(&self, p: &Vector3) -> f32 {
+        //perlin_noise 的值域是 [0, 1]，换到 [-1, 1]。
+        let noise = perlin_noise(&(p * self.frequency)) * 2.0 - 1.0;
+        self.sdf.distance(p) + self.amplitude * noise
+    }
+
+    fn bounding_box(&self) -> BBox {
+        expand(&self.sdf.bounding_box(), self.amplitude.abs())
+    }
+
+    fn lipschitz(&self) -> f32 {
+        self.sdf.lipschitz() + (self.amplitude * self.frequency).abs() * NOISE_LIPSCHITZ
+    }
+}
+
+/// 把距离函数当作 `Shape`，沿射线做球面追踪，法线取距离函数的梯度。
+pub struct SdfShape<S> {
+    pub sdf: S,
+    /// 距离小于它就认为到达了表面。
+    pub epsilon: f32,
+    pub max_steps: u32,
+}
+
+impl<S: Sdf> SdfShape<S> {
+    pub fn new(sdf: S) -> Self {
+        SdfShape {
+            sdf,
+            epsilon: 1e-4,
+            max_steps: 512,
+        }
+    }
+
+    /// 四面体采样的中心差分。
+    fn normal(&self, p: &Vector3) -> Vector3 {
+        let h = self.epsilon;
+        let k = [
+            vec3(1.0, -1.0, -1.0),
+            vec3(-1.0, -1.0, 1.0),
+            vec3(-1.0, 1.0, -1.0),
+            vec3(1.0, 1.0, 1.0),
+        ];
+        let gradient = k.iter().fold(Vector3::zero(), |acc, k| {
+            acc + k * self.sdf.distance(&(p + k * h))
+        });
+        if gradient.magnitude2() > 0.0 {
+            gradient.normalize()
+        } else {
+            Vector3::unit_y()
+        }
+    }
+}
+
+impl<S: Sdf> Shape for SdfShape<S> {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let bbox = expand(&self.sdf.bounding_box(), self.epsilon);
+        let (start, end) = bbox.ray_range(ray, tmin, tmax)?;
+        //按长度推进，方向不一定是单位向量。
+        let length = ray.direction.magnitude();
+        let dir = ray.direction / length;
+        let (mut s, end) = (start * length, end * length);
+        let step_scale = 1.0 / self.sdf.lipschitz();
+        let eps = self.epsilon;
+
+        //从上一个交点出发时起点贴着表面，先离开表面再开始找交点，避免打回原处。
+        let mut leaving = start == tmin && self.sdf.distance(&(ray.origin + dir * s)).abs() < eps;
+        let mut side = None;
+        for _ in 0..self.max_steps {
+            if s > end {
+                return None;
+            }
+            let p = ray.origin + dir * s;
+            let d = self.sdf.distance(&p);
+            if leaving {
+                if d.abs() < eps {
+                    s += eps;
+                    continue;
+                }
+                leaving = false;
+            }
+            //起点在内部时沿 -d 推进，找的是离开物体的位置。
+            let d = d * *side.get_or_insert(d.signum());
+            if d < eps {
+                let t = s / length;
+                let normal = self.normal(&p);
+                return Some(HitRecord::new(
+                    t,
+                    p,
+                    normal,
+                    Vector2::zero(),
+                    &Vector3::zero(),
+                    &Vector3::zero(),
+                ));
+            }
+            s += d * step_scale;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.sdf.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    fn towards_origin(z: f32) -> Ray {
+        RayBuilder {
+            origin: vec3(0.0, 0.0, z),
+            direction: -Vector3::unit_z(),
+        }.build()
+    }
+
+    fn ball(x: f32, radius: f32) -> SdfSphere {
+        SdfSphere {
+            center: vec3(x, 0.0, 0.0),
+            radius,
+        }
+    }
+
+    #[test]
+    fn sphere_tracing() {
+        let shape = SdfShape::new(ball(0.0, 1.0));
+        let hit = shape.hit(&towards_origin(5.0), 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 4.0, epsilon = 1e-3);
+        assert_relative_eq!(hit.normal, Vector3::unit_z(), epsilon = 1e-3);
+        assert!(shape.hit(&towards_origin(5.0), 0.0, 3.0).is_none());
+
+        //从表面出发继续前进，得到的是另一侧的出口而不是起点。
+        let inside = RayBuilder {
+            origin: hit.pos,
+            direction: -Vector3::unit_z(),
+        }.build();
+        let exit = shape.hit(&inside, 1e-4, 100.0).unwrap();
+        assert_relative_eq!(exit.pos.z, -1.0, epsilon = 1e-3);
+        assert_relative_eq!(exit.normal, -Vector3::unit_z(), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn combinators() {
+        //挖掉中间的小球后，射线穿过外壳打在内壁上。
+        let hollow = SdfShape::new(ball(0.0, 1.0).difference(ball(0.0, 0.5)));
+        let ray = RayBuilder {
+            origin: vec3(0.0, 0.0, 0.0),
+            direction: Vector3::unit_z(),
+        }.build();
+        let hit = hollow.hit(&ray, 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 0.5, epsilon = 1e-3);
+
+        let lens = SdfShape::new(ball(-0.5, 1.0).intersection(ball(0.5, 1.0)));
+        assert_relative_eq!(lens.bounding_box().min.x, -0.5);
+        let hit = lens.hit(&towards_origin(5.0), 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 5.0 - 0.75f32.sqrt(), epsilon = 1e-3);
+
+        //平滑融合会在两个球之间填出一段颈部。
+        let pair = ball(-1.2, 1.0).union(ball(1.2, 1.0));
+        assert!(SdfShape::new(pair)
+            .hit(&towards_origin(5.0), 0.0, 100.0)
+            .is_none());
+        let blob = SdfShape::new(ball(-1.2, 1.0).smooth_union(ball(1.2, 1.0), 1.0));
+        assert!(blob.hit(&towards_origin(5.0), 0.0, 100.0).is_some());
+    }
+
+    #[test]
+    fn displaced_surface_stays_in_bounds() {
+        let rough = SdfShape::new(ball(0.0, 1.0).displace(0.1, 4.0));
+        let bbox = rough.sdf.bounding_box();
+        for i in 0..16 {
+            let angle = i as f32 * 0.4;
+            let origin = vec3(angle.cos(), 0.3, angle.sin()) * 5.0;
+            let ray = RayBuilder {
+                origin,
+                direction: -origin,
+            }.build();
+            let hit = rough.hit(&ray, 0.0, 1.0).unwrap();
+            let r = hit.pos.magnitude();
+            assert!(r > 0.89 && r < 1.11);
+            assert!(hit.pos.x >= bbox.min.x && hit.pos.x <= bbox.max.x);
+            assert_relative_eq!(rough.sdf.distance(&hit.pos), 0.0, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn noise_lipschitz_bounds_gradient() {
+        use noise::{Noise, Perlin};
+        let perlin = Perlin::default();
+        let mut max = 0.0f32;
+        for i in 0..32 * 32 * 32 {
+            let (x, y, z) = (i % 32, i / 32 % 32, i / 1024);
+            let p = vec3(x as f32, y as f32, z as f32) * 0.11 + vec3(0.013, 0.029, 0.037);
+            let (value, gradient) = perlin.eval3(p);
+            //`Displace` 用的 `perlin_noise * 2 - 1` 就是同一个噪声。
+            assert_relative_eq!(perlin_noise(&p) * 2.0 - 1.0, value, epsilon = 1e-5);
+            max = max.max(gradient.magnitude());
+        }
+        assert!(max > 2.5 && max < NOISE_LIPSCHITZ);
+    }
+}
@@ -1,5 +1,8 @@
 use math::*;
-use {HitRecord, Ray, Shape};
+use {HitRecord, Interval, Ray, Shape};
+use bvh::BBox;
+use texture::Footprint;
+use std::f32;
 
 #[derive(Copy, Clone)]
 pub struct Sphere {
@@ -11,49 +14,113 @@ impl Sphere {
     pub fn new(center: Vector3, radius: f32) -> Self {
         Sphere { center, radius }
     }
-}
 
-impl Shape for Sphere {
+    /// 射线所在直线与球面的两个交点，从小到大排列。
     // (x - c_x)^2 + (y - c_y)^2 + (z - c_z)^2 - R^2 = 0
     // (p - c) . (p - c) - R^2 = 0 (dot product)
     // (o + td - c) . (o + td - c) - R^2 = 0
     // solve the equation.
-    // normal:
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32, transform: &Matrix) -> Option<HitRecord> {
-        let center = transform * make_pos(&self.center);
-        let temp = &make_pos(&ray.origin) - &center;
-        let ray_dir = make_dir(&ray.direction);
-        let ray_origin = make_pos(&ray.origin);
-        let a = ray_dir.magnitude2();
-        let b = 2.0 * ray_dir.dot(temp);
+    fn roots(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let temp = ray.origin - self.center;
+        let a = ray.direction.magnitude2();
+        let b = 2.0 * ray.direction.dot(temp);
         let c = temp.magnitude2() - self.radius * self.radius;
 
         let discriminant = b * b - 4.0 * a * c;
         if discriminant > 0.0 {
             let discriminant = discriminant.sqrt();
-            let mut t = (-b - discriminant) / 2.0 * a;
-            if t < tmin {
-                t = (-b + discriminant) / (2.0 * a);
-            }
-            if t < tmin || t > tmax {
-                None
-            } else {
-                let dir = t * &ray_dir;
-                let point = &ray_origin + &dir;
-                let normal = &point - &center;
-                let normal = normal.normalize();
-                // let delta = &point - &center;
-                // let theta = (delta.z / self.radius).acos();
-                // let phi = f32::atan2(delta.y, delta.x);
-                Some(HitRecord {
-                    t,
-                    normal: normal.truncate(),
-                    pos: point.truncate()
-                    // color: texture.get_value(&point, &Vector2::new(theta / 2.0 * f32::consts::PI, phi / f32::consts::PI)),
-                })
-            }
+            Some(((-b - discriminant) / (2.0 * a), (-b + discriminant) / (2.0 * a)))
         } else {
             None
         }
     }
+
+    fn record(&self, ray: &Ray, t: f32) -> HitRecord {
+        let point = ray.origin + ray.direction * t;
+        let normal = (point - self.center).normalize();
+        //y 轴朝上，theta 从北极量起，phi 绕 y 轴从 x 轴转向 z 轴。
+        let theta = normal.y.clamp(-1.0, 1.0).acos();
+        let mut phi = normal.z.atan2(normal.x);
+        if phi < 0.0 {
+            phi += 2.0 * f32::consts::PI;
+        }
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let tangent = vec3(-sin_phi, 0.0, cos_phi);
+        let r = self.radius;
+        HitRecord {
+            t,
+            normal,
+            shading_normal: normal,
+            uv: vec2(phi / (2.0 * f32::consts::PI), theta / f32::consts::PI),
+            barycentric: Vector3::zero(),
+            tangent,
+            bitangent: normal.cross(tangent),
+            pos: point,
+            dpdu: tangent * (2.0 * f32::consts::PI * r * sin_theta),
+            dpdv: vec3(cos_theta * cos_phi, -sin_theta, cos_theta * sin_phi)
+                * (f32::consts::PI * r),
+            footprint: Footprint::zero(),
+        }
+    }
+}
+
+impl Shape for Sphere {
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let (t0, t1) = self.roots(ray)?;
+        let t = if t0 < tmin { t1 } else { t0 };
+        if t < tmin || t > tmax {
+            None
+        } else {
+            Some(self.record(ray, t))
+        }
+    }
+
+    fn intervals(&self, ray: &Ray, tmin: f32, tmax: f32) -> Vec<Interval> {
+        match self.roots(ray) {
+            Some((t0, t1)) if t0 <= tmax && t1 >= tmin => vec![Interval {
+                t0: t0.max(tmin),
+                t1: t1.min(tmax),
+                enter: if t0 >= tmin {
+                    Some(self.record(ray, t0))
+                } else {
+                    None
+                },
+                exit: if t1 <= tmax {
+                    Some(self.record(ray, t1))
+                } else {
+                    None
+                },
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let r = vec3(self.radius, self.radius, self.radius);
+        BBox::new(self.center - r, self.center + r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    #[test]
+    fn uv_and_tangent_frame() {
+        let sphere = Sphere::new(vec3(0.0, 0.0, -5.0), 1.0);
+        let ray = RayBuilder {
+            origin: vec3(0.0, 0.0, 0.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = sphere
+            .hit(&ray, 0.0, 100.0)
+            .unwrap();
+        //打在 +z 一侧的赤道上。
+        assert_relative_eq!(hit.uv, vec2(0.25, 0.5), epsilon = 1e-6);
+        assert_relative_eq!(hit.tangent, -Vector3::unit_x(), epsilon = 1e-6);
+        assert_relative_eq!(hit.bitangent, -Vector3::unit_y(), epsilon = 1e-6);
+        assert_relative_eq!(hit.normal.cross(hit.tangent), hit.bitangent);
+    }
 }
@@ -0,0 +1,211 @@
+use math::*;
+use {HitRecord, Ray, Shape};
+use bvh::BBox;
+use super::azimuth;
+use std::f32;
+
+/// 绕 y 轴的圆环，`major_radius` 是管道中心线的半径，`minor_radius` 是管道的半径。
+/// u 是绕 y 轴的角度，v 是绕管道的角度，从外侧赤道开始向上。
+#[derive(Copy, Clone)]
+pub struct Torus {
+    pub center: Vector3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl Torus {
+    pub fn new(center: Vector3, major_radius: f32, minor_radius: f32) -> Self {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+/// `x^3 + a x^2 + b x + c = 0` 最大的实根。
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    //x = z - a / 3 消去二次项：z^3 + p z + q = 0。
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    let z = if discriminant >= 0.0 {
+        let s = discriminant.sqrt();
+        (-q / 2.0 + s).cbrt() + (-q / 2.0 - s).cbrt()
+    } else {
+        //三个实根，k = 0 的那个最大。
+        let r = (-p / 3.0).sqrt();
+        let theta = (-q / (2.0 * r * r * r)).clamp(-1.0, 1.0).acos();
+        2.0 * r * (theta / 3.0).cos()
+    };
+    z - a / 3.0
+}
+
+/// 四次方程 `c[4] t^4 + ... + c[0] = 0` 的实根（Ferrari 方法），不保证顺序。
+fn solve_quartic(c: &[f64; 5], roots: &mut Vec<f64>) {
+    roots.clear();
+    let (a, b, cc, d) = (c[3] / c[4], c[2] / c[4], c[1] / c[4], c[0] / c[4]);
+    //t = y - a / 4 消去三次项：y^4 + p y^2 + q y + r = 0。
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = cc - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * cc / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+    let mut push_quadratic = |b: f64, c: f64| {
+        let discriminant = b * b - 4.0 * c;
+        if discriminant >= 0.0 {
+            let s = discriminant.sqrt();
+            roots.push((-b - s) / 2.0 - a / 4.0);
+            roots.push((-b + s) / 2.0 - a / 4.0);
+        }
+    };
+    if q.abs() < 1e-12 {
+        //双二次方程。
+        let discriminant = p * p - 4.0 * r;
+        if discriminant < 0.0 {
+            return;
+        }
+        let s = discriminant.sqrt();
+        for &y2 in &[(-p - s) / 2.0, (-p + s) / 2.0] {
+            push_quadratic(0.0, -y2);
+        }
+        return;
+    }
+    //(y^2 + p / 2 + m)^2 = 2m y^2 - q y + (m^2 + m p + p^2 / 4 - r)，
+    //取 m 使右边是完全平方，就分解成两个二次方程。
+    let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+    if m <= 0.0 {
+        return;
+    }
+    let s = (2.0 * m).sqrt();
+    push_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+    push_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+}
+
+impl Shape for Torus {
+    // (x^2 + y^2 + z^2 + R^2 - r^2)^2 - 4 R^2 (x^2 + z^2) = 0
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let big_r = f64::from(self.major_radius);
+        let small_r = f64::from(self.minor_radius);
+        let d = ray.direction.cast::<f64>()?;
+        let o = (ray.origin - self.center).cast::<f64>()?;
+        //把起点挪到离圆心最近的地方再解方程，减小系数的量级差。
+        let dd = d.magnitude2();
+        let shift = -o.dot(d) / dd;
+        let o = o + d * shift;
+
+        let od = o.dot(d);
+        let k = o.magnitude2() + big_r * big_r - small_r * small_r;
+        let r4 = 4.0 * big_r * big_r;
+        let coefficients = [
+            k * k - r4 * (o.x * o.x + o.z * o.z),
+            4.0 * od * k - 2.0 * r4 * (o.x * d.x + o.z * d.z),
+            2.0 * dd * k + 4.0 * od * od - r4 * (d.x * d.x + d.z * d.z),
+            4.0 * dd * od,
+            dd * dd,
+        ];
+        let eval = |t: f64| {
+            coefficients
+                .iter()
+                .rev()
+                .fold(0.0, |acc, c| acc * t + c)
+        };
+        let derivative = |t: f64| {
+            4.0 * coefficients[4] * t * t * t
+                + 3.0 * coefficients[3] * t * t
+                + 2.0 * coefficients[2] * t
+                + coefficients[1]
+        };
+        let mut roots = Vec::with_capacity(4);
+        solve_quartic(&coefficients, &mut roots);
+
+        let mut closest: Option<f64> = None;
+        for &root in &roots {
+            //用牛顿迭代修正解析解的误差。
+            let mut t = root;
+            for _ in 0..2 {
+                let slope = derivative(t);
+                if slope != 0.0 {
+                    t -= eval(t) / slope;
+                }
+            }
+            let t_ray = t + shift;
+            if t_ray < f64::from(tmin) || t_ray > f64::from(tmax) {
+                continue;
+            }
+            if closest.is_none_or(|c| t_ray < c) {
+                closest = Some(t_ray);
+            }
+        }
+        let t = closest? as f32;
+
+        let pos = ray.origin + ray.direction * t;
+        let p = pos - self.center;
+        let phi = azimuth(&p);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        //管道中心线上离交点最近的点。
+        let ring = vec3(cos_phi, 0.0, sin_phi) * self.major_radius;
+        let normal = (p - ring).normalize();
+        let radial = vec3(p.x, 0.0, p.z).magnitude() - self.major_radius;
+        let mut theta = p.y.atan2(radial);
+        if theta < 0.0 {
+            theta += 2.0 * f32::consts::PI;
+        }
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let tau = 2.0 * f32::consts::PI;
+        Some(HitRecord::new(
+            t,
+            pos,
+            normal,
+            vec2(phi, theta) / tau,
+            &(vec3(-sin_phi, 0.0, cos_phi) * (tau * (big_r + small_r * cos_theta))),
+            &(vec3(-sin_theta * cos_phi, cos_theta, -sin_theta * sin_phi) * (tau * small_r)),
+        ))
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let extent = vec3(big_r + small_r, small_r, big_r + small_r);
+        BBox::new(self.center - extent, self.center + extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RayBuilder;
+
+    #[test]
+    fn ring_hits() {
+        let torus = Torus::new(Vector3::zero(), 2.0, 0.5);
+        //沿 x 轴穿过整个圆环，依次经过四个交点。
+        let ray = RayBuilder {
+            origin: vec3(-5.0, 0.0, 0.0),
+            direction: Vector3::unit_x(),
+        }.build();
+        let mut tmin = 0.0;
+        for &(t, nx) in &[(2.5, -1.0), (3.5, 1.0), (6.5, -1.0), (7.5, 1.0)] {
+            let hit = torus.hit(&ray, tmin, 100.0).unwrap();
+            assert_relative_eq!(hit.t, t, epsilon = 1e-4);
+            assert_relative_eq!(hit.normal, vec3(nx, 0.0, 0.0), epsilon = 1e-4);
+            tmin = hit.t + 1e-3;
+        }
+        assert!(torus.hit(&ray, tmin, 100.0).is_none());
+
+        //从正上方打在管道顶部。
+        let down = RayBuilder {
+            origin: vec3(0.0, 5.0, 2.0),
+            direction: -Vector3::unit_y(),
+        }.build();
+        let hit = torus.hit(&down, 0.0, 100.0).unwrap();
+        assert_relative_eq!(hit.t, 4.5, epsilon = 1e-4);
+        assert_relative_eq!(hit.normal, Vector3::unit_y(), epsilon = 1e-4);
+        assert_relative_eq!(hit.uv, vec2(0.25, 0.25), epsilon = 1e-4);
+        //穿过中间的洞。
+        let hole = RayBuilder {
+            origin: vec3(0.0, 5.0, 0.0),
+            direction: -Vector3::unit_y(),
+        }.build();
+        assert!(torus.hit(&hole, 0.0, 100.0).is_none());
+    }
+}
@@ -1,6 +1,8 @@
-use math::{make_pos, InnerSpace, Matrix, Vector3};
+use math::{vec2, vec3, InnerSpace, Vector3, Zero};
 use {HitRecord, Ray, Shape};
-use std::rc::Rc;
+use bvh::{BBox, Primitive};
+use std::sync::Arc;
+use texture::Footprint;
 use super::super::vertices::Vertex;
 
 pub struct Triangle {
@@ -16,10 +18,8 @@ impl Triangle {
 }
 
 impl Shape for Triangle {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32, transform: &Matrix) -> Option<HitRecord> {
-        let p0 = transform * make_pos(&self.p0);
-        let p1 = transform * make_pos(&self.p1);
-        let p2 = transform * make_pos(&self.p2);
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let (p0, p1, p2) = (self.p0, self.p1, self.p2);
 
         let a = p0.x - p1.x;
         let b = p0.y - p1.y;
@@ -55,28 +55,51 @@ impl Shape for Triangle {
             if gamma <= 0.0 || beta + gamma >= 1.0 {
                 None
             } else {
-                let tval = -(f * akjb + e * jcal + g * blkc) / denom;
-                let vec = &p2 - &p0;
+                let tval = -(f * akjb + e * jcal + d * blkc) / denom;
+                let vec = p2 - p0;
                 if tval >= tmin && tval <= tmax {
-                    Some(HitRecord {
+                    let normal = (p1 - p0).cross(vec).normalize();
+                    let mut hit = HitRecord {
                         t: tval,
-                        normal: ((&p1 - &p0).truncate().cross(vec.truncate())).normalize(),
+                        normal,
+                        shading_normal: normal,
+                        //没有纹理坐标时用重心坐标代替。
+                        uv: vec2(beta, gamma),
+                        barycentric: vec3(1.0 - beta - gamma, beta, gamma),
+                        tangent: Vector3::zero(),
+                        bitangent: Vector3::zero(),
                         pos: ray.origin + ray.direction * tval,
-                    })
+                        dpdu: p1 - p0,
+                        dpdv: vec,
+                        footprint: Footprint::zero(),
+                    };
+                    hit.set_tangents(&(p1 - p0));
+                    Some(hit)
                 } else {
                     None
                 }
             }
         }
     }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::from_point(self.p0)
+            .union_point(&self.p1)
+            .union_point(&self.p2)
+    }
 }
 
 pub struct MeshTriangle<T: Vertex> {
-    mesh: Rc<[T]>,
+    mesh: Arc<[T]>,
     points: [usize; 3],
 }
 
 impl<T: Vertex> MeshTriangle<T> {
+    /// `points` 是三个顶点在 `mesh` 中的下标。
+    pub fn new(mesh: Arc<[T]>, points: [usize; 3]) -> Self {
+        MeshTriangle { mesh, points }
+    }
+
     fn as_triangle(&self) -> Triangle {
         let points = &self.points;
         let mesh = &self.mesh;
@@ -89,7 +112,89 @@ impl<T: Vertex> MeshTriangle<T> {
 }
 
 impl<T: Vertex> Shape for MeshTriangle<T> {
-    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32, transform: &Matrix) -> Option<HitRecord> {
-        self.as_triangle().hit(ray, tmin, tmax, transform)
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        let mut hit = self.as_triangle().hit(ray, tmin, tmax)?;
+        let vertices = [
+            &self.mesh[self.points[0]],
+            &self.mesh[self.points[1]],
+            &self.mesh[self.points[2]],
+        ];
+        let b = hit.barycentric;
+
+        if let (Some(uv0), Some(uv1), Some(uv2)) = (
+            vertices[0].get_uv(),
+            vertices[1].get_uv(),
+            vertices[2].get_uv(),
+        ) {
+            hit.uv = uv0 * b.x + uv1 * b.y + uv2 * b.z;
+            let pos = |i: usize| *vertices[i].get_pos();
+            let (duv02, duv12) = (uv0 - uv2, uv1 - uv2);
+            let det = duv02.x * duv12.y - duv02.y * duv12.x;
+            if det.abs() > 1e-12 {
+                let (dp02, dp12) = (pos(0) - pos(2), pos(1) - pos(2));
+                hit.dpdu = (duv12.y * dp02 - duv02.y * dp12) / det;
+                hit.dpdv = (duv02.x * dp12 - duv12.x * dp02) / det;
+            }
+        }
+
+        if let (Some(n0), Some(n1), Some(n2)) = (
+            vertices[0].get_normal(),
+            vertices[1].get_normal(),
+            vertices[2].get_normal(),
+        ) {
+            let n = n0 * b.x + n1 * b.y + n2 * b.z;
+            if n.magnitude2() > 0.0 {
+                hit.shading_normal = n.normalize();
+                //几何法线翻到和着色法线同一侧，背面判断才会一致。
+                if hit.normal.dot(hit.shading_normal) < 0.0 {
+                    hit.normal = -hit.normal;
+                }
+            }
+        }
+        let dpdu = hit.dpdu;
+        hit.set_tangents(&dpdu);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.as_triangle().bounding_box()
+    }
+}
+
+impl<T: Vertex> Primitive for MeshTriangle<T> {
+    fn bounding_box(&self) -> BBox {
+        Shape::bounding_box(self)
+    }
+
+    fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<HitRecord> {
+        Shape::hit(self, ray, tmin, tmax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use vertices::VertexUvn;
+    use RayBuilder;
+
+    #[test]
+    fn interpolates_vertex_attributes() {
+        let normal = |x: f32| vec3(x, 0.0, 1.0).normalize();
+        let mesh: Arc<[VertexUvn]> = Arc::from(vec![
+            VertexUvn::new(vec3(0.0, 0.0, 0.0), normal(-1.0), vec2(0.0, 0.0)),
+            VertexUvn::new(vec3(1.0, 0.0, 0.0), normal(1.0), vec2(1.0, 0.0)),
+            VertexUvn::new(vec3(0.0, 1.0, 0.0), normal(1.0), vec2(0.0, 1.0)),
+        ]);
+        let triangle = MeshTriangle::new(mesh, [0, 1, 2]);
+        let ray = RayBuilder {
+            origin: vec3(0.5, 0.25, 1.0),
+            direction: -Vector3::unit_z(),
+        }.build();
+        let hit = Shape::hit(&triangle, &ray, 0.0, 10.0).unwrap();
+        assert_relative_eq!(hit.barycentric, vec3(0.25, 0.5, 0.25), epsilon = 1e-6);
+        assert_relative_eq!(hit.uv, vec2(0.5, 0.25), epsilon = 1e-6);
+        assert_relative_eq!(hit.normal, Vector3::unit_z());
+        assert_relative_eq!(hit.shading_normal, normal(0.5), epsilon = 1e-6);
     }
 }
@@ -0,0 +1,257 @@
+use super::{ColorRamp, Footprint, Texture};
+use math::*;
+use rgb::Rgb;
+use HitRecord;
+
+/// 按 `amount` 的每个通道在 `a` 和 `b` 之间插值，0 取 `a`，1 取 `b`。
+pub struct Mix {
+    pub a: Box<dyn Texture>,
+    pub b: Box<dyn Texture>,
+    pub amount: Box<dyn Texture>,
+}
+
+impl Texture for Mix {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        let t = self.amount.get_value(pos, uv);
+        let (a, b) = (self.a.get_value(pos, uv), self.b.get_value(pos, uv));
+        a * (Rgb::white() - t) + b * t
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        let t = self.amount.get_filtered(pos, uv, footprint);
+        let a = self.a.get_filtered(pos, uv, footprint);
+        let b = self.b.get_filtered(pos, uv, footprint);
+        a * (Rgb::white() - t) + b * t
+    }
+
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        let t = self.amount.get_at_hit(hit);
+        let (a, b) = (self.a.get_at_hit(hit), self.b.get_at_hit(hit));
+        a * (Rgb::white() - t) + b * t
+    }
+}
+
+/// 两个纹理逐通道相乘，常用来给图案叠一层明暗变化。
+pub struct Multiply {
+    pub a: Box<dyn Texture>,
+    pub b: Box<dyn Texture>,
+}
+
+impl Texture for Multiply {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        self.a.get_value(pos, uv) * self.b.get_value(pos, uv)
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        self.a.get_filtered(pos, uv, footprint) * self.b.get_filtered(pos, uv, footprint)
+    }
+
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        self.a.get_at_hit(hit) * self.b.get_at_hit(hit)
+    }
+}
+
+/// 把输入的亮度从 `range` 线性映射到 [0, 1]，再经过 `ramp` 上色。
+pub struct Remap {
+    pub input: Box<dyn Texture>,
+    pub range: (f32, f32),
+    pub ramp: ColorRamp,
+}
+
+impl Remap {
+    fn map(&self, input: Rgb) -> Rgb {
+        let (low, high) = self.range;
+        self.ramp.at((input.luminance() - low) / (high - low))
+    }
+}
+
+impl Texture for Remap {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        self.map(self.input.get_value(pos, uv))
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        self.map(self.input.get_filtered(pos, uv, footprint))
+    }
+
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        self.map(self.input.get_at_hit(hit))
+    }
+}
+
+/// 纹理坐标先乘 `scale`，再旋转 `rotation` 弧度，最后加上 `offset`。
+/// `scale` 为 2 时纹理在每个方向上重复两次。
+pub struct UvTransform {
+    pub texture: Box<dyn Texture>,
+    pub scale: Vector2,
+    pub rotation: f32,
+    pub offset: Vector2,
+}
+
+impl UvTransform {
+    /// 缩放和旋转，不含平移。
+    fn linear(&self, v: Vector2) -> Vector2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let s = v.mul_element_wise(self.scale);
+        vec2(cos * s.x - sin * s.y, sin * s.x + cos * s.y)
+    }
+}
+
+impl Texture for UvTransform {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        self.texture
+            .get_value(pos, &(self.linear(*uv) + self.offset))
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        let footprint = Footprint {
+            duvdx: self.linear(footprint.duvdx),
+            duvdy: self.linear(footprint.duvdy),
+            ..*footprint
+        };
+        let uv = self.linear(*uv) + self.offset;
+        self.texture.get_filtered(pos, &uv, &footprint)
+    }
+}
+
+/// 像摆放物体一样摆放纹理：用 `transform` 的逆把位置变回纹理自己的空间，
+/// 所以放大两倍的棋盘格格子也大两倍。
+pub struct PositionTransform {
+    pub texture: Box<dyn Texture>,
+    inverse: Affine,
+}
+
+impl PositionTransform {
+    pub fn new(texture: Box<dyn Texture>, transform: &Affine) -> Self {
+        PositionTransform {
+            texture,
+            inverse: transform.inverse(),
+        }
+    }
+}
+
+impl Texture for PositionTransform {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        self.texture.get_value(&self.inverse.point(pos), uv)
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        let footprint = Footprint {
+            dpdx: self.inverse.vector(&footprint.dpdx),
+            dpdy: self.inverse.vector(&footprint.dpdy),
+            ..*footprint
+        };
+        self.texture
+            .get_filtered(&self.inverse.point(pos), uv, &footprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use texture::{Checkerboard, Domain, PureColorTexture};
+
+    fn color(r: f32, g: f32, b: f32) -> Box<dyn Texture> {
+        Box::new(PureColorTexture {
+            color: Rgb::new(r, g, b),
+        })
+    }
+
+    #[test]
+    fn mix_multiply_remap() {
+        let mix = Mix {
+            a: color(1.0, 0.0, 0.0),
+            b: color(0.0, 0.0, 1.0),
+            amount: color(0.25, 0.25, 0.25),
+        };
+        let value = |t: &dyn Texture| t.get_value(&Vector3::zero(), &Vector2::zero());
+        assert_eq!(value(&mix), Rgb::new(0.75, 0.0, 0.25));
+
+        let multiply = Multiply {
+            a: Box::new(mix),
+            b: color(2.0, 1.0, 0.0),
+        };
+        assert_eq!(value(&multiply), Rgb::new(1.5, 0.0, 0.0));
+
+        let remap = Remap {
+            input: color(0.5, 0.5, 0.5),
+            range: (0.25, 0.75),
+            ramp: ColorRamp::two(Rgb::black(), Rgb::new(0.0, 2.0, 0.0)),
+        };
+        assert_relative_eq!(value(&remap).g, 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn transforms() {
+        let checker = || {
+            Box::new(Checkerboard {
+                even: color(0.0, 0.0, 0.0),
+                odd: color(1.0, 1.0, 1.0),
+                domain: Domain::Uv,
+            })
+        };
+        let tiled = UvTransform {
+            texture: checker(),
+            scale: vec2(2.0, 2.0),
+            rotation: 0.0,
+            offset: Vector2::zero(),
+        };
+        let at = |t: &dyn Texture, u, v| t.get_value(&Vector3::zero(), &vec2(u, v)).r;
+        assert_eq!(at(&tiled, 0.25, 0.25), 0.0);
+        assert_eq!(at(&tiled, 0.75, 0.25), 1.0);
+
+        //绕原点转 90 度后 (0.5, 0.5) 落到 (-0.5, 0.5)，换成了另一种颜色的格子。
+        let rotated = UvTransform {
+            rotation: 90f32.to_radians(),
+            ..tiled
+        };
+        assert_eq!(at(&rotated, 0.25, 0.25), 1.0);
+
+        let placed = PositionTransform::new(
+            Box::new(Checkerboard {
+                domain: Domain::Position,
+                ..*checker()
+            }),
+            &Affine::scale(2.0, 2.0, 2.0).unwrap(),
+        );
+        let p = |x| placed.get_value(&vec3(x, 0.5, 0.5), &Vector2::zero()).r;
+        assert_eq!(p(1.5), 0.0);
+        assert_eq!(p(2.5), 1.0);
+    }
+
+    #[test]
+    fn transformed_footprints() {
+        //纹理坐标放大 4 倍后，原来只盖住半个格子的范围盖住了两个格子。
+        let tiled = UvTransform {
+            texture: Box::new(Checkerboard {
+                even: color(0.0, 0.0, 0.0),
+                odd: color(1.0, 1.0, 1.0),
+                domain: Domain::Uv,
+            }),
+            scale: vec2(4.0, 4.0),
+            rotation: 0.0,
+            offset: Vector2::zero(),
+        };
+        let footprint = Footprint {
+            duvdx: vec2(0.25, 0.0),
+            ..Footprint::zero()
+        };
+        let c = tiled.get_filtered(&Vector3::zero(), &vec2(0.125, 0.125), &footprint);
+        assert_relative_eq!(c.r, 0.5);
+
+        let placed = PositionTransform::new(
+            Box::new(Checkerboard {
+                even: color(0.0, 0.0, 0.0),
+                odd: color(1.0, 1.0, 1.0),
+                domain: Domain::Position,
+            }),
+            &Affine::scale(2.0, 2.0, 2.0).unwrap(),
+        );
+        let footprint = Footprint {
+            dpdx: vec3(0.5, 0.0, 0.0),
+            ..Footprint::zero()
+        };
+        let c = placed.get_filtered(&vec3(1.0, 0.5, 0.5), &Vector2::zero(), &footprint);
+        assert_relative_eq!(c.r, 0.0);
+    }
+}
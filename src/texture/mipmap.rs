@@ -0,0 +1,471 @@
+extern crate image;
+
+use super::{Footprint, Texture};
+use hdr::{read_hdr, read_pfm};
+use math::*;
+use rgb::Rgb;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+/// 纹理坐标超出 [0, 1] 时怎样取纹素。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    /// 取最近的边缘纹素。
+    Clamp,
+    /// 每隔一个周期镜像一次，接缝处连续。
+    Mirror,
+}
+
+impl WrapMode {
+    fn index(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// 在一层图像里怎样重建两个纹素之间的值。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexelFilter {
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom 样条，取周围 4x4 个纹素。
+    Bicubic,
+}
+
+/// 纹理在屏幕上缩小时怎样在 mip 层之间取值。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipFilter {
+    /// 只用原图。
+    None,
+    /// 按覆盖范围的长边选层，在相邻两层之间插值。
+    Trilinear,
+    /// 椭圆加权平均，斜着看的表面不会像三线性那样发糊。
+    Ewa,
+}
+
+/// EWA 椭圆长短轴之比的上限，超过时加长短轴，免得要累加的纹素太多。
+const MAX_ANISOTROPY: f32 = 8.0;
+
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<Rgb>,
+}
+
+impl Level {
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> Rgb {
+        let (x, y) = (wrap.index(x, self.width), wrap.index(y, self.height));
+        self.texels[y * self.width + x]
+    }
+
+    /// 长宽各减半（至少为 1）。新纹素是它覆盖的那块区域的面积加权平均，
+    /// 奇数边长时边上的纹素只算一部分，每一层的平均值都和原图一样。
+    fn downsample(&self) -> Level {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let (xs, ys) = (coverage(self.width, width), coverage(self.height, height));
+        let mut texels = Vec::with_capacity(width * height);
+        for y in &ys {
+            for x in &xs {
+                let mut sum = Rgb::black();
+                for &(yy, wy) in y {
+                    for &(xx, wx) in x {
+                        sum += self.texels[yy * self.width + xx] * (wx * wy);
+                    }
+                }
+                texels.push(sum);
+            }
+        }
+        Level {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    /// 纹理坐标换算成纹素坐标，纹素中心在半整数处。
+    fn st(&self, uv: Vector2) -> Vector2 {
+        vec2(
+            uv.x * self.width as f32 - 0.5,
+            uv.y * self.height as f32 - 0.5,
+        )
+    }
+
+    fn sample(&self, uv: Vector2, filter: TexelFilter, wrap: WrapMode) -> Rgb {
+        let st = self.st(uv);
+        let (x, y) = (st.x.floor(), st.y.floor());
+        let (fx, fy) = (st.x - x, st.y - y);
+        let (x, y) = (x as i64, y as i64);
+        match filter {
+            TexelFilter::Nearest => self.texel(
+                (uv.x * self.width as f32).floor() as i64,
+                (uv.y * self.height as f32).floor() as i64,
+                wrap,
+            ),
+            TexelFilter::Bilinear => {
+                let t = |dx, dy| self.texel(x + dx, y + dy, wrap);
+                lerp(fy, lerp(fx, t(0, 0), t(1, 0)), lerp(fx, t(0, 1), t(1, 1)))
+            }
+            TexelFilter::Bicubic => {
+                let (wx, wy) = (catmull_rom(fx), catmull_rom(fy));
+                let mut sum = Rgb::black();
+                for (j, wy) in wy.iter().enumerate() {
+                    for (i, wx) in wx.iter().enumerate() {
+                        sum += self.texel(x + i as i64 - 1, y + j as i64 - 1, wrap) * (wx * wy);
+                    }
+                }
+                //样条会过冲，颜色不能是负的。
+                Rgb::new(sum.r.max(0.0), sum.g.max(0.0), sum.b.max(0.0))
+            }
+        }
+    }
+
+    /// 以 `uv` 为中心、`axis0` 和 `axis1`（纹理坐标单位）为半轴的椭圆内的高斯加权平均。
+    fn ewa(&self, uv: Vector2, axis0: Vector2, axis1: Vector2, wrap: WrapMode) -> Rgb {
+        let size = vec2(self.width as f32, self.height as f32);
+        let st = self.st(uv);
+        let (a0, a1) = (axis0.mul_element_wise(size), axis1.mul_element_wise(size));
+        //椭圆 a s² + b s t + c t² < 1，各加 1 让它至少盖住一个纹素。
+        let a = a0.y * a0.y + a1.y * a1.y + 1.0;
+        let b = -2.0 * (a0.x * a0.y + a1.x * a1.y);
+        let c = a0.x * a0.x + a1.x * a1.x + 1.0;
+        let f = 1.0 / (a * c - b * b / 4.0);
+        let (a, b, c) = (a * f, b * f, c * f);
+
+        let det = 4.0 * a * c - b * b;
+        let (half_s, half_t) = (2.0 * (det * c).sqrt() / det, 2.0 * (det * a).sqrt() / det);
+        let (s0, s1) = (
+            (st.x - half_s).ceil() as i64,
+            (st.x + half_s).floor() as i64,
+        );
+        let (t0, t1) = (
+            (st.y - half_t).ceil() as i64,
+            (st.y + half_t).floor() as i64,
+        );
+
+        const ALPHA: f32 = 2.0;
+        let mut sum = Rgb::black();
+        let mut total = 0.0;
+        for t in t0..t1 + 1 {
+            let dt = t as f32 - st.y;
+            for s in s0..s1 + 1 {
+                let ds = s as f32 - st.x;
+                let r2 = a * ds * ds + b * ds * dt + c * dt * dt;
+                if r2 < 1.0 {
+                    let weight = (-ALPHA * r2).exp() - (-ALPHA).exp();
+                    sum += self.texel(s, t, wrap) * weight;
+                    total += weight;
+                }
+            }
+        }
+        if total > 0.0 {
+            sum / total
+        } else {
+            self.sample(uv, TexelFilter::Bilinear, wrap)
+        }
+    }
+}
+
+/// 把 `from` 个纹素缩成 `to` 个时，每个新纹素覆盖的旧纹素和权重，权重之和为 1。
+fn coverage(from: usize, to: usize) -> Vec<Vec<(usize, f32)>> {
+    let step = from as f32 / to as f32;
+    (0..to)
+        .map(|i| {
+            let (start, end) = (i as f32 * step, (i + 1) as f32 * step);
+            (start.floor() as usize..(end.ceil() as usize).min(from))
+                .map(|k| {
+                    let overlap = end.min(k as f32 + 1.0) - start.max(k as f32);
+                    (k, overlap / step)
+                })
+                .filter(|&(_, w)| w > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+/// Catmull-Rom 样条在 `-1, 0, 1, 2` 四个纹素上的权重，`f` 是到第 0 个纹素的距离。
+fn catmull_rom(f: f32) -> [f32; 4] {
+    let (f2, f3) = (f * f, f * f * f);
+    [
+        -0.5 * f3 + f2 - 0.5 * f,
+        1.5 * f3 - 2.5 * f2 + 1.0,
+        -1.5 * f3 + 2.0 * f2 + 0.5 * f,
+        0.5 * f3 - 0.5 * f2,
+    ]
+}
+
+/// 图像纹理。`u` 向右、`v` 向下，`(0, 0)` 是图像左上角，一个周期是 [0, 1]。
+///
+/// 构造时就建好 mip 金字塔，`lookup` 根据纹理坐标在屏幕上的变化率选层。
+pub struct ImageTexture {
+    /// 第 0 层是原图，最后一层是 1x1。
+    levels: Vec<Level>,
+    pub wrap: WrapMode,
+    pub filter: TexelFilter,
+    pub mip: MipFilter,
+}
+
+impl ImageTexture {
+    /// `texels` 是线性颜色，按行存放，第 0 行在最上面。
+    pub fn new(width: usize, height: usize, texels: Vec<Rgb>) -> Self {
+        assert!(width > 0 && height > 0, "an image texture cannot be empty");
+        assert_eq!(texels.len(), width * height);
+        let mut levels = vec![Level {
+            width,
+            height,
+            texels,
+        }];
+        loop {
+            let next = {
+                let last = &levels[levels.len() - 1];
+                if last.width == 1 && last.height == 1 {
+                    break;
+                }
+                last.downsample()
+            };
+            levels.push(next);
+        }
+        ImageTexture {
+            levels,
+            wrap: WrapMode::Repeat,
+            filter: TexelFilter::Bilinear,
+            mip: MipFilter::Trilinear,
+        }
+    }
+
+    /// 8 位图像按 sRGB 解码成线性颜色。
+    pub fn from_image(image: &image::RgbImage) -> Self {
+        let texels = image.pixels().map(|&p| Rgb::from(p)).collect();
+        ImageTexture::new(image.width() as usize, image.height() as usize, texels)
+    }
+
+    /// 读取 `.hdr`、`.pfm` 或者 `image` 库支持的格式（PNG、JPEG 等）。
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let hdr = match path.extension().and_then(|e| e.to_str()) {
+            Some("hdr") => Some(read_hdr(&mut BufReader::new(File::open(path)?))?),
+            Some("pfm") => Some(read_pfm(&mut BufReader::new(File::open(path)?))?),
+            _ => None,
+        };
+        if let Some(hdr) = hdr {
+            return Ok(ImageTexture::new(
+                hdr.width as usize,
+                hdr.height as usize,
+                hdr.pixels,
+            ));
+        }
+        let image = image::open(path).map_err(|e| match e {
+            image::ImageError::IoError(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        })?;
+        Ok(ImageTexture::from_image(&image.to_rgb()))
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    /// 按 `mip` 过滤。`duvdx` 和 `duvdy` 是纹理坐标随屏幕上 x、y 方向移动一个像素的变化量，
+    /// 都为零时只在原图上用 `filter` 取值。
+    pub fn lookup(&self, uv: Vector2, duvdx: Vector2, duvdy: Vector2) -> Rgb {
+        let size = vec2(self.width() as f32, self.height() as f32);
+        let (dx, dy) = (duvdx.mul_element_wise(size), duvdy.mul_element_wise(size));
+        match self.mip {
+            MipFilter::None => self.levels[0].sample(uv, self.filter, self.wrap),
+            MipFilter::Trilinear => {
+                let width = dx.magnitude().max(dy.magnitude());
+                self.blend_levels(width, |level| level.sample(uv, self.filter, self.wrap))
+            }
+            MipFilter::Ewa => {
+                let (major, mut minor) = if dx.magnitude2() < dy.magnitude2() {
+                    (duvdy, duvdx)
+                } else {
+                    (duvdx, duvdy)
+                };
+                let (major_len, minor_len) = (
+                    major.mul_element_wise(size).magnitude(),
+                    minor.mul_element_wise(size).magnitude(),
+                );
+                if minor_len == 0.0 {
+                    return self.levels[0].sample(uv, self.filter, self.wrap);
+                }
+                //太扁的椭圆加长短轴，相应地选更粗的层。
+                let mut width = minor_len;
+                if minor_len * MAX_ANISOTROPY < major_len {
+                    let scale = major_len / (minor_len * MAX_ANISOTROPY);
+                    minor *= scale;
+                    width *= scale;
+                }
+                self.blend_levels(width, |level| level.ewa(uv, major, minor, self.wrap))
+            }
+        }
+    }
+
+    /// 覆盖范围为 `width` 个原图纹素时选出相邻两层，按层号的小数部分插值。
+    fn blend_levels<F: Fn(&Level) -> Rgb>(&self, width: f32, sample: F) -> Rgb {
+        let top = self.levels.len() - 1;
+        let level = width.max(1e-8).log2().clamp(0.0, top as f32);
+        let base = level.floor() as usize;
+        if base == top {
+            //最后一层只有一个纹素，就是整幅图的平均。EWA 的椭圆这时可能大得没边。
+            return self.levels[top].texels[0];
+        }
+        let t = level - base as f32;
+        let fine = sample(&self.levels[base]);
+        if t == 0.0 {
+            fine
+        } else {
+            lerp(t, fine, sample(&self.levels[base + 1]))
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn get_value(&self, _pos: &Vector3, uv: &Vector2) -> Rgb {
+        self.lookup(*uv, Vector2::zero(), Vector2::zero())
+    }
+
+    fn get_filtered(&self, _pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        self.lookup(*uv, footprint.duvdx, footprint.duvdy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(values: &[f32]) -> Vec<Rgb> {
+        values.iter().map(|&v| Rgb::new(v, v, v)).collect()
+    }
+
+    fn row(wrap: WrapMode, filter: TexelFilter) -> ImageTexture {
+        ImageTexture {
+            wrap,
+            filter,
+            mip: MipFilter::None,
+            ..ImageTexture::new(4, 1, gray(&[0.0, 1.0, 2.0, 3.0]))
+        }
+    }
+
+    #[test]
+    fn edges_do_not_panic() {
+        let mut texture = ImageTexture::new(3, 2, gray(&[0.0, 0.2, 0.4, 0.6, 0.8, 1.0]));
+        let footprints = [0.0, 0.01, 0.3, 5.0];
+        for &wrap in &[WrapMode::Repeat, WrapMode::Clamp, WrapMode::Mirror] {
+            for &filter in &[
+                TexelFilter::Nearest,
+                TexelFilter::Bilinear,
+                TexelFilter::Bicubic,
+            ] {
+                for &mip in &[MipFilter::None, MipFilter::Trilinear, MipFilter::Ewa] {
+                    texture.wrap = wrap;
+                    texture.filter = filter;
+                    texture.mip = mip;
+                    for i in -6..11 {
+                        for j in -6..11 {
+                            let uv = vec2(i as f32 * 0.25, j as f32 * 0.25);
+                            for &d in &footprints {
+                                let c = texture.lookup(uv, vec2(d, 0.0), vec2(0.0, d * 0.3));
+                                //双三次插值会稍微过冲。
+                                assert!(c.r >= 0.0 && c.r < 1.5, "{:?}", c);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let at = |wrap, u| {
+            row(wrap, TexelFilter::Nearest)
+                .lookup(vec2(u, 0.5), Vector2::zero(), Vector2::zero())
+                .r
+        };
+        assert_eq!(at(WrapMode::Repeat, 1.1), 0.0);
+        assert_eq!(at(WrapMode::Repeat, -0.1), 3.0);
+        assert_eq!(at(WrapMode::Clamp, 1.1), 3.0);
+        assert_eq!(at(WrapMode::Clamp, -5.0), 0.0);
+        assert_eq!(at(WrapMode::Mirror, 1.1), 3.0);
+        assert_eq!(at(WrapMode::Mirror, 1.3), 2.0);
+        assert_eq!(at(WrapMode::Mirror, -0.1), 0.0);
+    }
+
+    #[test]
+    fn filters() {
+        let at = |filter, u| {
+            row(WrapMode::Clamp, filter)
+                .get_value(&Vector3::zero(), &vec2(u, 0.5))
+                .r
+        };
+        //纹素中心在 (i + 0.5) / 4 处。
+        assert_eq!(at(TexelFilter::Nearest, 0.49), 1.0);
+        assert_relative_eq!(at(TexelFilter::Bilinear, 0.5), 1.5);
+        assert_relative_eq!(at(TexelFilter::Bilinear, 0.375), 1.0);
+        //Catmull-Rom 能精确重建线性变化。
+        assert_relative_eq!(at(TexelFilter::Bicubic, 0.5), 1.5, epsilon = 1e-5);
+        assert_relative_eq!(at(TexelFilter::Bicubic, 0.45), 1.3, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn mip_pyramid() {
+        let values: Vec<f32> = (0..15).map(|i| i as f32).collect();
+        let texture = ImageTexture::new(5, 3, gray(&values));
+        let sizes: Vec<_> = texture.levels.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, vec![(5, 3), (2, 1), (1, 1)]);
+        assert_relative_eq!(texture.levels[2].texels[0].r, 7.0, epsilon = 1e-5);
+
+        let huge = vec2(100.0, 0.0);
+        let c = texture.lookup(vec2(0.3, 0.3), huge, vec2(0.0, 100.0));
+        assert_relative_eq!(c.r, 7.0, epsilon = 1e-5);
+
+        //8x8 的棋盘格，每个像素盖住 4 个纹素时应该接近灰色。
+        let checker = (0..64)
+            .map(|i| ((i % 8 + i / 8) % 2) as f32)
+            .collect::<Vec<_>>();
+        let mut texture = ImageTexture::new(8, 8, gray(&checker));
+        let footprint = (vec2(0.5, 0.0), vec2(0.0, 0.5));
+        for &mip in &[MipFilter::Trilinear, MipFilter::Ewa] {
+            texture.mip = mip;
+            let c = texture.lookup(vec2(0.41, 0.63), footprint.0, footprint.1);
+            assert!((c.r - 0.5).abs() < 0.1, "{:?}: {:?}", mip, c);
+        }
+        texture.mip = MipFilter::None;
+        let c = texture.lookup(vec2(0.0625, 0.0625), footprint.0, footprint.1);
+        assert_eq!(c.r, 0.0);
+    }
+
+    #[test]
+    fn open() {
+        let name = format!("rrt_image_texture_{}.png", ::std::process::id());
+        let path = ::std::env::temp_dir().join(name);
+        let image = image::ImageBuffer::from_fn(2, 1, |x, _| image::Rgb {
+            data: [255 * x as u8, 0, 0],
+        });
+        image.save(&path).unwrap();
+        let texture = ImageTexture::open(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!((texture.width(), texture.height()), (2, 1));
+        assert_eq!(texture.levels[0].texels[1], Rgb::new(1.0, 0.0, 0.0));
+
+        assert!(ImageTexture::open("no/such/texture.png").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use math::*;
+use rgb::Rgb;
+use std::sync::Arc;
+use HitRecord;
+
+mod combine;
+mod mipmap;
+mod procedural;
+
+pub use self::combine::{Mix, Multiply, PositionTransform, Remap, UvTransform};
+pub use self::mipmap::{ImageTexture, MipFilter, TexelFilter, WrapMode};
+pub use self::procedural::{
+    Checkerboard, ColorRamp, Domain, Gradient, Grid, Marble, NoiseTexture, Stripes, Wood,
+};
+
+/// 交点处的位置和纹理坐标随屏幕上 x、y 方向移动一个像素的变化量，
+/// 纹理在这个范围内取平均就不会走样。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Footprint {
+    pub dpdx: Vector3,
+    pub dpdy: Vector3,
+    pub duvdx: Vector2,
+    pub duvdy: Vector2,
+}
+
+impl Footprint {
+    /// 只取一个点。
+    pub fn zero() -> Self {
+        Footprint {
+            dpdx: Vector3::zero(),
+            dpdy: Vector3::zero(),
+            duvdx: Vector2::zero(),
+            duvdy: Vector2::zero(),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Footprint::zero()
+    }
+}
+
+/// `pos` 是世界空间里的交点位置，`uv` 是形状给出的纹理坐标。
+pub trait Texture: Send + Sync {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb;
+
+    /// `footprint` 范围内的平均值。默认忽略范围，只取中心一点。
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, _footprint: &Footprint) -> Rgb {
+        self.get_value(pos, uv)
+    }
+
+    /// 交点处的值，需要重心坐标之类其他信息的纹理覆盖这个方法。
+    /// 默认按 `hit.footprint` 取 `get_filtered`。
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        self.get_filtered(&hit.pos, &hit.uv, &hit.footprint)
+    }
+}
+
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        (**self).get_value(pos, uv)
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        (**self).get_filtered(pos, uv, footprint)
+    }
+
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        (**self).get_at_hit(hit)
+    }
+}
+
+impl<T: Texture + ?Sized> Texture for Box<T> {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        (**self).get_value(pos, uv)
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        (**self).get_filtered(pos, uv, footprint)
+    }
+
+    fn get_at_hit(&self, hit: &HitRecord) -> Rgb {
+        (**self).get_at_hit(hit)
+    }
+}
+
+pub struct PureColorTexture {
+    pub color: Rgb,
+}
+
+impl Texture for PureColorTexture {
+    fn get_value(&self, _pos: &Vector3, _uv: &Vector2) -> Rgb {
+        self.color
+    }
+}
@@ -0,0 +1,415 @@
+use super::{Footprint, Texture};
+use math::*;
+use noise::{Fractal, Noise, Perlin};
+use rgb::Rgb;
+use std::f32;
+
+/// 分段线性的颜色渐变，把 [0, 1] 里的值映射成颜色，两端之外取端点的颜色。
+#[derive(Debug, Clone)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Rgb)>,
+}
+
+impl ColorRamp {
+    /// `stops` 是 `(位置, 颜色)`，顺序任意，至少要有一个。
+    pub fn new(mut stops: Vec<(f32, Rgb)>) -> Self {
+        assert!(!stops.is_empty(), "a color ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        ColorRamp { stops }
+    }
+
+    pub fn two(start: Rgb, end: Rgb) -> Self {
+        ColorRamp::new(vec![(0.0, start), (1.0, end)])
+    }
+
+    pub fn at(&self, t: f32) -> Rgb {
+        let i = self.stops.iter().position(|&(x, _)| x > t);
+        match i {
+            Some(0) => self.stops[0].1,
+            Some(i) => {
+                let ((x0, c0), (x1, c1)) = (self.stops[i - 1], self.stops[i]);
+                lerp((t - x0) / (x1 - x0), c0, c1)
+            }
+            None => self.stops[self.stops.len() - 1].1,
+        }
+    }
+}
+
+/// 黑到白。
+impl Default for ColorRamp {
+    fn default() -> Self {
+        ColorRamp::two(Rgb::black(), Rgb::white())
+    }
+}
+
+/// 图案取哪种坐标。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Domain {
+    /// 纹理坐标 `(u, v, 0)`，图案贴在表面上。
+    Uv,
+    /// 交点位置，图案像是从一整块材料里雕出来的。
+    Position,
+}
+
+impl Domain {
+    /// 坐标和它的维数。
+    fn point(self, pos: &Vector3, uv: &Vector2) -> (Vector3, usize) {
+        match self {
+            Domain::Uv => (uv.extend(0.0), 2),
+            Domain::Position => (*pos, 3),
+        }
+    }
+
+    /// 坐标随屏幕上 x、y 方向移动一个像素的变化量。
+    fn differentials(self, footprint: &Footprint) -> (Vector3, Vector3) {
+        match self {
+            Domain::Uv => (footprint.duvdx.extend(0.0), footprint.duvdy.extend(0.0)),
+            Domain::Position => (footprint.dpdx, footprint.dpdy),
+        }
+    }
+
+    /// 在每个分量上取平均的半宽。
+    fn filter_width(self, footprint: &Footprint) -> Vector3 {
+        let (dx, dy) = self.differentials(footprint);
+        vec3(
+            dx.x.abs().max(dy.x.abs()),
+            dx.y.abs().max(dy.y.abs()),
+            dx.z.abs().max(dy.z.abs()),
+        )
+    }
+}
+
+/// 周期为 `period`、每个周期开头长为 `width` 的一段为 1 其余为 0 的方波，从 0 到 `x` 的积分。
+fn pulse_integral(x: f32, period: f32, width: f32) -> f32 {
+    let n = (x / period).floor();
+    n * width + (x - n * period).min(width)
+}
+
+/// 上面的方波在 `[x - w, x + w]` 上的平均值，`w` 很小时就是 `x` 处的值。
+fn pulse_average(x: f32, w: f32, period: f32, width: f32) -> f32 {
+    //范围小到接近 `x` 的浮点精度时，两个积分相减只剩下舍入误差。
+    if w > 1e-4 * x.abs().max(1.0) {
+        let integral = pulse_integral(x + w, period, width) - pulse_integral(x - w, period, width);
+        (integral / (2.0 * w)).clamp(0.0, 1.0)
+    } else if x - (x / period).floor() * period < width {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// 噪声值从 `range` 线性映射到 [0, 1] 后经过 `ramp` 上色。
+pub struct NoiseTexture {
+    pub noise: Box<dyn Noise + Send + Sync>,
+    pub range: (f32, f32),
+    pub ramp: ColorRamp,
+}
+
+impl NoiseTexture {
+    /// 值域为 [-1, 1] 的噪声，例如 Perlin、单纯形和它们的 fBm。
+    pub fn new<N: Noise + Send + Sync + 'static>(noise: N) -> Self {
+        NoiseTexture {
+            noise: Box::new(noise),
+            range: (-1.0, 1.0),
+            ramp: ColorRamp::default(),
+        }
+    }
+
+    pub fn turbulence(seed: u64, octaves: u32) -> Self {
+        NoiseTexture {
+            range: (0.0, 1.0),
+            ..NoiseTexture::new(Fractal::turbulence(Perlin::new(seed), octaves))
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn get_value(&self, pos: &Vector3, _uv: &Vector2) -> Rgb {
+        let (low, high) = self.range;
+        self.ramp.at((self.noise.noise3(*pos) - low) / (high - low))
+    }
+}
+
+/// 大理石：沿 x 轴的正弦条纹被湍流扰动，周期是 2。
+pub struct Marble {
+    pub noise: Box<dyn Noise + Send + Sync>,
+    /// 扰动的强度。
+    pub strength: f32,
+    pub ramp: ColorRamp,
+}
+
+impl Marble {
+    pub fn new(seed: u64) -> Self {
+        Marble {
+            noise: Box::new(Fractal::turbulence(Perlin::new(seed), 6)),
+            strength: 4.0,
+            ramp: ColorRamp::default(),
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn get_value(&self, pos: &Vector3, _uv: &Vector2) -> Rgb {
+        let phase = (pos.x + self.strength * self.noise.noise3(*pos)) * f32::consts::PI;
+        self.ramp.at(0.5 + 0.5 * phase.sin())
+    }
+}
+
+/// 木纹：绕 y 轴的同心年轮，间距为 1，被噪声扰动。`ramp` 从年轮内侧到外侧。
+pub struct Wood {
+    pub noise: Box<dyn Noise + Send + Sync>,
+    pub strength: f32,
+    pub ramp: ColorRamp,
+}
+
+impl Wood {
+    pub fn new(seed: u64) -> Self {
+        Wood {
+            noise: Box::new(Fractal::fbm(Perlin::new(seed), 3)),
+            strength: 0.3,
+            ramp: ColorRamp::default(),
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn get_value(&self, pos: &Vector3, _uv: &Vector2) -> Rgb {
+        let r = vec2(pos.x, pos.z).magnitude() + self.strength * self.noise.noise3(*pos);
+        self.ramp.at(r - r.floor())
+    }
+}
+
+/// 边长为 1 的棋盘格。
+pub struct Checkerboard {
+    pub even: Box<dyn Texture>,
+    pub odd: Box<dyn Texture>,
+    pub domain: Domain,
+}
+
+impl Texture for Checkerboard {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        let (p, dims) = self.domain.point(pos, uv);
+        let sum: i64 = (0..dims).map(|k| p[k].floor() as i64).sum();
+        if sum & 1 == 0 {
+            self.even.get_value(pos, uv)
+        } else {
+            self.odd.get_value(pos, uv)
+        }
+    }
+
+    /// 每一维上落在奇数格的比例为 `a`，坐标之和为奇数的比例是 `(1 - Π(1 - 2a)) / 2`。
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        if footprint.is_zero() {
+            return self.get_value(pos, uv);
+        }
+        let (p, dims) = self.domain.point(pos, uv);
+        let w = self.domain.filter_width(footprint);
+        let even = (0..dims)
+            .map(|k| 1.0 - 2.0 * pulse_average(p[k] - 1.0, w[k], 2.0, 1.0))
+            .product::<f32>();
+        let odd = (1.0 - even) / 2.0;
+        self.even.get_filtered(pos, uv, footprint) * (1.0 - odd)
+            + self.odd.get_filtered(pos, uv, footprint) * odd
+    }
+}
+
+/// 间距为 1 的网格线，`width` 是线宽占格子边长的比例。
+pub struct Grid {
+    pub line: Box<dyn Texture>,
+    pub fill: Box<dyn Texture>,
+    pub width: f32,
+    pub domain: Domain,
+}
+
+impl Texture for Grid {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        let (p, dims) = self.domain.point(pos, uv);
+        let on_line = (0..dims).any(|k| {
+            let f = p[k] - p[k].floor();
+            f < self.width / 2.0 || f > 1.0 - self.width / 2.0
+        });
+        if on_line {
+            self.line.get_value(pos, uv)
+        } else {
+            self.fill.get_value(pos, uv)
+        }
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        if footprint.is_zero() {
+            return self.get_value(pos, uv);
+        }
+        let (p, dims) = self.domain.point(pos, uv);
+        let w = self.domain.filter_width(footprint);
+        let fill = (0..dims)
+            .map(|k| 1.0 - pulse_average(p[k] + self.width / 2.0, w[k], 1.0, self.width))
+            .product::<f32>();
+        self.line.get_filtered(pos, uv, footprint) * (1.0 - fill)
+            + self.fill.get_filtered(pos, uv, footprint) * fill
+    }
+}
+
+/// 垂直于 `direction` 的条纹，沿 `direction` 每隔 `1 / |direction|` 交替一次。
+pub struct Stripes {
+    pub a: Box<dyn Texture>,
+    pub b: Box<dyn Texture>,
+    pub direction: Vector3,
+    pub domain: Domain,
+}
+
+impl Texture for Stripes {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        let (p, _) = self.domain.point(pos, uv);
+        if (p.dot(self.direction).floor() as i64) & 1 == 0 {
+            self.a.get_value(pos, uv)
+        } else {
+            self.b.get_value(pos, uv)
+        }
+    }
+
+    fn get_filtered(&self, pos: &Vector3, uv: &Vector2, footprint: &Footprint) -> Rgb {
+        if footprint.is_zero() {
+            return self.get_value(pos, uv);
+        }
+        let (p, _) = self.domain.point(pos, uv);
+        let (dx, dy) = self.domain.differentials(footprint);
+        let d = self.direction;
+        let w = d.dot(dx).abs().max(d.dot(dy).abs());
+        let b = pulse_average(p.dot(d) - 1.0, w, 2.0, 1.0);
+        self.a.get_filtered(pos, uv, footprint) * (1.0 - b)
+            + self.b.get_filtered(pos, uv, footprint) * b
+    }
+}
+
+/// 沿 `direction` 的线性渐变，`p · direction` 从 0 到 1 对应 `ramp` 的两端。
+pub struct Gradient {
+    pub ramp: ColorRamp,
+    pub direction: Vector3,
+    pub domain: Domain,
+}
+
+impl Texture for Gradient {
+    fn get_value(&self, pos: &Vector3, uv: &Vector2) -> Rgb {
+        let (p, _) = self.domain.point(pos, uv);
+        self.ramp.at(p.dot(self.direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use texture::PureColorTexture;
+
+    fn color(c: f32) -> Box<dyn Texture> {
+        Box::new(PureColorTexture {
+            color: Rgb::new(c, c, c),
+        })
+    }
+
+    #[test]
+    fn ramp_stops() {
+        let ramp = ColorRamp::new(vec![
+            (1.0, Rgb::white()),
+            (0.0, Rgb::black()),
+            (0.5, Rgb::new(1.0, 0.0, 0.0)),
+        ]);
+        assert_eq!(ramp.at(-1.0), Rgb::black());
+        assert_eq!(ramp.at(0.25), Rgb::new(0.5, 0.0, 0.0));
+        assert_eq!(ramp.at(0.75), Rgb::new(1.0, 0.5, 0.5));
+        assert_eq!(ramp.at(2.0), Rgb::white());
+    }
+
+    #[test]
+    fn patterns() {
+        let checker = Checkerboard {
+            even: color(0.0),
+            odd: color(1.0),
+            domain: Domain::Position,
+        };
+        let at = |t: &dyn Texture, p: Vector3| t.get_value(&p, &Vector2::zero()).r;
+        assert_eq!(at(&checker, vec3(0.5, 0.5, 0.5)), 0.0);
+        assert_eq!(at(&checker, vec3(1.5, 0.5, 0.5)), 1.0);
+        assert_eq!(at(&checker, vec3(-0.5, 0.5, 0.5)), 1.0);
+
+        let grid = Grid {
+            line: color(1.0),
+            fill: color(0.0),
+            width: 0.1,
+            domain: Domain::Uv,
+        };
+        let uv = |t: &dyn Texture, u, v| t.get_value(&Vector3::zero(), &vec2(u, v)).r;
+        assert_eq!(uv(&grid, 0.5, 0.5), 0.0);
+        assert_eq!(uv(&grid, 0.98, 0.5), 1.0);
+        assert_eq!(uv(&grid, 0.5, 0.02), 1.0);
+
+        let stripes = Stripes {
+            a: color(0.0),
+            b: color(1.0),
+            direction: vec3(0.0, 2.0, 0.0),
+            domain: Domain::Position,
+        };
+        assert_eq!(at(&stripes, vec3(5.0, 0.4, 0.0)), 0.0);
+        assert_eq!(at(&stripes, vec3(5.0, 0.6, 0.0)), 1.0);
+
+        let gradient = Gradient {
+            ramp: ColorRamp::default(),
+            direction: vec3(0.0, 0.0, 0.5),
+            domain: Domain::Position,
+        };
+        assert_relative_eq!(at(&gradient, vec3(0.0, 0.0, 1.0)), 0.5);
+    }
+
+    #[test]
+    fn filtered_patterns() {
+        let checker = Checkerboard {
+            even: color(0.0),
+            odd: color(1.0),
+            domain: Domain::Uv,
+        };
+        let footprint = |w: f32| Footprint {
+            duvdx: vec2(w, 0.0),
+            duvdy: vec2(0.0, w),
+            ..Footprint::zero()
+        };
+        let at = |t: &dyn Texture, u, v, w| {
+            t.get_filtered(&Vector3::zero(), &vec2(u, v), &footprint(w))
+                .r
+        };
+        assert_eq!(at(&checker, 1.5, 0.5, 0.0), 1.0);
+        //范围在一个格子里面时不变，盖住很多格子时是平均值。
+        assert_relative_eq!(at(&checker, 1.5, 0.5, 0.25), 1.0);
+        assert_relative_eq!(at(&checker, 1.5, 0.5, 20.3), 0.5, epsilon = 0.05);
+        assert_relative_eq!(at(&checker, 1.0, 0.5, 0.25), 0.5);
+
+        let grid = Grid {
+            line: color(1.0),
+            fill: color(0.0),
+            width: 0.1,
+            domain: Domain::Uv,
+        };
+        assert_eq!(at(&grid, 0.5, 0.5, 0.2), 0.0);
+        assert_relative_eq!(at(&grid, 0.5, 0.5, 10.0), 1.0 - 0.9 * 0.9, epsilon = 1e-4);
+
+        let stripes = Stripes {
+            a: color(0.0),
+            b: color(1.0),
+            direction: vec3(4.0, 0.0, 0.0),
+            domain: Domain::Uv,
+        };
+        assert_relative_eq!(at(&stripes, 0.3, 0.5, 1.0), 0.5, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn noise_patterns_stay_in_ramp() {
+        let marble = Marble::new(1);
+        let wood = Wood::new(2);
+        let turbulence = NoiseTexture::turbulence(3, 4);
+        for i in 0..100 {
+            let p = vec3(i as f32 * 0.37, (i as f32 * 0.71).sin(), i as f32 * -0.13);
+            for t in &[&marble as &dyn Texture, &wood, &turbulence] {
+                let c = t.get_value(&p, &Vector2::zero()).r;
+                assert!((0.0..=1.0).contains(&c), "{}", c);
+            }
+        }
+    }
+}
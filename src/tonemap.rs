@@ -0,0 +1,130 @@
+extern crate image;
+
+use rgb::Rgb;
+
+/// 把高动态范围的线性值压到 `[0, 1]` 的算子。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    /// 直接截断。
+    Clamp,
+    /// 按亮度做 `L / (1 + L)`，保持色相。
+    Reinhard,
+    /// 亮度为 `white` 时映射到 1 的 Reinhard。
+    ReinhardExtended { white: f32 },
+    /// Narkowicz 对 ACES 电影曲线的拟合。
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(&self, c: Rgb) -> Rgb {
+        let mapped = match *self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => ToneMap::scale_luminance(c, |l| l / (1.0 + l)),
+            ToneMap::ReinhardExtended { white } => {
+                ToneMap::scale_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMap::Aces => {
+                let aces = |x: f32| {
+                    let x = x.max(0.0);
+                    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+                };
+                Rgb::new(aces(c.r), aces(c.g), aces(c.b))
+            }
+        };
+        Rgb::new(
+            mapped.r.clamp(0.0, 1.0),
+            mapped.g.clamp(0.0, 1.0),
+            mapped.b.clamp(0.0, 1.0),
+        )
+    }
+
+    fn scale_luminance<F: Fn(f32) -> f32>(c: Rgb, f: F) -> Rgb {
+        let l = c.luminance();
+        if l <= 0.0 {
+            Rgb::black()
+        } else {
+            c * (f(l) / l)
+        }
+    }
+}
+
+/// 输出流程：曝光、色调映射，最后做 sRGB 编码并量化到 8 位。
+#[derive(Debug, Clone, Copy)]
+pub struct ToneMapper {
+    /// 以档为单位，颜色先乘上 `2^exposure`。
+    pub exposure: f32,
+    pub operator: ToneMap,
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        ToneMapper {
+            exposure: 0.0,
+            operator: ToneMap::Clamp,
+        }
+    }
+}
+
+impl ToneMapper {
+    /// 映射后的线性值，在 `[0, 1]` 内。
+    pub fn map(&self, c: Rgb) -> Rgb {
+        self.operator.apply(c * self.exposure.exp2())
+    }
+
+    pub fn encode(&self, c: Rgb) -> image::Rgb<u8> {
+        self.map(c).into()
+    }
+
+    /// `pixels` 按行存放，第 0 行在最上面。
+    pub fn to_image(&self, width: u32, height: u32, pixels: &[Rgb]) -> image::RgbImage {
+        image::ImageBuffer::from_fn(width, height, |x, y| {
+            self.encode(pixels[(y * width + x) as usize])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use rgb::{linear_to_srgb, srgb_to_linear};
+
+    #[test]
+    fn srgb_roundtrip() {
+        for i in 0..=255u8 {
+            let encoded: image::Rgb<u8> = Rgb::from(image::Rgb { data: [i, i, i] }).into();
+            assert_eq!(encoded.data[0], i);
+        }
+        assert_relative_eq!(linear_to_srgb(srgb_to_linear(0.3)), 0.3, epsilon = 1e-6);
+        //中灰在 sRGB 下约为 188。
+        let gray: image::Rgb<u8> = Rgb::new(0.5, 2.0, -1.0).into();
+        assert_eq!(gray.data, [188, 255, 0]);
+    }
+
+    #[test]
+    fn operators_stay_in_range() {
+        let bright = Rgb::new(40.0, 10.0, 0.5);
+        for op in &[
+            ToneMap::Clamp,
+            ToneMap::Reinhard,
+            ToneMap::ReinhardExtended { white: 4.0 },
+            ToneMap::Aces,
+        ] {
+            let c = op.apply(bright);
+            for v in &[c.r, c.g, c.b] {
+                assert!(*v >= 0.0 && *v <= 1.0);
+            }
+        }
+        let white = ToneMap::ReinhardExtended { white: 4.0 }.apply(Rgb::new(4.0, 4.0, 4.0));
+        assert_relative_eq!(white.g, 1.0, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let mapper = ToneMapper {
+            exposure: 1.0,
+            operator: ToneMap::Clamp,
+        };
+        assert_relative_eq!(mapper.map(Rgb::new(0.25, 0.0, 0.0)).r, 0.5);
+    }
+}
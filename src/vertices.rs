@@ -1,7 +1,20 @@
 use math::{Vector2, Vector3};
+use rgb::Rgb;
 
-pub trait Vertex {
+pub trait Vertex: Send + Sync {
     fn get_pos(&self) -> &Vector3;
+
+    fn get_uv(&self) -> Option<&Vector2> {
+        None
+    }
+
+    fn get_normal(&self) -> Option<&Vector3> {
+        None
+    }
+
+    fn get_color(&self) -> Option<&Rgb> {
+        None
+    }
 }
 
 macro_rules! impl_vertex {
@@ -12,26 +25,129 @@ macro_rules! impl_vertex {
             }
         }
     };
+    ($type: ty, uv) => {
+        impl Vertex for $type {
+            fn get_pos(&self) -> &Vector3 {
+                &self.pos
+            }
+
+            fn get_uv(&self) -> Option<&Vector2> {
+                Some(&self.uv)
+            }
+        }
+    };
+    ($type: ty, normal) => {
+        impl Vertex for $type {
+            fn get_pos(&self) -> &Vector3 {
+                &self.pos
+            }
+
+            fn get_normal(&self) -> Option<&Vector3> {
+                Some(&self.normal)
+            }
+        }
+    };
+    ($type: ty, uv, normal) => {
+        impl Vertex for $type {
+            fn get_pos(&self) -> &Vector3 {
+                &self.pos
+            }
+
+            fn get_uv(&self) -> Option<&Vector2> {
+                Some(&self.uv)
+            }
+
+            fn get_normal(&self) -> Option<&Vector3> {
+                Some(&self.normal)
+            }
+        }
+    };
+}
+
+/// 只有位置的顶点。
+impl Vertex for Vector3 {
+    fn get_pos(&self) -> &Vector3 {
+        self
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VertexUV {
     pos: Vector3,
     uv: Vector2,
 }
 
-impl_vertex!(VertexUV);
+impl VertexUV {
+    pub fn new(pos: Vector3, uv: Vector2) -> Self {
+        VertexUV { pos, uv }
+    }
+}
+
+impl_vertex!(VertexUV, uv);
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VertexNormal {
     pos: Vector3,
-    uv: Vector2,
+    normal: Vector3,
+}
+
+impl VertexNormal {
+    pub fn new(pos: Vector3, normal: Vector3) -> Self {
+        VertexNormal { pos, normal }
+    }
 }
 
-impl_vertex!(VertexNormal);
+impl_vertex!(VertexNormal, normal);
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VertexUvn {
     pos: Vector3,
     normal: Vector3,
     uv: Vector2,
 }
 
-impl_vertex!(VertexUvn);
+impl VertexUvn {
+    pub fn new(pos: Vector3, normal: Vector3, uv: Vector2) -> Self {
+        VertexUvn { pos, normal, uv }
+    }
+}
+
+impl_vertex!(VertexUvn, uv, normal);
+
+/// 每个属性都可有可无的顶点，用于属性组合事先不确定的格式，比如 PLY。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexAttributes {
+    pub pos: Vector3,
+    pub normal: Option<Vector3>,
+    pub uv: Option<Vector2>,
+    pub color: Option<Rgb>,
+}
+
+impl VertexAttributes {
+    pub fn new(pos: Vector3) -> Self {
+        VertexAttributes {
+            pos,
+            normal: None,
+            uv: None,
+            color: None,
+        }
+    }
+}
+
+impl Vertex for VertexAttributes {
+    fn get_pos(&self) -> &Vector3 {
+        &self.pos
+    }
+
+    fn get_uv(&self) -> Option<&Vector2> {
+        self.uv.as_ref()
+    }
+
+    fn get_normal(&self) -> Option<&Vector3> {
+        self.normal.as_ref()
+    }
+
+    fn get_color(&self) -> Option<&Rgb> {
+        self.color.as_ref()
+    }
+}